@@ -1,10 +1,16 @@
 use clap::{Parser, Subcommand};
+use futures_util::StreamExt;
 use librepods_core::*;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "librepods")]
 #[command(about = "Apple AirPods Control Framework", long_about = None)]
 struct Cli {
+    /// Talk to a simulated pair of AirPods instead of real hardware
+    #[arg(long, global = true)]
+    simulate: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -21,12 +27,23 @@ enum Commands {
     Status { id: String },
     /// Set ANC mode
     Anc { id: String, mode: String },
+    /// Dissect a raw AAP frame given as hex
+    Decode {
+        hex: String,
+        /// Print the dissection as JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[tokio::main]
 async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
+    if cli.simulate {
+        return simulate(cli.command).await;
+    }
+
     match cli.command {
         Commands::Scan => {
             println!("Scanning for AirPods devices...");
@@ -43,7 +60,102 @@ async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
         Commands::Anc { id, mode } => {
             println!("Setting ANC mode to {} for device: {}", mode, id);
         }
+        Commands::Decode { hex, json } => {
+            let dissection = Dissector::new().dissect_hex(&hex)?;
+            if json {
+                println!("{}", dissection.to_json()?);
+            } else {
+                print!("{}", dissection);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse an ANC mode name as accepted on the command line
+fn parse_anc_mode(mode: &str) -> Result<AncMode> {
+    match mode.to_ascii_lowercase().as_str() {
+        "off" => Ok(AncMode::Off),
+        "active" | "on" => Ok(AncMode::Active),
+        "transparency" => Ok(AncMode::Transparency),
+        "adaptive" => Ok(AncMode::Adaptive),
+        _ => Err(Error::ParseError(format!("unknown ANC mode: {}", mode))),
+    }
+}
+
+/// Battery levels from an advertisement, such as `L 80%  R 90%  case -`
+fn advertised_levels(pairing: &advertisement::ProximityPairing) -> String {
+    let level = |level: Option<u8>, charging: bool| match level {
+        Some(level) if charging => format!("{}%+", level),
+        Some(level) => format!("{}%", level),
+        None => "-".to_string(),
+    };
+    format!(
+        "L {}  R {}  case {}",
+        level(pairing.left, pairing.left_charging),
+        level(pairing.right, pairing.right_charging),
+        level(pairing.case, pairing.case_charging)
+    )
+}
+
+/// Run a command against the demo devices of a [`SimulatedBackend`]
+async fn simulate(command: Commands) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let simulator = SimulatedBackend::demo();
+    let engine = Arc::new(Engine::with_backend(simulator.clone()));
+    for device in simulator.devices() {
+        engine.register_device(Device::new(
+            device.address().to_string(),
+            device.name().to_string(),
+            device.model(),
+        ));
+    }
+    let pump = simulator.run(Duration::from_millis(20));
+
+    match command {
+        Commands::Scan => {
+            println!("Scanning for simulated AirPods devices...");
+            let mut seen = engine.scan().await?.take(simulator.devices().len());
+            while let Some(device) = seen.next().await {
+                let model = engine
+                    .get_device(&device.address)
+                    .map(|known| format!(" ({:?})", known.model()))
+                    .unwrap_or_default();
+                let battery = device
+                    .proximity_pairing()
+                    .map(|pairing| format!("  {}", advertised_levels(&pairing)))
+                    .unwrap_or_default();
+                println!("{}  {}{}{}", device.address, device.name, model, battery);
+            }
+            engine.stop_scan().await?;
+        }
+        Commands::Connect { id } => {
+            engine.connect(&id).await?;
+            println!("Connected to simulated device: {}", id);
+        }
+        Commands::Disconnect { id } => {
+            engine.connect(&id).await?;
+            engine.disconnect(&id).await?;
+            println!("Disconnected from simulated device: {}", id);
+        }
+        Commands::Status { id } => {
+            engine.connect(&id).await?;
+            engine.battery(&id).await?;
+            let device = engine.get_device(&id).ok_or(Error::DeviceNotConnected)?;
+            println!("{}", serde_json::to_string_pretty(&device.snapshot())?);
+        }
+        Commands::Anc { id, mode } => {
+            let mode = parse_anc_mode(&mode)?;
+            engine.connect(&id).await?;
+            engine.set_anc(&id, mode).await?;
+            println!("Set ANC mode to {:?} for simulated device: {}", mode, id);
+        }
+        Commands::Decode { .. } => {
+            pump.abort();
+            return Err("decode does not use a device; run it without --simulate".into());
+        }
     }
 
+    pump.abort();
     Ok(())
 }
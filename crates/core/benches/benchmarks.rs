@@ -16,14 +16,14 @@ fn bench_device_creation(c: &mut Criterion) {
 fn bench_message_serialization(c: &mut Criterion) {
     c.bench_function("serialize_message", |b| {
         let msg = Message::new(MessageType::BatteryStatus, vec![50, 60, 70]);
-        b.iter(|| msg.serialize())
+        b.iter(|| msg.serialize().unwrap())
     });
 }
 
 fn bench_engine_operations(c: &mut Criterion) {
     c.bench_function("register_device", |b| {
         b.iter(|| {
-            let mut engine = Engine::new();
+            let engine = Engine::new();
             let device = Device::new(
                 black_box("test_id".to_string()),
                 black_box("Test AirPods".to_string()),
@@ -34,5 +34,10 @@ fn bench_engine_operations(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_device_creation, bench_message_serialization, bench_engine_operations);
+criterion_group!(
+    benches,
+    bench_device_creation,
+    bench_message_serialization,
+    bench_engine_operations
+);
 criterion_main!(benches);
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use librepods_core::dissector::parse_hex;
+use librepods_core::{Dissector, FrameVersion};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Dissector::new().dissect(data).to_json();
+    let _ = Dissector::new().with_version(FrameVersion::V2).dissect(data).to_text();
+    if let Ok(hex) = std::str::from_utf8(data) {
+        let _ = parse_hex(hex);
+    }
+});
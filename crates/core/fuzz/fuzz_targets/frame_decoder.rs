@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use librepods_core::FrameDecoder;
+
+fuzz_target!(|data: &[u8]| {
+    // First byte picks the chunk size so fragmentation is explored too
+    let Some((&chunk, stream)) = data.split_first() else {
+        return;
+    };
+    let mut decoder = FrameDecoder::new();
+    for piece in stream.chunks(chunk as usize + 1) {
+        let _ = decoder.push(piece);
+    }
+    let _ = decoder.finish();
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use librepods_core::{FrameVersion, Message};
+
+fuzz_target!(|data: &[u8]| {
+    for version in FrameVersion::SUPPORTED {
+        if let Ok(msg) = Message::parse_versioned(data, version) {
+            let bytes = msg.serialize_versioned(version).unwrap();
+            assert_eq!(Message::parse_versioned(&bytes, version).unwrap(), msg);
+        }
+    }
+});
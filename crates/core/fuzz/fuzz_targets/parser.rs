@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use librepods_core::parser::parse_message;
+use librepods_core::Message;
+
+fuzz_target!(|data: &[u8]| {
+    let nom = parse_message(data).map(|(_, msg)| msg).ok();
+    assert_eq!(nom, Message::parse(data).ok());
+});
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use librepods_core::{MessageType, Payload};
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&opcode, body)) = data.split_first() else {
+        return;
+    };
+    if let Ok(payload) = Payload::decode(MessageType::from_opcode(opcode), body) {
+        let _ = payload.encode();
+    }
+});
@@ -0,0 +1,292 @@
+//! Apple proximity pairing advertisements
+//!
+//! AirPods advertise manufacturer data under Apple's company identifier
+//! while they are out of the case or the lid is open. The proximity pairing
+//! message in it (type `0x07`) carries the model, battery levels in steps
+//! of ten percent, charging flags and the lid state, so batteries can be
+//! read from a scan without connecting.
+//!
+//! Layout after the company identifier:
+//!
+//! | Offset | Content                                                  |
+//! |--------|----------------------------------------------------------|
+//! | 0      | Message type, `0x07`                                     |
+//! | 1      | Length of the rest of the message                        |
+//! | 2      | Prefix, `0x01` once paired                               |
+//! | 3..5   | Model id, little-endian                                  |
+//! | 5      | Status; bit 5 set when the left bud is the primary       |
+//! | 6      | Bud levels, one nibble each, primary bud in the low one  |
+//! | 7      | Charging flags in the high nibble, case level in the low |
+//! | 8      | Lid; bit 3 clear while the lid is open                   |
+//! | 9      | Colour                                                   |
+//! | 10..   | Encrypted, not decoded                                   |
+//!
+//! Levels run from 0 to 10; 15 means the component is out of range, such as
+//! a case that has been closed or a bud that is missing.
+
+use crate::device::DeviceModel;
+use crate::error::{Error, Result};
+use crate::state::BatteryInfo;
+use serde::{Deserialize, Serialize};
+
+/// Apple's Bluetooth SIG company identifier
+pub const APPLE_COMPANY_ID: u16 = 0x004C;
+
+/// Message type of proximity pairing within Apple's manufacturer data
+pub const PROXIMITY_PAIRING_TYPE: u8 = 0x07;
+
+/// Bytes up to and including the lid state
+const MIN_LENGTH: usize = 9;
+
+/// Length byte AirPods send, covering the encrypted tail too
+const ADVERTISED_LENGTH: u8 = 0x19;
+
+const LEFT_PRIMARY: u8 = 0x20;
+const LID_CLOSED: u8 = 0x08;
+const UNKNOWN_LEVEL: u8 = 0x0F;
+
+/// Model ids advertised by each supported model
+const MODEL_IDS: [(u16, DeviceModel); 11] = [
+    (0x200F, DeviceModel::AirPods2),
+    (0x2013, DeviceModel::AirPods3),
+    (0x2019, DeviceModel::AirPods4),
+    (0x201B, DeviceModel::AirPods4),
+    (0x200E, DeviceModel::AirPodsProGen1),
+    (0x2014, DeviceModel::AirPodsProGen2),
+    (0x2024, DeviceModel::AirPodsProGen2),
+    (0x2027, DeviceModel::AirPodsProGen3),
+    (0x200A, DeviceModel::AirPodsMax),
+    (0x201F, DeviceModel::AirPodsMax),
+    (0x2012, DeviceModel::BeatsFitPro),
+];
+
+/// The model advertising `id`, if it is one this crate supports
+pub fn model_from_id(id: u16) -> Option<DeviceModel> {
+    MODEL_IDS
+        .iter()
+        .find(|(known, _)| *known == id)
+        .map(|(_, model)| *model)
+}
+
+/// The id a model advertises; for models sold in several versions, the first
+pub fn model_id(model: DeviceModel) -> u16 {
+    MODEL_IDS
+        .iter()
+        .find(|(_, known)| *known == model)
+        .map(|(id, _)| *id)
+        .unwrap_or_default()
+}
+
+/// Decoded proximity pairing message
+///
+/// Levels are percentages, `None` when the component is out of range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProximityPairing {
+    /// Model id as advertised
+    pub model_id: u16,
+    /// The model `model_id` stands for, if supported
+    pub model: Option<DeviceModel>,
+    /// Left bud level
+    pub left: Option<u8>,
+    /// Right bud level
+    pub right: Option<u8>,
+    /// Case level
+    pub case: Option<u8>,
+    /// Whether the left bud is charging
+    pub left_charging: bool,
+    /// Whether the right bud is charging
+    pub right_charging: bool,
+    /// Whether the case is charging
+    pub case_charging: bool,
+    /// Whether the case lid is open
+    pub lid_open: bool,
+}
+
+impl ProximityPairing {
+    /// Decode Apple manufacturer data, starting at the message type
+    ///
+    /// Fails with `ParseError` for other Apple messages and
+    /// `InvalidLength` when the message is cut short.
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        match data.first() {
+            Some(&PROXIMITY_PAIRING_TYPE) => {}
+            Some(other) => {
+                return Err(Error::ParseError(format!(
+                    "not a proximity pairing message: type {:#04x}",
+                    other
+                )))
+            }
+            None => return Err(Error::InvalidLength),
+        }
+        if data.len() < MIN_LENGTH || usize::from(data[1]) + 2 < MIN_LENGTH {
+            return Err(Error::InvalidLength);
+        }
+
+        let model_id = u16::from_le_bytes([data[3], data[4]]);
+        let left_primary = data[5] & LEFT_PRIMARY != 0;
+        let (primary, secondary) = (data[6] & 0x0F, data[6] >> 4);
+        let (left, right) = if left_primary {
+            (primary, secondary)
+        } else {
+            (secondary, primary)
+        };
+        let charging = data[7] >> 4;
+        let (left_charging, right_charging) = if left_primary {
+            (charging & 0x01 != 0, charging & 0x02 != 0)
+        } else {
+            (charging & 0x02 != 0, charging & 0x01 != 0)
+        };
+
+        Ok(Self {
+            model_id,
+            model: model_from_id(model_id),
+            left: level(left),
+            right: level(right),
+            case: level(data[7] & 0x0F),
+            left_charging,
+            right_charging,
+            case_charging: charging & 0x04 != 0,
+            lid_open: data[8] & LID_CLOSED == 0,
+        })
+    }
+
+    /// Encode as AirPods advertise it, the left bud being the primary
+    ///
+    /// Levels are rounded to the nearest ten percent and the encrypted tail
+    /// is left zeroed.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = vec![0; 2 + usize::from(ADVERTISED_LENGTH)];
+        data[0] = PROXIMITY_PAIRING_TYPE;
+        data[1] = ADVERTISED_LENGTH;
+        data[2] = 0x01;
+        data[3..5].copy_from_slice(&self.model_id.to_le_bytes());
+        data[5] = LEFT_PRIMARY;
+        data[6] = nibble(self.left) | nibble(self.right) << 4;
+        let charging = u8::from(self.left_charging)
+            | u8::from(self.right_charging) << 1
+            | u8::from(self.case_charging) << 2;
+        data[7] = nibble(self.case) | charging << 4;
+        data[8] = if self.lid_open { 0 } else { LID_CLOSED };
+        data
+    }
+
+    /// The levels as a battery report
+    ///
+    /// `None` unless at least one bud is in range. Components out of range
+    /// read as 0, and the report counts as charging if anything is.
+    pub fn battery_info(&self) -> Option<BatteryInfo> {
+        if self.left.is_none() && self.right.is_none() {
+            return None;
+        }
+        Some(BatteryInfo {
+            left_bud: self.left.unwrap_or(0),
+            right_bud: self.right.unwrap_or(0),
+            case: self.case.unwrap_or(0),
+            is_charging: self.left_charging || self.right_charging || self.case_charging,
+        })
+    }
+}
+
+fn level(nibble: u8) -> Option<u8> {
+    (nibble <= 10).then_some(nibble * 10)
+}
+
+fn nibble(level: Option<u8>) -> u8 {
+    level.map_or(UNKNOWN_LEVEL, |level| (level.min(100) + 5) / 10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// AirPods Pro 2 with the right bud as primary: left 80%, right 90%,
+    /// case 50% and charging, lid open
+    const PRO_2: [u8; 27] = [
+        0x07, 0x19, 0x01, 0x14, 0x20, 0x0B, 0x89, 0x45, 0x31, 0x00, 0x05, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_decode() {
+        let decoded = ProximityPairing::decode(&PRO_2).unwrap();
+        assert_eq!(decoded.model, Some(DeviceModel::AirPodsProGen2));
+        assert_eq!(
+            (decoded.left, decoded.right, decoded.case),
+            (Some(80), Some(90), Some(50))
+        );
+        assert!(decoded.case_charging && !decoded.left_charging && !decoded.right_charging);
+        assert!(decoded.lid_open);
+        assert_eq!(
+            decoded.battery_info(),
+            Some(BatteryInfo {
+                left_bud: 80,
+                right_bud: 90,
+                case: 50,
+                is_charging: true,
+            })
+        );
+    }
+
+    #[test]
+    fn test_out_of_range_and_unknown_model() {
+        let mut data = PRO_2;
+        data[3..5].copy_from_slice(&0x2002u16.to_le_bytes());
+        data[6] = 0xF7;
+        data[7] = 0x0F;
+        data[8] = LID_CLOSED;
+        let decoded = ProximityPairing::decode(&data).unwrap();
+        assert_eq!((decoded.model_id, decoded.model), (0x2002, None));
+        assert_eq!(
+            (decoded.left, decoded.right, decoded.case),
+            (None, Some(70), None)
+        );
+        assert!(!decoded.lid_open);
+        assert_eq!(decoded.battery_info().unwrap().left_bud, 0);
+
+        data[6] = 0xFF;
+        assert_eq!(
+            ProximityPairing::decode(&data).unwrap().battery_info(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_rejects_other_messages() {
+        assert_eq!(ProximityPairing::decode(&[]), Err(Error::InvalidLength));
+        assert_eq!(
+            ProximityPairing::decode(&PRO_2[..8]),
+            Err(Error::InvalidLength)
+        );
+        assert!(matches!(
+            ProximityPairing::decode(&[0x10, 0x05, 0x01, 0x18, 0x00, 0x00, 0x00]),
+            Err(Error::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let pairing = ProximityPairing {
+            model_id: model_id(DeviceModel::AirPodsMax),
+            model: Some(DeviceModel::AirPodsMax),
+            left: Some(40),
+            right: Some(100),
+            case: None,
+            left_charging: true,
+            right_charging: false,
+            case_charging: false,
+            lid_open: false,
+        };
+        let data = pairing.encode();
+        assert_eq!(data.len(), 27);
+        assert_eq!(ProximityPairing::decode(&data).unwrap(), pairing);
+
+        let rounded = ProximityPairing {
+            left: Some(84),
+            ..pairing
+        };
+        assert_eq!(
+            ProximityPairing::decode(&rounded.encode()).unwrap().left,
+            Some(80)
+        );
+    }
+}
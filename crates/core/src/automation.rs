@@ -0,0 +1,329 @@
+//! Rules that react to ear detection changes
+
+use crate::error::Result;
+use crate::events::{Event, EventBus, EventFilter, EventKind, EventType, RecvError};
+use crate::models::EarDetectionState;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+/// Future returned by [`EarAction::run`]
+pub type ActionFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
+
+/// A change between two ear detection states of one device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EarTransition {
+    /// State before the change
+    pub from: EarDetectionState,
+    /// State after the change
+    pub to: EarDetectionState,
+}
+
+impl EarTransition {
+    /// Change in the number of buds in ear; `None` if either side is unknown
+    fn in_ear_delta(&self) -> Option<i8> {
+        Some(in_ear_count(self.to)? as i8 - in_ear_count(self.from)? as i8)
+    }
+}
+
+fn in_ear_count(state: EarDetectionState) -> Option<u8> {
+    match state {
+        EarDetectionState::Unknown => None,
+        EarDetectionState::BothEarsOut => Some(0),
+        EarDetectionState::LeftEarIn | EarDetectionState::RightEarIn => Some(1),
+        EarDetectionState::BothEarsIn => Some(2),
+    }
+}
+
+/// Which transitions a rule fires on
+///
+/// Apart from `Exact`, conditions never match a transition from or to
+/// `Unknown`, so the first report after connecting does not trigger them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EarCondition {
+    /// Fewer buds are in ear than before
+    BudRemoved,
+    /// The last bud in ear was taken out
+    BothRemoved,
+    /// More buds are in ear than before
+    BudInserted,
+    /// Both buds are in ear and before they were not
+    BothInserted,
+    /// Exactly this transition
+    Exact {
+        /// State before the change
+        from: EarDetectionState,
+        /// State after the change
+        to: EarDetectionState,
+    },
+}
+
+impl EarCondition {
+    /// Whether `transition` satisfies the condition
+    pub fn matches(&self, transition: &EarTransition) -> bool {
+        let delta = transition.in_ear_delta();
+        match *self {
+            EarCondition::BudRemoved => delta.is_some_and(|d| d < 0),
+            EarCondition::BothRemoved => {
+                delta.is_some_and(|d| d < 0) && transition.to == EarDetectionState::BothEarsOut
+            }
+            EarCondition::BudInserted => delta.is_some_and(|d| d > 0),
+            EarCondition::BothInserted => {
+                delta.is_some_and(|d| d > 0) && transition.to == EarDetectionState::BothEarsIn
+            }
+            EarCondition::Exact { from, to } => transition.from == from && transition.to == to,
+        }
+    }
+}
+
+/// Something to do when a rule fires
+///
+/// Plain closures taking the device id and transition can be used for
+/// synchronous actions.
+pub trait EarAction: Send + Sync {
+    /// Act on `transition` of the device `device_id`
+    fn run<'a>(&'a self, device_id: &'a str, transition: EarTransition) -> ActionFuture<'a>;
+}
+
+impl<F> EarAction for F
+where
+    F: Fn(&str, EarTransition) -> Result<()> + Send + Sync,
+{
+    fn run<'a>(&'a self, device_id: &'a str, transition: EarTransition) -> ActionFuture<'a> {
+        let result = self(device_id, transition);
+        Box::pin(async move { result })
+    }
+}
+
+/// A named condition and the action it triggers
+#[derive(Clone)]
+pub struct EarRule {
+    name: String,
+    condition: EarCondition,
+    action: Arc<dyn EarAction>,
+}
+
+impl EarRule {
+    /// Rule running `action` whenever `condition` matches
+    pub fn new(name: &str, condition: EarCondition, action: impl EarAction + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            condition,
+            action: Arc::new(action),
+        }
+    }
+
+    /// Name reported with the rule's outcome
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Transitions the rule fires on
+    pub fn condition(&self) -> EarCondition {
+        self.condition
+    }
+}
+
+impl fmt::Debug for EarRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EarRule")
+            .field("name", &self.name)
+            .field("condition", &self.condition)
+            .finish()
+    }
+}
+
+/// Runs rules against each device's ear detection transitions
+///
+/// Rules fire in the order they were added. A failing action is logged and
+/// does not stop the remaining rules.
+#[derive(Debug, Default)]
+pub struct EarRuleEngine {
+    rules: Vec<EarRule>,
+    states: HashMap<String, EarDetectionState>,
+}
+
+impl EarRuleEngine {
+    /// Engine without rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule after the existing ones
+    pub fn add_rule(&mut self, rule: EarRule) {
+        self.rules.push(rule);
+    }
+
+    /// Add a rule after the existing ones, builder style
+    pub fn with_rule(mut self, rule: EarRule) -> Self {
+        self.add_rule(rule);
+        self
+    }
+
+    /// Rules in the order they fire
+    pub fn rules(&self) -> &[EarRule] {
+        &self.rules
+    }
+
+    /// Last state seen for a device
+    pub fn state(&self, device_id: &str) -> EarDetectionState {
+        self.states
+            .get(device_id)
+            .copied()
+            .unwrap_or(EarDetectionState::Unknown)
+    }
+
+    /// Record a new state and run the rules matching the transition
+    ///
+    /// Returns the name and outcome of each rule that fired.
+    pub async fn handle(
+        &mut self,
+        device_id: &str,
+        state: EarDetectionState,
+    ) -> Vec<(String, Result<()>)> {
+        let from = self.states.insert(device_id.to_string(), state);
+        let transition = EarTransition {
+            from: from.unwrap_or(EarDetectionState::Unknown),
+            to: state,
+        };
+        if transition.from == transition.to {
+            return Vec::new();
+        }
+
+        let mut outcomes = Vec::new();
+        for rule in self
+            .rules
+            .iter()
+            .filter(|r| r.condition.matches(&transition))
+        {
+            let result = rule.action.run(device_id, transition).await;
+            if let Err(err) = &result {
+                log::warn!("ear rule '{}' failed for {}: {}", rule.name, device_id, err);
+            }
+            outcomes.push((rule.name.clone(), result));
+        }
+        outcomes
+    }
+
+    /// Spawn a task applying the rules to ear detection events on `bus`
+    ///
+    /// The task runs until it is aborted or the bus is dropped.
+    pub fn run(mut self, bus: &EventBus) -> JoinHandle<()> {
+        let mut events = bus.subscribe_filtered(EventFilter::new().kind(EventKind::EarDetection));
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(Event {
+                        event_type: EventType::EarDetection(state),
+                        device_id,
+                        ..
+                    }) => {
+                        self.handle(&device_id, state).await;
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use std::sync::Mutex;
+    use EarDetectionState::*;
+
+    fn recorder(log: &Arc<Mutex<Vec<String>>>, label: &'static str) -> impl EarAction {
+        let log = Arc::clone(log);
+        move |device_id: &str, _: EarTransition| {
+            log.lock().unwrap().push(format!("{} {}", label, device_id));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_conditions() {
+        let t = |from, to| EarTransition { from, to };
+        assert!(EarCondition::BudRemoved.matches(&t(BothEarsIn, LeftEarIn)));
+        assert!(!EarCondition::BothRemoved.matches(&t(BothEarsIn, LeftEarIn)));
+        assert!(EarCondition::BothRemoved.matches(&t(RightEarIn, BothEarsOut)));
+        assert!(EarCondition::BothInserted.matches(&t(LeftEarIn, BothEarsIn)));
+        assert!(EarCondition::BudInserted.matches(&t(BothEarsOut, RightEarIn)));
+        assert!(!EarCondition::BudInserted.matches(&t(LeftEarIn, RightEarIn)));
+        assert!(!EarCondition::BothInserted.matches(&t(Unknown, BothEarsIn)));
+        assert!(EarCondition::Exact {
+            from: Unknown,
+            to: BothEarsIn
+        }
+        .matches(&t(Unknown, BothEarsIn)));
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume_rules() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = EarRuleEngine::new()
+            .with_rule(EarRule::new(
+                "pause",
+                EarCondition::BudRemoved,
+                recorder(&log, "pause"),
+            ))
+            .with_rule(EarRule::new(
+                "resume",
+                EarCondition::BothInserted,
+                recorder(&log, "resume"),
+            ));
+
+        assert!(engine.handle("dev", BothEarsIn).await.is_empty());
+        assert!(engine.handle("dev", BothEarsIn).await.is_empty());
+        assert_eq!(engine.handle("dev", LeftEarIn).await.len(), 1);
+        assert_eq!(engine.handle("dev", BothEarsIn).await.len(), 1);
+        assert!(engine.handle("other", LeftEarIn).await.is_empty());
+
+        assert_eq!(*log.lock().unwrap(), vec!["pause dev", "resume dev"]);
+        assert_eq!(engine.state("dev"), BothEarsIn);
+    }
+
+    #[tokio::test]
+    async fn test_failing_action_does_not_stop_others() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let failing = |_: &str, _: EarTransition| -> Result<()> { Err(Error::Cancelled) };
+        let mut engine = EarRuleEngine::new()
+            .with_rule(EarRule::new("failing", EarCondition::BudRemoved, failing))
+            .with_rule(EarRule::new(
+                "pause",
+                EarCondition::BudRemoved,
+                recorder(&log, "pause"),
+            ));
+
+        engine.handle("dev", BothEarsIn).await;
+        let outcomes = engine.handle("dev", BothEarsOut).await;
+        assert_eq!(outcomes[0], ("failing".to_string(), Err(Error::Cancelled)));
+        assert_eq!(outcomes[1], ("pause".to_string(), Ok(())));
+        assert_eq!(log.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_runs_from_event_bus() {
+        let bus = EventBus::new();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let task = EarRuleEngine::new()
+            .with_rule(EarRule::new(
+                "pause",
+                EarCondition::BudRemoved,
+                recorder(&log, "pause"),
+            ))
+            .run(&bus);
+
+        bus.emit(Event::new(EventType::EarDetection(BothEarsIn), "dev"));
+        bus.emit(Event::new(EventType::EarDetection(RightEarIn), "dev"));
+        drop(bus);
+        task.await.unwrap();
+
+        assert_eq!(*log.lock().unwrap(), vec!["pause dev"]);
+    }
+}
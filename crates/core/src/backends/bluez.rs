@@ -1,55 +1,874 @@
 //! BlueZ backend for Linux
+//!
+//! Talks to `org.bluez` on the system bus: `Adapter1` for discovery,
+//! `Device1` for connections and `GattCharacteristic1` for AAP reads,
+//! writes and notifications. Objects are found through the
+//! `ObjectManager` at `/`, so no object paths beyond the adapter are
+//! assumed.
 
-#[cfg(target_os = "linux")]
-use crate::bluetooth::BluetoothBackend;
-use crate::error::Result;
+use crate::bluetooth::{
+    AsyncBluetoothBackend, BackendFuture, BluetoothDevice, NotificationStream, ScanStream,
+};
+use crate::error::{Error, Result};
+use futures_util::{future, stream, StreamExt};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::sync::watch;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+use zbus::{Connection, MatchRule, Message, MessageStream};
 
-#[cfg(target_os = "linux")]
-pub struct BlueZBackend;
+/// Bus name of the BlueZ daemon
+pub const BLUEZ_SERVICE: &str = "org.bluez";
 
-#[cfg(target_os = "linux")]
-impl BluetoothBackend for BlueZBackend {
-    fn start_scan(&mut self) -> Result<()> {
-        Ok(())
+/// Object path of the first adapter
+pub const DEFAULT_ADAPTER: &str = "/org/bluez/hci0";
+
+/// How long to wait for a device's GATT services after connecting
+pub const SERVICES_TIMEOUT: Duration = Duration::from_secs(10);
+
+const ADAPTER_INTERFACE: &str = "org.bluez.Adapter1";
+const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+const SERVICE_INTERFACE: &str = "org.bluez.GattService1";
+const CHARACTERISTIC_INTERFACE: &str = "org.bluez.GattCharacteristic1";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const OBJECT_MANAGER_INTERFACE: &str = "org.freedesktop.DBus.ObjectManager";
+
+type Properties = HashMap<String, OwnedValue>;
+type ManagedObjects = HashMap<OwnedObjectPath, HashMap<String, Properties>>;
+
+/// Backend driving BlueZ over D-Bus
+///
+/// Only [`AsyncBluetoothBackend`] is implemented: D-Bus calls run on the
+/// caller's tokio runtime and notified values arrive as streams.
+pub struct BlueZBackend {
+    connection: Connection,
+    adapter: String,
+    characteristics: Mutex<HashMap<(String, u128, u128), OwnedObjectPath>>,
+    scanning: watch::Sender<bool>,
+}
+
+impl BlueZBackend {
+    /// Connect to BlueZ on the system bus
+    pub async fn system() -> Result<Self> {
+        Ok(Self::with_connection(Connection::system().await?))
+    }
+
+    /// Connect to a bus at a D-Bus address such as `unix:path=/run/bus`
+    ///
+    /// Useful for private buses running a mock BlueZ service.
+    pub async fn with_address(address: &str) -> Result<Self> {
+        let connection = zbus::connection::Builder::address(address)?.build().await?;
+        Ok(Self::with_connection(connection))
+    }
+
+    /// Talk to BlueZ over an established bus connection
+    pub fn with_connection(connection: Connection) -> Self {
+        Self {
+            connection,
+            adapter: DEFAULT_ADAPTER.to_string(),
+            characteristics: Mutex::default(),
+            scanning: watch::Sender::new(false),
+        }
+    }
+
+    /// Use the adapter at `path` instead of [`DEFAULT_ADAPTER`]
+    pub fn with_adapter(mut self, path: &str) -> Self {
+        self.adapter = path.to_string();
+        self
+    }
+
+    /// Object path of the adapter in use
+    pub fn adapter(&self) -> &str {
+        &self.adapter
+    }
+
+    /// Object path BlueZ uses for the device at `address`
+    pub fn device_path(&self, address: &str) -> String {
+        format!(
+            "{}/dev_{}",
+            self.adapter,
+            address.to_uppercase().replace(':', "_")
+        )
     }
 
-    fn stop_scan(&mut self) -> Result<()> {
+    fn characteristics(&self) -> MutexGuard<'_, HashMap<(String, u128, u128), OwnedObjectPath>> {
+        self.characteristics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    async fn call(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+        body: &(impl serde::Serialize + zbus::zvariant::DynamicType),
+    ) -> Result<Message> {
+        call(&self.connection, path, interface, method, body).await
+    }
+
+    async fn managed_objects(&self) -> Result<ManagedObjects> {
+        let reply = self
+            .call("/", OBJECT_MANAGER_INTERFACE, "GetManagedObjects", &())
+            .await?;
+        Ok(reply.body().deserialize()?)
+    }
+
+    /// Devices BlueZ knows on the adapter, discovered or paired
+    pub async fn devices(&self) -> Result<Vec<BluetoothDevice>> {
+        let objects = self.managed_objects().await?;
+        let prefix = format!("{}/", self.adapter);
+        let mut devices: Vec<BluetoothDevice> = objects
+            .iter()
+            .filter(|(path, _)| path.as_str().starts_with(&prefix))
+            .filter_map(|(_, interfaces)| interfaces.get(DEVICE_INTERFACE))
+            .filter_map(device_from_properties)
+            .collect();
+        devices.sort_by(|a, b| a.address.cmp(&b.address));
+        Ok(devices)
+    }
+
+    async fn services_resolved(&self, path: &str) -> Result<bool> {
+        let reply = self
+            .call(
+                path,
+                PROPERTIES_INTERFACE,
+                "Get",
+                &(DEVICE_INTERFACE, "ServicesResolved"),
+            )
+            .await?;
+        let value: OwnedValue = reply.body().deserialize()?;
+        Ok(bool::try_from(value).unwrap_or(false))
+    }
+
+    async fn start_discovery(&self) -> Result<()> {
+        self.call(&self.adapter, ADAPTER_INTERFACE, "StartDiscovery", &())
+            .await?;
+        self.scanning.send_replace(true);
         Ok(())
     }
 
-    fn connect(&mut self, _address: &str) -> Result<()> {
+    async fn stop_discovery(&self) -> Result<()> {
+        self.call(&self.adapter, ADAPTER_INTERFACE, "StopDiscovery", &())
+            .await?;
+        self.scanning.send_replace(false);
         Ok(())
     }
 
-    fn disconnect(&mut self, _address: &str) -> Result<()> {
+    /// Start discovery and stream the devices found until it stops
+    ///
+    /// New devices show up as `InterfacesAdded` on the object manager and
+    /// advertisement updates as `PropertiesChanged` on the device; either way the
+    /// device's properties are fetched again.
+    async fn discover(&self) -> Result<ScanStream> {
+        let added = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(OBJECT_MANAGER_INTERFACE)?
+            .member("InterfacesAdded")?
+            .build();
+        let changed = MatchRule::builder()
+            .msg_type(zbus::message::Type::Signal)
+            .interface(PROPERTIES_INTERFACE)?
+            .member("PropertiesChanged")?
+            .path_namespace(self.adapter.as_str())?
+            .build();
+        // Listen before starting so no device found in between is missed
+        let added = MessageStream::for_match_rule(added, &self.connection, None).await?;
+        let changed = MessageStream::for_match_rule(changed, &self.connection, None).await?;
+        self.start_discovery().await?;
+        let known = self.devices().await?;
+
+        let connection = self.connection.clone();
+        let prefix = format!("{}/", self.adapter);
+        let updates = stream::select(
+            added.filter_map(|message| future::ready(added_device(message))),
+            changed.filter_map(|message| future::ready(advertisement_changed(message))),
+        )
+        .filter_map(move |path| {
+            let connection = connection.clone();
+            let found = path.as_str().starts_with(&prefix);
+            async move {
+                if !found {
+                    return None;
+                }
+                let reply = call(
+                    &connection,
+                    path.as_str(),
+                    PROPERTIES_INTERFACE,
+                    "GetAll",
+                    &(DEVICE_INTERFACE,),
+                )
+                .await
+                .ok()?;
+                device_from_properties(&reply.body().deserialize().ok()?)
+            }
+        });
+
+        let mut scanning = self.scanning.subscribe();
+        let stopped = async move {
+            let _ = scanning.wait_for(|scanning| !scanning).await;
+        };
+        Ok(Box::pin(
+            stream::iter(known).chain(updates).take_until(stopped),
+        ))
+    }
+
+    /// Connect and wait until BlueZ has resolved the GATT services
+    async fn connect_device(&self, address: &str) -> Result<()> {
+        let path = self.device_path(address);
+        self.call(&path, DEVICE_INTERFACE, "Connect", &()).await?;
+        let wait = async {
+            while !self.services_resolved(&path).await? {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            Ok(())
+        };
+        tokio::time::timeout(SERVICES_TIMEOUT, wait)
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+
+    async fn disconnect_device(&self, address: &str) -> Result<()> {
+        self.characteristics()
+            .retain(|(device, _, _), _| device != address);
+        let path = self.device_path(address);
+        self.call(&path, DEVICE_INTERFACE, "Disconnect", &())
+            .await?;
         Ok(())
     }
 
-    fn write_characteristic(
-        &mut self,
-        _address: &str,
-        _service_uuid: u128,
-        _char_uuid: u128,
-        _data: &[u8],
+    /// Object path of a characteristic, looked up once per connection
+    async fn characteristic(
+        &self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> Result<OwnedObjectPath> {
+        let key = (address.to_string(), service_uuid, char_uuid);
+        if let Some(path) = self.characteristics().get(&key) {
+            return Ok(path.clone());
+        }
+        let objects = self.managed_objects().await?;
+        let path = find_characteristic(
+            &objects,
+            &self.device_path(address),
+            service_uuid,
+            char_uuid,
+        )
+        .ok_or_else(|| {
+            Error::BluetoothError(format!(
+                "{} has no characteristic {} in service {}",
+                address,
+                format_uuid(char_uuid),
+                format_uuid(service_uuid)
+            ))
+        })?;
+        self.characteristics().insert(key, path.clone());
+        Ok(path)
+    }
+
+    async fn write_value(
+        &self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+        data: &[u8],
     ) -> Result<()> {
+        let path = self
+            .characteristic(address, service_uuid, char_uuid)
+            .await?;
+        let options: HashMap<&str, Value> = HashMap::new();
+        self.call(
+            &path,
+            CHARACTERISTIC_INTERFACE,
+            "WriteValue",
+            &(data, options),
+        )
+        .await?;
         Ok(())
     }
 
-    fn read_characteristic(
-        &mut self,
-        _address: &str,
-        _service_uuid: u128,
-        _char_uuid: u128,
+    async fn read_value(
+        &self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
     ) -> Result<Vec<u8>> {
-        Ok(Vec::new())
+        let path = self
+            .characteristic(address, service_uuid, char_uuid)
+            .await?;
+        let options: HashMap<&str, Value> = HashMap::new();
+        let reply = self
+            .call(&path, CHARACTERISTIC_INTERFACE, "ReadValue", &(options,))
+            .await?;
+        Ok(reply.body().deserialize()?)
     }
 
-    fn enable_notifications(
-        &mut self,
-        _address: &str,
-        _service_uuid: u128,
-        _char_uuid: u128,
-    ) -> Result<()> {
-        Ok(())
+    /// Start notifications and stream the characteristic's `Value`
+    ///
+    /// The stream ends once BlueZ reports the device disconnected.
+    async fn start_notify(
+        &self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> Result<NotificationStream> {
+        let path = self
+            .characteristic(address, service_uuid, char_uuid)
+            .await?;
+        let values = MessageStream::for_match_rule(
+            properties_changed(path.as_str())?,
+            &self.connection,
+            None,
+        )
+        .await?;
+        let mut device = MessageStream::for_match_rule(
+            properties_changed(&self.device_path(address))?,
+            &self.connection,
+            None,
+        )
+        .await?;
+        // Subscribed first so the first value after StartNotify is not missed
+        self.call(&path, CHARACTERISTIC_INTERFACE, "StartNotify", &())
+            .await?;
+
+        let disconnected = async move {
+            while let Some(message) = device.next().await {
+                if changed_property::<bool>(message, DEVICE_INTERFACE, "Connected") == Some(false) {
+                    break;
+                }
+            }
+        };
+        let values = values.filter_map(|message| {
+            future::ready(changed_property(message, CHARACTERISTIC_INTERFACE, "Value"))
+        });
+        Ok(Box::pin(values.take_until(disconnected)))
+    }
+}
+
+impl AsyncBluetoothBackend for BlueZBackend {
+    fn scan(&self) -> BackendFuture<'_, ScanStream> {
+        Box::pin(self.discover())
+    }
+
+    fn stop_scan(&self) -> BackendFuture<'_, ()> {
+        Box::pin(self.stop_discovery())
+    }
+
+    fn connect<'a>(&'a self, address: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(self.connect_device(address))
+    }
+
+    fn disconnect<'a>(&'a self, address: &'a str) -> BackendFuture<'a, ()> {
+        Box::pin(self.disconnect_device(address))
+    }
+
+    fn write_characteristic<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+        data: &'a [u8],
+    ) -> BackendFuture<'a, ()> {
+        Box::pin(self.write_value(address, service_uuid, char_uuid, data))
+    }
+
+    fn read_characteristic<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> BackendFuture<'a, Vec<u8>> {
+        Box::pin(self.read_value(address, service_uuid, char_uuid))
+    }
+
+    fn notifications<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> BackendFuture<'a, NotificationStream> {
+        Box::pin(self.start_notify(address, service_uuid, char_uuid))
+    }
+}
+
+async fn call(
+    connection: &Connection,
+    path: &str,
+    interface: &str,
+    method: &str,
+    body: &(impl serde::Serialize + zbus::zvariant::DynamicType),
+) -> Result<Message> {
+    connection
+        .call_method(Some(BLUEZ_SERVICE), path, Some(interface), method, body)
+        .await
+        .map_err(bluez_error)
+}
+
+fn properties_changed(path: &str) -> Result<MatchRule<'_>> {
+    Ok(MatchRule::builder()
+        .msg_type(zbus::message::Type::Signal)
+        .interface(PROPERTIES_INTERFACE)?
+        .member("PropertiesChanged")?
+        .path(path)?
+        .build())
+}
+
+/// New value of `name` in a `PropertiesChanged` signal for `interface`
+fn changed_property<T>(message: zbus::Result<Message>, interface: &str, name: &str) -> Option<T>
+where
+    T: TryFrom<OwnedValue>,
+{
+    let message = message.ok()?;
+    let (changed_interface, changed, _): (String, Properties, Vec<String>) =
+        message.body().deserialize().ok()?;
+    if changed_interface != interface {
+        return None;
+    }
+    property(&changed, name)
+}
+
+/// Path of a device in an `InterfacesAdded` signal
+fn added_device(message: zbus::Result<Message>) -> Option<OwnedObjectPath> {
+    let message = message.ok()?;
+    let (path, interfaces): (OwnedObjectPath, HashMap<String, Properties>) =
+        message.body().deserialize().ok()?;
+    interfaces.contains_key(DEVICE_INTERFACE).then_some(path)
+}
+
+/// Path of a device whose RSSI or manufacturer data changed
+fn advertisement_changed(message: zbus::Result<Message>) -> Option<OwnedObjectPath> {
+    let message = message.ok()?;
+    let (interface, changed, _): (String, Properties, Vec<String>) =
+        message.body().deserialize().ok()?;
+    let advertised = interface == DEVICE_INTERFACE
+        && (changed.contains_key("RSSI") || changed.contains_key("ManufacturerData"));
+    let header = message.header();
+    advertised.then(|| header.path().map(|path| path.to_owned().into()))?
+}
+
+fn device_from_properties(props: &Properties) -> Option<BluetoothDevice> {
+    let address = string_property(props, "Address")?;
+    Some(BluetoothDevice {
+        name: string_property(props, "Alias")
+            .or_else(|| string_property(props, "Name"))
+            .unwrap_or_else(|| address.clone()),
+        rssi: property::<i16>(props, "RSSI").map_or(0, i32::from),
+        is_connected: property::<bool>(props, "Connected").unwrap_or(false),
+        manufacturer_data: property::<HashMap<u16, OwnedValue>>(props, "ManufacturerData")
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(company, data)| Some((company, Vec::<u8>::try_from(data).ok()?)))
+            .collect(),
+        address,
+    })
+}
+
+/// Map BlueZ's D-Bus errors onto the crate's errors
+fn bluez_error(err: zbus::Error) -> Error {
+    match &err {
+        zbus::Error::MethodError(name, description, _) => match name.as_str() {
+            "org.bluez.Error.NotConnected" => Error::DeviceNotConnected,
+            "org.bluez.Error.NotPermitted" | "org.bluez.Error.NotAuthorized" => {
+                Error::PermissionDenied
+            }
+            _ => Error::BluetoothError(match description {
+                Some(description) => format!("{}: {}", name, description),
+                None => name.to_string(),
+            }),
+        },
+        _ => err.into(),
+    }
+}
+
+/// The hyphenated lowercase form BlueZ uses for UUIDs
+pub fn format_uuid(uuid: u128) -> String {
+    let hex = format!("{:032x}", uuid);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Path of the characteristic `char_uuid` in service `service_uuid` of a device
+fn find_characteristic(
+    objects: &ManagedObjects,
+    device_path: &str,
+    service_uuid: u128,
+    char_uuid: u128,
+) -> Option<OwnedObjectPath> {
+    let prefix = format!("{}/", device_path);
+    let service_uuid = format_uuid(service_uuid);
+    let char_uuid = format_uuid(char_uuid);
+    let uuid_matches = |props: &Properties, uuid: &str| {
+        string_property(props, "UUID").is_some_and(|u| u.eq_ignore_ascii_case(uuid))
+    };
+
+    objects
+        .iter()
+        .filter(|(path, _)| path.as_str().starts_with(&prefix))
+        .find(|(_, interfaces)| {
+            let Some(props) = interfaces.get(CHARACTERISTIC_INTERFACE) else {
+                return false;
+            };
+            let service = property::<OwnedObjectPath>(props, "Service")
+                .and_then(|service| objects.get(&service))
+                .and_then(|interfaces| interfaces.get(SERVICE_INTERFACE));
+            uuid_matches(props, &char_uuid)
+                && service.is_some_and(|s| uuid_matches(s, &service_uuid))
+        })
+        .map(|(path, _)| path.clone())
+}
+
+fn property<T>(props: &Properties, name: &str) -> Option<T>
+where
+    T: TryFrom<OwnedValue>,
+{
+    T::try_from(props.get(name)?.try_clone().ok()?).ok()
+}
+
+fn string_property(props: &Properties, name: &str) -> Option<String> {
+    property(props, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{AAP_CHARACTERISTIC_UUID, AAP_SERVICE_UUID};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use zbus::object_server::SignalContext;
+    use zbus::{connection, interface, Guid};
+
+    const ADDRESS: &str = "A0:B1:C2:D3:E4:F5";
+    const DEVICE: &str = "/org/bluez/hci0/dev_A0_B1_C2_D3_E4_F5";
+    const SERVICE: &str = "/org/bluez/hci0/dev_A0_B1_C2_D3_E4_F5/service0010";
+    const CHARACTERISTIC: &str = "/org/bluez/hci0/dev_A0_B1_C2_D3_E4_F5/service0010/char0011";
+
+    #[derive(Debug, zbus::DBusError)]
+    #[zbus(prefix = "org.bluez.Error")]
+    enum MockError {
+        #[zbus(error)]
+        ZBus(zbus::Error),
+        NotConnected(String),
+    }
+
+    struct MockAdapter {
+        discovering: Arc<AtomicBool>,
+    }
+
+    #[interface(name = "org.bluez.Adapter1")]
+    impl MockAdapter {
+        fn start_discovery(&self) {
+            self.discovering.store(true, Ordering::SeqCst);
+        }
+
+        fn stop_discovery(&self) {
+            self.discovering.store(false, Ordering::SeqCst);
+        }
+    }
+
+    struct MockDevice {
+        connected: Arc<AtomicBool>,
+        rssi: i16,
+    }
+
+    #[interface(name = "org.bluez.Device1")]
+    impl MockDevice {
+        fn connect(&self) {
+            self.connected.store(true, Ordering::SeqCst);
+        }
+
+        async fn disconnect(
+            &self,
+            #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        ) -> zbus::fdo::Result<()> {
+            self.connected.store(false, Ordering::SeqCst);
+            self.connected_changed(&ctxt).await?;
+            Ok(())
+        }
+
+        #[zbus(property)]
+        fn address(&self) -> String {
+            ADDRESS.to_string()
+        }
+
+        #[zbus(property)]
+        fn name(&self) -> String {
+            "AirPods Pro".to_string()
+        }
+
+        #[zbus(property, name = "RSSI")]
+        fn rssi(&self) -> i16 {
+            self.rssi
+        }
+
+        #[zbus(property)]
+        fn manufacturer_data(&self) -> HashMap<u16, Value<'static>> {
+            // AirPods Pro 2, both buds at 100% and the case at 50%
+            let mut pairing: Vec<u8> = vec![0x07, 0x19, 0x01, 0x14, 0x20, 0x20, 0xAA, 0x05, 0x00];
+            pairing.resize(27, 0);
+            HashMap::from([(0x004C, Value::from(pairing))])
+        }
+
+        #[zbus(property)]
+        fn connected(&self) -> bool {
+            self.connected.load(Ordering::SeqCst)
+        }
+
+        #[zbus(property)]
+        fn services_resolved(&self) -> bool {
+            self.connected.load(Ordering::SeqCst)
+        }
+    }
+
+    struct MockService;
+
+    #[interface(name = "org.bluez.GattService1")]
+    impl MockService {
+        #[zbus(property, name = "UUID")]
+        fn uuid(&self) -> String {
+            format_uuid(AAP_SERVICE_UUID)
+        }
+    }
+
+    /// AAP characteristic notifying back whatever is written to it
+    struct MockCharacteristic {
+        connected: Arc<AtomicBool>,
+        value: Vec<u8>,
+        notifying: bool,
+    }
+
+    impl MockCharacteristic {
+        fn check_connected(&self) -> std::result::Result<(), MockError> {
+            if self.connected.load(Ordering::SeqCst) {
+                Ok(())
+            } else {
+                Err(MockError::NotConnected("Not Connected".to_string()))
+            }
+        }
+    }
+
+    #[interface(name = "org.bluez.GattCharacteristic1")]
+    impl MockCharacteristic {
+        fn read_value(
+            &self,
+            _options: HashMap<String, OwnedValue>,
+        ) -> std::result::Result<Vec<u8>, MockError> {
+            self.check_connected()?;
+            Ok(self.value.clone())
+        }
+
+        async fn write_value(
+            &mut self,
+            value: Vec<u8>,
+            _options: HashMap<String, OwnedValue>,
+            #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        ) -> std::result::Result<(), MockError> {
+            self.check_connected()?;
+            self.value = value;
+            if self.notifying {
+                self.value_changed(&ctxt).await?;
+            }
+            Ok(())
+        }
+
+        fn start_notify(&mut self) -> std::result::Result<(), MockError> {
+            self.check_connected()?;
+            self.notifying = true;
+            Ok(())
+        }
+
+        #[zbus(property, name = "UUID")]
+        fn uuid(&self) -> String {
+            format_uuid(AAP_CHARACTERISTIC_UUID)
+        }
+
+        #[zbus(property)]
+        fn service(&self) -> OwnedObjectPath {
+            OwnedObjectPath::try_from(SERVICE).unwrap()
+        }
+
+        #[zbus(property)]
+        fn value(&self) -> Vec<u8> {
+            self.value.clone()
+        }
+    }
+
+    /// A backend talking to a fake BlueZ serving one AirPods Pro
+    async fn mock_bluez() -> (BlueZBackend, Arc<AtomicBool>, Connection) {
+        let discovering = Arc::new(AtomicBool::new(false));
+        let connected = Arc::new(AtomicBool::new(false));
+        let (server, client) = std::os::unix::net::UnixStream::pair().unwrap();
+        server.set_nonblocking(true).unwrap();
+        client.set_nonblocking(true).unwrap();
+
+        let server =
+            connection::Builder::unix_stream(tokio::net::UnixStream::from_std(server).unwrap())
+                .server(Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at("/", zbus::fdo::ObjectManager)
+                .unwrap()
+                .serve_at(
+                    DEFAULT_ADAPTER,
+                    MockAdapter {
+                        discovering: Arc::clone(&discovering),
+                    },
+                )
+                .unwrap()
+                .serve_at(
+                    DEVICE,
+                    MockDevice {
+                        connected: Arc::clone(&connected),
+                        rssi: -52,
+                    },
+                )
+                .unwrap()
+                .serve_at(SERVICE, MockService)
+                .unwrap()
+                .serve_at(
+                    CHARACTERISTIC,
+                    MockCharacteristic {
+                        connected,
+                        value: vec![0x01],
+                        notifying: false,
+                    },
+                )
+                .unwrap()
+                .build();
+        let client =
+            connection::Builder::unix_stream(tokio::net::UnixStream::from_std(client).unwrap())
+                .p2p()
+                .build();
+        let (server, client) = tokio::join!(server, client);
+
+        (
+            BlueZBackend::with_connection(client.unwrap()),
+            discovering,
+            server.unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_format_uuid() {
+        assert_eq!(
+            format_uuid(AAP_SERVICE_UUID),
+            "7dfc9000-7d1c-4951-86aa-8d9728f8d66c"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_discovery() {
+        let (backend, discovering, _bus) = mock_bluez().await;
+        let _devices = backend.scan().await.unwrap();
+        assert!(discovering.load(Ordering::SeqCst));
+
+        let devices = backend.devices().await.unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].address, ADDRESS);
+        assert_eq!(devices[0].name, "AirPods Pro");
+        assert_eq!(devices[0].rssi, -52);
+        assert!(!devices[0].is_connected);
+        let pairing = devices[0].proximity_pairing().unwrap();
+        assert_eq!(pairing.model, Some(crate::DeviceModel::AirPodsProGen2));
+        assert_eq!((pairing.left, pairing.case), (Some(100), Some(50)));
+
+        backend.stop_scan().await.unwrap();
+        assert!(!discovering.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_gatt() {
+        let (backend, _, _bus) = mock_bluez().await;
+        let aap = (AAP_SERVICE_UUID, AAP_CHARACTERISTIC_UUID);
+
+        assert_eq!(
+            backend.read_characteristic(ADDRESS, aap.0, aap.1).await,
+            Err(Error::DeviceNotConnected)
+        );
+        backend.connect(ADDRESS).await.unwrap();
+        assert_eq!(
+            backend
+                .read_characteristic(ADDRESS, aap.0, aap.1)
+                .await
+                .unwrap(),
+            vec![0x01]
+        );
+        backend
+            .write_characteristic(ADDRESS, aap.0, aap.1, &[0xAA, 0x01])
+            .await
+            .unwrap();
+        assert_eq!(
+            backend
+                .read_characteristic(ADDRESS, aap.0, aap.1)
+                .await
+                .unwrap(),
+            vec![0xAA, 0x01]
+        );
+
+        assert!(backend
+            .read_characteristic(ADDRESS, aap.0, 0x1234)
+            .await
+            .is_err());
+        backend.disconnect(ADDRESS).await.unwrap();
+        assert_eq!(
+            backend
+                .write_characteristic(ADDRESS, aap.0, aap.1, &[0x00])
+                .await,
+            Err(Error::DeviceNotConnected)
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_scan_stream() {
+        let (backend, discovering, bus) = mock_bluez().await;
+        let mut devices = backend.scan().await.unwrap();
+        assert!(discovering.load(Ordering::SeqCst));
+        assert_eq!(devices.next().await.unwrap().rssi, -52);
+
+        let device = bus
+            .object_server()
+            .interface::<_, MockDevice>(DEVICE)
+            .await
+            .unwrap();
+        device.get_mut().await.rssi = -40;
+        let rssi = Value::from(-40i16);
+        zbus::fdo::Properties::properties_changed(
+            device.signal_context(),
+            DEVICE_INTERFACE.try_into().unwrap(),
+            &HashMap::from([("RSSI", &rssi)]),
+            &[],
+        )
+        .await
+        .unwrap();
+        let update = devices.next().await.unwrap();
+        assert_eq!((update.address.as_str(), update.rssi), (ADDRESS, -40));
+
+        backend.stop_scan().await.unwrap();
+        assert!(devices.next().await.is_none());
+        assert!(!discovering.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_notification_stream_ends_on_disconnect() {
+        let (backend, _, _bus) = mock_bluez().await;
+        let aap = (AAP_SERVICE_UUID, AAP_CHARACTERISTIC_UUID);
+        backend.connect(ADDRESS).await.unwrap();
+
+        let mut values = backend.notifications(ADDRESS, aap.0, aap.1).await.unwrap();
+        backend
+            .write_characteristic(ADDRESS, aap.0, aap.1, &[0xAA, 0x02])
+            .await
+            .unwrap();
+        assert_eq!(values.next().await, Some(vec![0xAA, 0x02]));
+
+        backend.disconnect(ADDRESS).await.unwrap();
+        assert_eq!(values.next().await, None);
     }
 }
@@ -1,6 +1,6 @@
 //! Bluetooth backend implementations
 
-#[cfg(target_os = "linux")]
+#[cfg(all(target_os = "linux", feature = "bluetooth-linux"))]
 pub mod bluez;
 
 #[cfg(target_os = "macos")]
@@ -0,0 +1,451 @@
+//! Battery history and time-remaining estimates
+
+use crate::device::DeviceModel;
+use crate::error::{Error, Result};
+use crate::state::BatteryInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Samples kept per device before the oldest are dropped
+pub const HISTORY_CAPACITY: usize = 1024;
+
+/// Only samples this recent take part in rate estimates
+pub const RATE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Levels at or below which a low-battery alert is raised, in percent
+pub const DEFAULT_LOW_THRESHOLDS: [u8; 3] = [20, 10, 5];
+
+/// File name used for saved histories inside the store directory
+pub const BATTERY_FILE: &str = "battery.json";
+
+const FILE_VERSION: u32 = 1;
+
+/// One of the batteries reported in a `BatteryInfo`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BatteryComponent {
+    /// Left bud
+    Left,
+    /// Right bud
+    Right,
+    /// Charging case
+    Case,
+}
+
+impl BatteryComponent {
+    /// Every component, in report order
+    pub const ALL: [BatteryComponent; 3] = [
+        BatteryComponent::Left,
+        BatteryComponent::Right,
+        BatteryComponent::Case,
+    ];
+
+    /// Components a model has; AirPods Max report their single battery as
+    /// both buds and have no case
+    pub fn of_model(model: DeviceModel) -> &'static [BatteryComponent] {
+        match model {
+            DeviceModel::AirPodsMax => &Self::ALL[..2],
+            _ => &Self::ALL,
+        }
+    }
+
+    /// This component's level in `info`
+    pub fn level(self, info: &BatteryInfo) -> u8 {
+        match self {
+            BatteryComponent::Left => info.left_bud,
+            BatteryComponent::Right => info.right_bud,
+            BatteryComponent::Case => info.case,
+        }
+    }
+}
+
+/// A battery report with the time it was received, in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatterySample {
+    /// When the report arrived, in milliseconds
+    pub timestamp: u64,
+    /// The report
+    pub battery: BatteryInfo,
+}
+
+/// A component dropped to or below a low-battery threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LowBattery {
+    /// The component running low
+    pub component: BatteryComponent,
+    /// Its level, in percent
+    pub level: u8,
+    /// Lowest threshold crossed, in percent
+    pub threshold: u8,
+}
+
+/// Time series of battery reports for one device
+///
+/// Samples are kept in a ring buffer of `capacity` entries. Rates are fitted
+/// by least squares over the most recent samples that share the latest
+/// charging state and fall within [`RATE_WINDOW`] of it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatteryHistory {
+    capacity: usize,
+    samples: VecDeque<BatterySample>,
+    thresholds: Vec<u8>,
+    #[serde(default = "all_components")]
+    components: Vec<BatteryComponent>,
+}
+
+impl BatteryHistory {
+    /// Create a history keeping [`HISTORY_CAPACITY`] samples
+    pub fn new() -> Self {
+        Self::with_capacity(HISTORY_CAPACITY)
+    }
+
+    /// Create a history keeping at most `capacity` samples (at least one)
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            samples: VecDeque::with_capacity(capacity.min(HISTORY_CAPACITY)),
+            thresholds: DEFAULT_LOW_THRESHOLDS.to_vec(),
+            components: all_components(),
+        }
+    }
+
+    /// Create a history alerting only on the components `model` has
+    pub fn for_model(model: DeviceModel) -> Self {
+        Self {
+            components: BatteryComponent::of_model(model).to_vec(),
+            ..Self::new()
+        }
+    }
+
+    /// Components low-battery alerts are raised for
+    pub fn components(&self) -> &[BatteryComponent] {
+        &self.components
+    }
+
+    /// Replace the low-battery thresholds, in percent
+    pub fn set_thresholds(&mut self, thresholds: &[u8]) {
+        self.thresholds = thresholds.to_vec();
+    }
+
+    /// Low-battery thresholds, in percent
+    pub fn thresholds(&self) -> &[u8] {
+        &self.thresholds
+    }
+
+    /// Add a report taken at `timestamp` (milliseconds)
+    ///
+    /// Returns an alert for each component that crossed a threshold while
+    /// discharging, naming the lowest threshold crossed. The first sample
+    /// counts as crossing every threshold at or above its level. Components
+    /// outside [`components`](Self::components) never alert, nor do ones
+    /// reading 0, which is how reports mark a component that is missing.
+    pub fn record(&mut self, battery: BatteryInfo, timestamp: u64) -> Vec<LowBattery> {
+        let alerts = if battery.is_charging {
+            Vec::new()
+        } else {
+            let previous = self.latest().map(|s| &s.battery);
+            self.components
+                .iter()
+                .filter_map(|&component| {
+                    let level = component.level(&battery);
+                    if level == 0 {
+                        return None;
+                    }
+                    let before = previous
+                        .map(|p| component.level(p))
+                        .filter(|&before| before > 0);
+                    self.thresholds
+                        .iter()
+                        .copied()
+                        .filter(|&t| level <= t && !matches!(before, Some(b) if b <= t))
+                        .min()
+                        .map(|threshold| LowBattery {
+                            component,
+                            level,
+                            threshold,
+                        })
+                })
+                .collect()
+        };
+
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(BatterySample { timestamp, battery });
+        alerts
+    }
+
+    /// Samples, oldest first
+    pub fn samples(&self) -> impl Iterator<Item = &BatterySample> {
+        self.samples.iter()
+    }
+
+    /// Most recent sample
+    pub fn latest(&self) -> Option<&BatterySample> {
+        self.samples.back()
+    }
+
+    /// Number of samples kept
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Whether no sample has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Most samples kept before the oldest are dropped
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Change in level in percent per hour; negative while discharging
+    ///
+    /// `None` until the recent samples span some time.
+    pub fn rate(&self, component: BatteryComponent) -> Option<f64> {
+        let latest = self.latest()?;
+        let window = RATE_WINDOW.as_millis() as u64;
+        let points: Vec<(f64, f64)> = self
+            .samples
+            .iter()
+            .rev()
+            .take_while(|s| {
+                s.battery.is_charging == latest.battery.is_charging
+                    && latest.timestamp.saturating_sub(s.timestamp) <= window
+            })
+            .map(|s| {
+                let age = latest.timestamp.saturating_sub(s.timestamp) as f64 / 3_600_000.0;
+                (-age, component.level(&s.battery) as f64)
+            })
+            .collect();
+        least_squares_slope(&points)
+    }
+
+    /// Percent per hour lost while discharging
+    pub fn discharge_rate(&self, component: BatteryComponent) -> Option<f64> {
+        self.rate(component).map(|r| -r).filter(|r| *r > 0.0)
+    }
+
+    /// Percent per hour gained while charging
+    pub fn charge_rate(&self, component: BatteryComponent) -> Option<f64> {
+        self.rate(component).filter(|r| *r > 0.0)
+    }
+
+    /// Estimated time until `component` is empty at the current discharge rate
+    pub fn time_to_empty(&self, component: BatteryComponent) -> Option<Duration> {
+        let latest = self.latest().filter(|s| !s.battery.is_charging)?;
+        let rate = self.discharge_rate(component)?;
+        Some(hours(component.level(&latest.battery) as f64 / rate))
+    }
+
+    /// Estimated time until `component` is full at the current charge rate
+    pub fn time_to_full(&self, component: BatteryComponent) -> Option<Duration> {
+        let latest = self.latest().filter(|s| s.battery.is_charging)?;
+        let rate = self.charge_rate(component)?;
+        let missing = 100u8.saturating_sub(component.level(&latest.battery));
+        Some(hours(missing as f64 / rate))
+    }
+}
+
+impl Default for BatteryHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BatteryFile {
+    version: u32,
+    devices: BTreeMap<String, BatteryHistory>,
+}
+
+/// Read histories saved by [`save_histories`]; a missing file yields none
+pub fn load_histories(path: &Path) -> Result<BTreeMap<String, BatteryHistory>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let file: BatteryFile =
+        serde_json::from_slice(&fs::read(path)?).map_err(|e| Error::ParseError(e.to_string()))?;
+    if file.version != FILE_VERSION {
+        return Err(Error::VersionMismatch);
+    }
+    Ok(file.devices)
+}
+
+/// Write per-device histories to `path` atomically
+pub fn save_histories(path: &Path, histories: &BTreeMap<String, BatteryHistory>) -> Result<()> {
+    let file = BatteryFile {
+        version: FILE_VERSION,
+        devices: histories.clone(),
+    };
+    let json = serde_json::to_vec(&file).map_err(|e| Error::ParseError(e.to_string()))?;
+    crate::store::write_atomic(path, &json)
+}
+
+fn all_components() -> Vec<BatteryComponent> {
+    BatteryComponent::ALL.to_vec()
+}
+
+fn hours(value: f64) -> Duration {
+    Duration::from_secs_f64((value * 3600.0).max(0.0))
+}
+
+/// Slope of the least-squares line through `points`
+fn least_squares_slope(points: &[(f64, f64)]) -> Option<f64> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    if sxx == 0.0 {
+        return None;
+    }
+    let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    Some(sxy / sxx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: u64 = 60_000;
+
+    fn info(left: u8, right: u8, case: u8, is_charging: bool) -> BatteryInfo {
+        BatteryInfo {
+            left_bud: left,
+            right_bud: right,
+            case,
+            is_charging,
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer_drops_oldest() {
+        let mut history = BatteryHistory::with_capacity(3);
+        for level in 0..5 {
+            history.record(info(level, level, 50, false), level as u64);
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.samples().next().unwrap().battery.left_bud, 2);
+    }
+
+    #[test]
+    fn test_discharge_estimate() {
+        let mut history = BatteryHistory::new();
+        // Left loses 1% every 3 minutes (20%/h), right 1% every 6 minutes
+        for step in 0..10u64 {
+            let left = 80 - step as u8;
+            let right = 80 - (step / 2) as u8;
+            history.record(info(left, right, 60, false), step * 3 * MINUTE);
+        }
+
+        let rate = history.discharge_rate(BatteryComponent::Left).unwrap();
+        assert!((rate - 20.0).abs() < 0.01);
+        let remaining = history.time_to_empty(BatteryComponent::Left).unwrap();
+        // 71% at 20%/h
+        assert_eq!((remaining.as_secs_f64() / 60.0).round(), 213.0);
+        assert!(history.discharge_rate(BatteryComponent::Right).unwrap() < rate);
+        assert_eq!(history.discharge_rate(BatteryComponent::Case), None);
+        assert_eq!(history.time_to_full(BatteryComponent::Left), None);
+    }
+
+    #[test]
+    fn test_charge_estimate_ignores_earlier_discharge() {
+        let mut history = BatteryHistory::new();
+        history.record(info(50, 50, 90, false), 0);
+        history.record(info(40, 40, 90, false), 10 * MINUTE);
+        for step in 0..5u64 {
+            let level = 40 + 5 * step as u8;
+            history.record(info(level, level, 90, true), (20 + 5 * step) * MINUTE);
+        }
+
+        // 5% every 5 minutes
+        assert!((history.charge_rate(BatteryComponent::Left).unwrap() - 60.0).abs() < 0.01);
+        let remaining = history.time_to_full(BatteryComponent::Left).unwrap();
+        assert_eq!((remaining.as_secs_f64() / 60.0).round(), 40.0);
+        assert_eq!(history.time_to_empty(BatteryComponent::Left), None);
+    }
+
+    #[test]
+    fn test_not_enough_data() {
+        let mut history = BatteryHistory::new();
+        assert_eq!(history.rate(BatteryComponent::Left), None);
+        history.record(info(50, 50, 50, false), 0);
+        assert_eq!(history.time_to_empty(BatteryComponent::Left), None);
+        history.record(info(50, 50, 50, false), 0);
+        assert_eq!(history.rate(BatteryComponent::Left), None);
+    }
+
+    #[test]
+    fn test_low_battery_alerts() {
+        let mut history = BatteryHistory::new();
+        assert!(history.record(info(30, 30, 80, false), 0).is_empty());
+        assert_eq!(
+            history.record(info(20, 25, 80, false), MINUTE),
+            vec![LowBattery {
+                component: BatteryComponent::Left,
+                level: 20,
+                threshold: 20,
+            }]
+        );
+        assert!(history
+            .record(info(19, 25, 80, false), 2 * MINUTE)
+            .is_empty());
+
+        // Jumping past two thresholds reports the lowest
+        let alerts = history.record(info(19, 8, 80, false), 3 * MINUTE);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].threshold, 10);
+
+        assert!(history.record(info(4, 4, 80, true), 4 * MINUTE).is_empty());
+    }
+
+    #[test]
+    fn test_first_sample_alerts_once() {
+        let mut history = BatteryHistory::new();
+        history.set_thresholds(&[15]);
+        let alerts = history.record(info(8, 50, 50, false), 0);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].threshold, 15);
+    }
+
+    #[test]
+    fn test_missing_components_do_not_alert() {
+        // AirPods Max report their battery as both buds and the case as 0
+        let mut history = BatteryHistory::for_model(DeviceModel::AirPodsMax);
+        assert!(history.record(info(100, 100, 0, false), 0).is_empty());
+        let alerts = history.record(info(4, 4, 30, false), MINUTE);
+        assert_eq!(
+            alerts.iter().map(|a| a.component).collect::<Vec<_>>(),
+            vec![BatteryComponent::Left, BatteryComponent::Right]
+        );
+
+        // A case out of range reads 0 until it reports again
+        let mut history = BatteryHistory::new();
+        assert!(history.record(info(80, 80, 0, false), 0).is_empty());
+        let alerts = history.record(info(80, 80, 10, false), MINUTE);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].component, BatteryComponent::Case);
+    }
+
+    #[test]
+    fn test_histories_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(BATTERY_FILE);
+        assert!(load_histories(&path).unwrap().is_empty());
+
+        let mut history = BatteryHistory::with_capacity(8);
+        history.record(info(90, 85, 60, false), 1_000);
+        let mut histories = BTreeMap::new();
+        histories.insert("dev".to_string(), history);
+        save_histories(&path, &histories).unwrap();
+
+        assert_eq!(load_histories(&path).unwrap(), histories);
+    }
+}
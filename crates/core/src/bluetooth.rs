@@ -1,5 +1,12 @@
+use crate::advertisement::{ProximityPairing, APPLE_COMPANY_ID};
 use crate::error::Result;
+use futures_util::Stream;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedReceiver;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BluetoothDevice {
@@ -7,6 +14,17 @@ pub struct BluetoothDevice {
     pub name: String,
     pub rssi: i32,
     pub is_connected: bool,
+    /// Advertised manufacturer data by company identifier
+    #[serde(default)]
+    pub manufacturer_data: HashMap<u16, Vec<u8>>,
+}
+
+impl BluetoothDevice {
+    /// The proximity pairing message the device advertises, if any
+    pub fn proximity_pairing(&self) -> Option<ProximityPairing> {
+        let data = self.manufacturer_data.get(&APPLE_COMPANY_ID)?;
+        ProximityPairing::decode(data).ok()
+    }
 }
 
 pub trait BluetoothBackend: Send + Sync {
@@ -35,6 +53,161 @@ pub trait BluetoothBackend: Send + Sync {
     ) -> Result<()>;
 }
 
+impl<T: BluetoothBackend + ?Sized> BluetoothBackend for Box<T> {
+    fn start_scan(&mut self) -> Result<()> {
+        (**self).start_scan()
+    }
+
+    fn stop_scan(&mut self) -> Result<()> {
+        (**self).stop_scan()
+    }
+
+    fn connect(&mut self, address: &str) -> Result<()> {
+        (**self).connect(address)
+    }
+
+    fn disconnect(&mut self, address: &str) -> Result<()> {
+        (**self).disconnect(address)
+    }
+
+    fn write_characteristic(
+        &mut self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+        data: &[u8],
+    ) -> Result<()> {
+        (**self).write_characteristic(address, service_uuid, char_uuid, data)
+    }
+
+    fn read_characteristic(
+        &mut self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> Result<Vec<u8>> {
+        (**self).read_characteristic(address, service_uuid, char_uuid)
+    }
+
+    fn enable_notifications(
+        &mut self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> Result<()> {
+        (**self).enable_notifications(address, service_uuid, char_uuid)
+    }
+}
+
+/// Future returned by [`AsyncBluetoothBackend`] methods
+pub type BackendFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Values notified by one characteristic, in the order they arrived
+///
+/// The stream ends when the device disconnects.
+pub type NotificationStream = Pin<Box<dyn Stream<Item = Vec<u8>> + Send>>;
+
+/// Devices seen by a scan
+///
+/// Known devices come first, then newly found devices and each device again
+/// whenever its RSSI or manufacturer data changes. The stream ends when the
+/// scan is stopped.
+pub type ScanStream = Pin<Box<dyn Stream<Item = BluetoothDevice> + Send>>;
+
+/// Bluetooth backend for async callers
+///
+/// Unlike [`BluetoothBackend`], methods take `&self` so calls can overlap,
+/// and incoming data is delivered as streams. The trait is object safe;
+/// the engine holds its backend as an `Arc<dyn AsyncBluetoothBackend>`.
+pub trait AsyncBluetoothBackend: Send + Sync {
+    /// Start discovering devices
+    fn scan(&self) -> BackendFuture<'_, ScanStream>;
+    /// Stop discovering, ending the scan streams
+    fn stop_scan(&self) -> BackendFuture<'_, ()>;
+    /// Connect to the device at `address`
+    fn connect<'a>(&'a self, address: &'a str) -> BackendFuture<'a, ()>;
+    /// Disconnect from the device at `address`
+    fn disconnect<'a>(&'a self, address: &'a str) -> BackendFuture<'a, ()>;
+    /// Write `data` to a characteristic
+    fn write_characteristic<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+        data: &'a [u8],
+    ) -> BackendFuture<'a, ()>;
+    /// Read a characteristic's value
+    fn read_characteristic<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> BackendFuture<'a, Vec<u8>>;
+    /// Enable notifications on a characteristic and stream its values
+    fn notifications<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> BackendFuture<'a, NotificationStream>;
+}
+
+impl<T: AsyncBluetoothBackend + ?Sized> AsyncBluetoothBackend for Arc<T> {
+    fn scan(&self) -> BackendFuture<'_, ScanStream> {
+        (**self).scan()
+    }
+
+    fn stop_scan(&self) -> BackendFuture<'_, ()> {
+        (**self).stop_scan()
+    }
+
+    fn connect<'a>(&'a self, address: &'a str) -> BackendFuture<'a, ()> {
+        (**self).connect(address)
+    }
+
+    fn disconnect<'a>(&'a self, address: &'a str) -> BackendFuture<'a, ()> {
+        (**self).disconnect(address)
+    }
+
+    fn write_characteristic<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+        data: &'a [u8],
+    ) -> BackendFuture<'a, ()> {
+        (**self).write_characteristic(address, service_uuid, char_uuid, data)
+    }
+
+    fn read_characteristic<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> BackendFuture<'a, Vec<u8>> {
+        (**self).read_characteristic(address, service_uuid, char_uuid)
+    }
+
+    fn notifications<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> BackendFuture<'a, NotificationStream> {
+        (**self).notifications(address, service_uuid, char_uuid)
+    }
+}
+
+/// Stream the values sent on a channel until every sender is gone
+pub(crate) fn channel_stream<T: Send + 'static>(
+    receiver: UnboundedReceiver<T>,
+) -> Pin<Box<dyn Stream<Item = T> + Send>> {
+    Box::pin(futures_util::stream::unfold(
+        receiver,
+        |mut receiver| async move { receiver.recv().await.map(|item| (item, receiver)) },
+    ))
+}
+
 pub struct BluetoothManager;
 
 impl BluetoothManager {
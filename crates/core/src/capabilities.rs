@@ -0,0 +1,179 @@
+//! Per-model capability matrix
+
+use crate::device::{DeviceCapability, DeviceModel};
+use crate::firmware_version_analyzer::FirmwareVersionAnalyzer;
+use crate::registry::ProtocolRegistry;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+/// Maps each device model to the capabilities it supports
+///
+/// The matrix is derived from the protocol registry: a model has a capability
+/// when it supports a message type backed by that capability. Vendor message
+/// types registered at runtime therefore extend the matrix as well.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapabilityMatrix {
+    models: HashMap<DeviceModel, Vec<DeviceCapability>>,
+}
+
+impl CapabilityMatrix {
+    /// Matrix for the built-in protocol registry
+    pub fn builtin() -> &'static CapabilityMatrix {
+        static BUILTIN: OnceLock<CapabilityMatrix> = OnceLock::new();
+        BUILTIN.get_or_init(|| CapabilityMatrix::from_registry(&ProtocolRegistry::builtin()))
+    }
+
+    /// Build the matrix from the message types in a registry
+    pub fn from_registry(registry: &ProtocolRegistry) -> Self {
+        let mut descriptors: Vec<_> = registry.messages().collect();
+        descriptors.sort_by_key(|m| m.opcode);
+
+        let mut matrix = Self::default();
+        for descriptor in descriptors {
+            let Some(capability) = descriptor.capability else {
+                continue;
+            };
+            for model in &descriptor.models {
+                matrix.add(*model, capability);
+            }
+        }
+        matrix
+    }
+
+    /// Add a capability to a model
+    pub fn add(&mut self, model: DeviceModel, capability: DeviceCapability) {
+        let capabilities = self.models.entry(model).or_default();
+        if !capabilities.contains(&capability) {
+            capabilities.push(capability);
+        }
+    }
+
+    /// Capabilities of a model, in opcode order
+    pub fn capabilities_for(&self, model: DeviceModel) -> &[DeviceCapability] {
+        self.models.get(&model).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Whether a model has a capability, regardless of firmware
+    pub fn supports(&self, model: DeviceModel, capability: DeviceCapability) -> bool {
+        self.capabilities_for(model).contains(&capability)
+    }
+
+    /// Capabilities of a model running a particular firmware version
+    ///
+    /// Capabilities that appear in the feature list of any known firmware for
+    /// the model are firmware-gated: they are kept only if `version` lists
+    /// them. Features a version lists beyond the model's base set are added.
+    /// Unknown versions, or versions for another model, leave the base set.
+    pub fn capabilities_for_firmware(
+        &self,
+        model: DeviceModel,
+        version: &str,
+        analyzer: &FirmwareVersionAnalyzer,
+    ) -> Vec<DeviceCapability> {
+        let base = self.capabilities_for(model).to_vec();
+        let model_name = format!("{:?}", model);
+        match analyzer.get_version_info(version) {
+            Some(info) if info.device_model == model_name => {}
+            _ => return base,
+        }
+
+        let listed: Vec<DeviceCapability> = analyzer
+            .extract_features_from_version(version)
+            .iter()
+            .filter_map(|feature| capability_from_feature(feature))
+            .collect();
+        let gated: HashSet<DeviceCapability> = analyzer
+            .get_versions_for_model(&model_name)
+            .into_iter()
+            .flat_map(|info| info.features.iter())
+            .filter_map(|feature| capability_from_feature(feature))
+            .collect();
+
+        let mut capabilities: Vec<DeviceCapability> = base
+            .into_iter()
+            .filter(|c| !gated.contains(c) || listed.contains(c))
+            .collect();
+        for capability in listed {
+            if !capabilities.contains(&capability) {
+                capabilities.push(capability);
+            }
+        }
+        capabilities
+    }
+}
+
+/// Map a firmware feature name to a capability
+///
+/// Feature names are capability names, except `ANC` for noise control.
+pub fn capability_from_feature(feature: &str) -> Option<DeviceCapability> {
+    use DeviceCapability::*;
+    let capability = match feature {
+        "ANC" | "NoiseControl" => NoiseControl,
+        "BatteryMonitoring" => BatteryMonitoring,
+        "AdaptiveTransparency" => AdaptiveTransparency,
+        "EarDetection" => EarDetection,
+        "ConversationAwareness" => ConversationAwareness,
+        "HeadGestures" => HeadGestures,
+        "HearingAid" => HearingAid,
+        "CustomTransparency" => CustomTransparency,
+        "DeviceRename" => DeviceRename,
+        "LongPressActions" => LongPressActions,
+        "Multipoint" => Multipoint,
+        "FirmwareInfo" => FirmwareInfo,
+        "FindMy" => FindMy,
+        "HeartRate" => HeartRate,
+        "SpatialAudio" => SpatialAudio,
+        _ => return None,
+    };
+    Some(capability)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_matrix() {
+        let matrix = CapabilityMatrix::builtin();
+        assert!(matrix.supports(DeviceModel::AirPodsProGen3, DeviceCapability::HeartRate));
+        assert!(!matrix.supports(DeviceModel::AirPodsProGen2, DeviceCapability::HeartRate));
+        assert!(!matrix.supports(DeviceModel::AirPods2, DeviceCapability::NoiseControl));
+        assert!(matrix.supports(DeviceModel::AirPods2, DeviceCapability::BatteryMonitoring));
+        assert_eq!(
+            matrix.capabilities_for(DeviceModel::AirPodsMax)[0],
+            DeviceCapability::BatteryMonitoring
+        );
+    }
+
+    #[test]
+    fn test_firmware_gates_capabilities() {
+        let matrix = CapabilityMatrix::builtin();
+        let analyzer = FirmwareVersionAnalyzer::new();
+        let model = DeviceModel::AirPodsProGen2;
+
+        let old = matrix.capabilities_for_firmware(model, "5D134", &analyzer);
+        assert!(!old.contains(&DeviceCapability::SpatialAudio));
+        assert!(old.contains(&DeviceCapability::NoiseControl));
+        assert!(old.contains(&DeviceCapability::HearingAid));
+
+        let new = matrix.capabilities_for_firmware(model, "5E135", &analyzer);
+        assert!(new.contains(&DeviceCapability::SpatialAudio));
+    }
+
+    #[test]
+    fn test_unknown_firmware_keeps_base_set() {
+        let matrix = CapabilityMatrix::builtin();
+        let analyzer = FirmwareVersionAnalyzer::new();
+        let model = DeviceModel::AirPodsProGen2;
+
+        assert_eq!(
+            matrix.capabilities_for_firmware(model, "9Z999", &analyzer),
+            matrix.capabilities_for(model)
+        );
+        // 5C133 is AirPods Max firmware
+        assert_eq!(
+            matrix.capabilities_for_firmware(model, "5C133", &analyzer),
+            matrix.capabilities_for(model)
+        );
+    }
+}
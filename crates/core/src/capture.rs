@@ -0,0 +1,546 @@
+//! Packet capture recording and replay in btsnoop format
+//!
+//! Frames are stored as HCI ACL packets carrying ATT writes (outgoing) and
+//! ATT handle value notifications (incoming), so Wireshark can open the
+//! files and dissect them down to the AAP payload.
+
+use crate::bluetooth::{
+    AsyncBluetoothBackend, BackendFuture, BluetoothBackend, NotificationStream, ScanStream,
+};
+use crate::error::{Error, Result};
+use crate::payload::Payload;
+use crate::protocol::{FrameDecoder, FrameVersion};
+use crate::Engine;
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+const BTSNOOP_MAGIC: &[u8; 8] = b"btsnoop\0";
+const BTSNOOP_VERSION: u32 = 1;
+/// HCI UART (H4) datalink
+const BTSNOOP_DATALINK_H4: u32 = 1002;
+/// Microseconds between 0000-01-01 and the Unix epoch
+const BTSNOOP_EPOCH_OFFSET: i64 = 0x00DC_DDB3_0F2F_8000;
+
+const H4_ACL: u8 = 0x02;
+const ACL_START_FLAGS: u16 = 0x2000;
+const L2CAP_CID_ATT: u16 = 0x0004;
+const ATT_WRITE_COMMAND: u8 = 0x52;
+const ATT_HANDLE_VALUE_NOTIFICATION: u8 = 0x1B;
+
+/// Connection handle written into captured ACL headers
+pub const CAPTURE_CONNECTION_HANDLE: u16 = 0x0040;
+/// ATT attribute handle used for the AAP characteristic in captures
+pub const CAPTURE_ATT_HANDLE: u16 = 0x0010;
+
+/// Direction of a captured frame relative to this host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// Written to the device
+    Sent,
+    /// Notified by the device
+    Received,
+}
+
+/// A single captured characteristic write or notification
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureRecord {
+    /// Microseconds since the Unix epoch
+    pub timestamp_micros: u64,
+    /// Whether the frame was sent or received
+    pub direction: Direction,
+    /// The frame's bytes
+    pub data: Vec<u8>,
+}
+
+impl CaptureRecord {
+    /// Create a record stamped with the current time
+    pub fn now(direction: Direction, data: Vec<u8>) -> Self {
+        Self {
+            timestamp_micros: chrono::Utc::now().timestamp_micros() as u64,
+            direction,
+            data,
+        }
+    }
+}
+
+/// An ordered sequence of captured frames
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capture {
+    records: Vec<CaptureRecord>,
+}
+
+impl Capture {
+    /// Create an empty capture
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a record
+    pub fn push(&mut self, record: CaptureRecord) {
+        self.records.push(record);
+    }
+
+    /// Records in capture order
+    pub fn records(&self) -> &[CaptureRecord] {
+        &self.records
+    }
+
+    /// Number of records
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether nothing has been captured
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Records received from the device, in capture order
+    pub fn received(&self) -> impl Iterator<Item = &CaptureRecord> {
+        self.records
+            .iter()
+            .filter(|r| r.direction == Direction::Received)
+    }
+
+    /// Write the capture as a btsnoop file
+    pub fn write_btsnoop<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(BTSNOOP_MAGIC)?;
+        writer.write_all(&BTSNOOP_VERSION.to_be_bytes())?;
+        writer.write_all(&BTSNOOP_DATALINK_H4.to_be_bytes())?;
+
+        for record in &self.records {
+            let packet = encode_att_packet(record)?;
+            let flags: u32 = match record.direction {
+                Direction::Sent => 0,
+                Direction::Received => 1,
+            };
+            let timestamp = record.timestamp_micros as i64 + BTSNOOP_EPOCH_OFFSET;
+
+            writer.write_all(&(packet.len() as u32).to_be_bytes())?;
+            writer.write_all(&(packet.len() as u32).to_be_bytes())?;
+            writer.write_all(&flags.to_be_bytes())?;
+            writer.write_all(&0u32.to_be_bytes())?;
+            writer.write_all(&timestamp.to_be_bytes())?;
+            writer.write_all(&packet)?;
+        }
+        Ok(())
+    }
+
+    /// Read a btsnoop file, keeping only ATT writes and notifications
+    pub fn read_btsnoop<R: Read>(mut reader: R) -> Result<Self> {
+        let mut header = [0u8; 16];
+        reader.read_exact(&mut header)?;
+        if &header[0..8] != BTSNOOP_MAGIC {
+            return Err(Error::ParseError("not a btsnoop file".to_string()));
+        }
+        if be_u32(&header[8..12]) != BTSNOOP_VERSION {
+            return Err(Error::VersionMismatch);
+        }
+        let datalink = be_u32(&header[12..16]);
+        if datalink != BTSNOOP_DATALINK_H4 {
+            return Err(Error::ParseError(format!(
+                "unsupported btsnoop datalink {}",
+                datalink
+            )));
+        }
+
+        let mut capture = Capture::new();
+        let mut record_header = [0u8; 24];
+        loop {
+            match read_full(&mut reader, &mut record_header)? {
+                0 => break,
+                24 => {}
+                _ => return Err(Error::InvalidLength),
+            }
+            let included_len = be_u32(&record_header[4..8]) as usize;
+            let flags = be_u32(&record_header[8..12]);
+            let mut timestamp = [0u8; 8];
+            timestamp.copy_from_slice(&record_header[16..24]);
+            let timestamp = i64::from_be_bytes(timestamp) - BTSNOOP_EPOCH_OFFSET;
+
+            let mut packet = vec![0u8; included_len];
+            reader.read_exact(&mut packet)?;
+
+            if let Some(data) = decode_att_packet(&packet) {
+                capture.push(CaptureRecord {
+                    timestamp_micros: timestamp.max(0) as u64,
+                    direction: if flags & 1 == 0 {
+                        Direction::Sent
+                    } else {
+                        Direction::Received
+                    },
+                    data,
+                });
+            }
+        }
+        Ok(capture)
+    }
+
+    /// Save the capture to a btsnoop file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let file = std::fs::File::create(path)?;
+        self.write_btsnoop(std::io::BufWriter::new(file))
+    }
+
+    /// Load a capture from a btsnoop file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = std::fs::File::open(path)?;
+        Self::read_btsnoop(std::io::BufReader::new(file))
+    }
+
+    /// Replay received frames through the engine as if they came from `device_id`
+    ///
+    /// Each notification is fed to a fresh `FrameDecoder` in capture order, so
+    /// fragmented and coalesced frames decode exactly as they did live. Decode
+    /// errors are returned in place rather than aborting the replay.
+    pub fn replay(
+        &self,
+        engine: &Engine,
+        device_id: &str,
+        version: FrameVersion,
+    ) -> Vec<Result<Payload>> {
+        let mut decoder = FrameDecoder::with_version(version);
+        let mut results = Vec::new();
+        for record in self.received() {
+            for message in decoder.push(&record.data) {
+                results.push(message.and_then(|m| engine.handle_message(device_id, &m)));
+            }
+        }
+        if let Err(err) = decoder.finish() {
+            results.push(Err(err));
+        }
+        results
+    }
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Like `read_exact`, but reports how much was read at end of file
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+fn encode_att_packet(record: &CaptureRecord) -> Result<Vec<u8>> {
+    let opcode = match record.direction {
+        Direction::Sent => ATT_WRITE_COMMAND,
+        Direction::Received => ATT_HANDLE_VALUE_NOTIFICATION,
+    };
+    let att_len = 3 + record.data.len();
+    let l2cap_len = u16::try_from(att_len).map_err(|_| Error::InvalidLength)?;
+    let acl_len = u16::try_from(att_len + 4).map_err(|_| Error::InvalidLength)?;
+
+    let mut packet = Vec::with_capacity(5 + 4 + att_len);
+    packet.push(H4_ACL);
+    packet.extend_from_slice(&(CAPTURE_CONNECTION_HANDLE | ACL_START_FLAGS).to_le_bytes());
+    packet.extend_from_slice(&acl_len.to_le_bytes());
+    packet.extend_from_slice(&l2cap_len.to_le_bytes());
+    packet.extend_from_slice(&L2CAP_CID_ATT.to_le_bytes());
+    packet.push(opcode);
+    packet.extend_from_slice(&CAPTURE_ATT_HANDLE.to_le_bytes());
+    packet.extend_from_slice(&record.data);
+    Ok(packet)
+}
+
+fn decode_att_packet(packet: &[u8]) -> Option<Vec<u8>> {
+    if packet.len() < 12 || packet[0] != H4_ACL {
+        return None;
+    }
+    let cid = u16::from_le_bytes([packet[7], packet[8]]);
+    if cid != L2CAP_CID_ATT {
+        return None;
+    }
+    match packet[9] {
+        ATT_WRITE_COMMAND | ATT_HANDLE_VALUE_NOTIFICATION => Some(packet[12..].to_vec()),
+        _ => None,
+    }
+}
+
+/// Backend wrapper that records every frame written or received
+///
+/// Through [`AsyncBluetoothBackend`], as the engine uses it, writes, reads
+/// and every value on a notification stream are captured automatically.
+/// The blocking `BluetoothBackend` trait has no notification callback, so
+/// its callers pass incoming notification bytes to
+/// [`CapturingBackend::record_notification`].
+pub struct CapturingBackend<B> {
+    inner: B,
+    capture: Arc<Mutex<Capture>>,
+}
+
+impl<B> CapturingBackend<B> {
+    /// Wrap `inner`, starting with an empty capture
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            capture: Arc::new(Mutex::new(Capture::new())),
+        }
+    }
+
+    /// Shared handle to the capture being recorded
+    pub fn capture_handle(&self) -> Arc<Mutex<Capture>> {
+        Arc::clone(&self.capture)
+    }
+
+    /// Snapshot of everything recorded so far
+    pub fn capture(&self) -> Capture {
+        self.capture.lock().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    /// Record a notification received from the device
+    pub fn record_notification(&self, data: &[u8]) {
+        self.record(Direction::Received, data);
+    }
+
+    /// The wrapped backend
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// The wrapped backend, mutably; writes made through it are not captured
+    pub fn inner_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    /// Unwrap the backend, dropping this handle to the capture
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    fn record(&self, direction: Direction, data: &[u8]) {
+        record(&self.capture, direction, data);
+    }
+}
+
+fn record(capture: &Mutex<Capture>, direction: Direction, data: &[u8]) {
+    if let Ok(mut capture) = capture.lock() {
+        capture.push(CaptureRecord::now(direction, data.to_vec()));
+    }
+}
+
+impl<B: BluetoothBackend> BluetoothBackend for CapturingBackend<B> {
+    fn start_scan(&mut self) -> Result<()> {
+        self.inner.start_scan()
+    }
+
+    fn stop_scan(&mut self) -> Result<()> {
+        self.inner.stop_scan()
+    }
+
+    fn connect(&mut self, address: &str) -> Result<()> {
+        self.inner.connect(address)
+    }
+
+    fn disconnect(&mut self, address: &str) -> Result<()> {
+        self.inner.disconnect(address)
+    }
+
+    fn write_characteristic(
+        &mut self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+        data: &[u8],
+    ) -> Result<()> {
+        self.record(Direction::Sent, data);
+        self.inner
+            .write_characteristic(address, service_uuid, char_uuid, data)
+    }
+
+    fn read_characteristic(
+        &mut self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> Result<Vec<u8>> {
+        let data = self
+            .inner
+            .read_characteristic(address, service_uuid, char_uuid)?;
+        self.record(Direction::Received, &data);
+        Ok(data)
+    }
+
+    fn enable_notifications(
+        &mut self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> Result<()> {
+        self.inner
+            .enable_notifications(address, service_uuid, char_uuid)
+    }
+}
+
+impl<B: AsyncBluetoothBackend> AsyncBluetoothBackend for CapturingBackend<B> {
+    fn scan(&self) -> BackendFuture<'_, ScanStream> {
+        self.inner.scan()
+    }
+
+    fn stop_scan(&self) -> BackendFuture<'_, ()> {
+        self.inner.stop_scan()
+    }
+
+    fn connect<'a>(&'a self, address: &'a str) -> BackendFuture<'a, ()> {
+        self.inner.connect(address)
+    }
+
+    fn disconnect<'a>(&'a self, address: &'a str) -> BackendFuture<'a, ()> {
+        self.inner.disconnect(address)
+    }
+
+    fn write_characteristic<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+        data: &'a [u8],
+    ) -> BackendFuture<'a, ()> {
+        self.record(Direction::Sent, data);
+        self.inner
+            .write_characteristic(address, service_uuid, char_uuid, data)
+    }
+
+    fn read_characteristic<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> BackendFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            let data = self
+                .inner
+                .read_characteristic(address, service_uuid, char_uuid)
+                .await?;
+            self.record(Direction::Received, &data);
+            Ok(data)
+        })
+    }
+
+    fn notifications<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> BackendFuture<'a, NotificationStream> {
+        Box::pin(async move {
+            let values = self
+                .inner
+                .notifications(address, service_uuid, char_uuid)
+                .await?;
+            let capture = Arc::clone(&self.capture);
+            let values = values.inspect(move |data| record(&capture, Direction::Received, data));
+            Ok(Box::pin(values) as NotificationStream)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{Message, MessageType};
+
+    fn sample_capture() -> Capture {
+        let mut capture = Capture::new();
+        capture.push(CaptureRecord {
+            timestamp_micros: 1_700_000_000_000_000,
+            direction: Direction::Sent,
+            data: Message::new(MessageType::AncControl, vec![1])
+                .serialize()
+                .unwrap(),
+        });
+        capture.push(CaptureRecord {
+            timestamp_micros: 1_700_000_000_050_000,
+            direction: Direction::Received,
+            data: Message::new(MessageType::BatteryStatus, vec![80, 70, 100, 0])
+                .serialize()
+                .unwrap(),
+        });
+        capture
+    }
+
+    #[test]
+    fn test_btsnoop_round_trip() {
+        let capture = sample_capture();
+        let mut bytes = Vec::new();
+        capture.write_btsnoop(&mut bytes).unwrap();
+        assert_eq!(&bytes[0..8], BTSNOOP_MAGIC);
+
+        let parsed = Capture::read_btsnoop(bytes.as_slice()).unwrap();
+        assert_eq!(parsed, capture);
+    }
+
+    #[test]
+    fn test_btsnoop_rejects_garbage() {
+        let result = Capture::read_btsnoop(&b"notsnoop00000000"[..]);
+        assert!(matches!(result, Err(Error::ParseError(_))));
+
+        let mut bytes = Vec::new();
+        sample_capture().write_btsnoop(&mut bytes).unwrap();
+        bytes.truncate(bytes.len() - 30);
+        assert!(Capture::read_btsnoop(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_replay_drives_engine() {
+        let engine = Engine::new();
+        let results = sample_capture().replay(&engine, "dev", FrameVersion::V1);
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Ok(Payload::BatteryStatus(_))));
+        assert_eq!(
+            engine
+                .protocol_analyzer()
+                .observed_message_types()
+                .get(&0x01),
+            Some(&1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_captures_engine_traffic() {
+        use crate::device::{Device, DeviceModel};
+        use crate::models::AncMode;
+        use crate::protocol::Message;
+        use crate::simulator::{SimulatedBackend, SimulatedDevice};
+
+        let simulator = SimulatedBackend::new();
+        simulator.add_device(SimulatedDevice::new(
+            "sim",
+            "AirPods Pro",
+            DeviceModel::AirPodsProGen2,
+        ));
+        let backend = CapturingBackend::new(simulator);
+        let capture = backend.capture_handle();
+        let engine = Arc::new(Engine::with_backend(backend));
+        engine.register_device(Device::new(
+            "sim".to_string(),
+            "AirPods Pro".to_string(),
+            DeviceModel::AirPodsProGen2,
+        ));
+
+        engine.connect("sim").await.unwrap();
+        engine.set_anc("sim", AncMode::Transparency).await.unwrap();
+
+        let capture = capture.lock().unwrap().clone();
+        let frames = |direction| {
+            capture
+                .records()
+                .iter()
+                .filter(|r| r.direction == direction)
+                .map(|r| Message::parse(&r.data).unwrap().msg_type)
+                .collect::<Vec<_>>()
+        };
+        assert!(frames(Direction::Sent).contains(&MessageType::AncControl));
+        assert!(frames(Direction::Received).contains(&MessageType::AncControl));
+    }
+}
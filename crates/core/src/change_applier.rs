@@ -62,7 +62,10 @@ impl ChangeLog {
     }
 
     pub fn get_changes_by_type(&self, change_type: ChangeType) -> Vec<&AppliedChange> {
-        self.changes.iter().filter(|c| c.change_type == change_type).collect()
+        self.changes
+            .iter()
+            .filter(|c| c.change_type == change_type)
+            .collect()
     }
 
     pub fn get_failed_changes(&self) -> Vec<&AppliedChange> {
@@ -77,11 +80,17 @@ impl ChangeLog {
         let mut report = String::new();
         report.push_str("=== CHANGE APPLICATION REPORT ===\n\n");
 
-        report.push_str(&format!("Period: {} to {}\n", self.start_time, self.end_time));
+        report.push_str(&format!(
+            "Period: {} to {}\n",
+            self.start_time, self.end_time
+        ));
         report.push_str(&format!("Total Changes: {}\n", self.total_changes));
         report.push_str(&format!("  Successful: {}\n", self.successful_changes));
         report.push_str(&format!("  Failed: {}\n", self.failed_changes));
-        report.push_str(&format!("Success Rate: {:.1}%\n\n", self.get_success_rate()));
+        report.push_str(&format!(
+            "Success Rate: {:.1}%\n\n",
+            self.get_success_rate()
+        ));
 
         let adds = self.get_changes_by_type(ChangeType::Add).len();
         let modifies = self.get_changes_by_type(ChangeType::Modify).len();
@@ -98,8 +107,14 @@ impl ChangeLog {
         if !failed.is_empty() {
             report.push_str(&format!("Failed Changes: {}\n", failed.len()));
             for change in failed {
-                report.push_str(&format!("  {} - {}\n", change.file_path, 
-                    change.error_message.as_ref().unwrap_or(&"Unknown error".to_string())));
+                report.push_str(&format!(
+                    "  {} - {}\n",
+                    change.file_path,
+                    change
+                        .error_message
+                        .as_ref()
+                        .unwrap_or(&"Unknown error".to_string())
+                ));
             }
         }
 
@@ -136,11 +151,17 @@ impl ChangeApplier {
             success: true,
             error_message: None,
         };
-        self.applied_changes.insert(change.change_id.clone(), change.clone());
+        self.applied_changes
+            .insert(change.change_id.clone(), change.clone());
         change
     }
 
-    pub fn apply_modify(&mut self, file_path: String, old_content: String, new_content: String) -> AppliedChange {
+    pub fn apply_modify(
+        &mut self,
+        file_path: String,
+        old_content: String,
+        new_content: String,
+    ) -> AppliedChange {
         let change = AppliedChange {
             change_id: format!("modify-{}", file_path.replace('/', "-")),
             file_path: file_path.clone(),
@@ -152,7 +173,8 @@ impl ChangeApplier {
             success: true,
             error_message: None,
         };
-        self.applied_changes.insert(change.change_id.clone(), change.clone());
+        self.applied_changes
+            .insert(change.change_id.clone(), change.clone());
         change
     }
 
@@ -168,13 +190,18 @@ impl ChangeApplier {
             success: true,
             error_message: None,
         };
-        self.applied_changes.insert(change.change_id.clone(), change.clone());
+        self.applied_changes
+            .insert(change.change_id.clone(), change.clone());
         change
     }
 
     pub fn apply_rename(&mut self, old_path: String, new_path: String) -> AppliedChange {
         let change = AppliedChange {
-            change_id: format!("rename-{}-{}", old_path.replace('/', "-"), new_path.replace('/', "-")),
+            change_id: format!(
+                "rename-{}-{}",
+                old_path.replace('/', "-"),
+                new_path.replace('/', "-")
+            ),
             file_path: new_path,
             change_type: ChangeType::Rename,
             old_content: Some(old_path),
@@ -184,7 +211,8 @@ impl ChangeApplier {
             success: true,
             error_message: None,
         };
-        self.applied_changes.insert(change.change_id.clone(), change.clone());
+        self.applied_changes
+            .insert(change.change_id.clone(), change.clone());
         change
     }
 
@@ -245,7 +273,11 @@ mod tests {
                 timestamp: "2025-11-21T00:00:00Z".to_string(),
                 applied_by: "dev".to_string(),
                 success: i < 8,
-                error_message: if i >= 8 { Some("Error".to_string()) } else { None },
+                error_message: if i >= 8 {
+                    Some("Error".to_string())
+                } else {
+                    None
+                },
             };
             log.add_change(change);
         }
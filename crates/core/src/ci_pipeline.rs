@@ -56,7 +56,12 @@ impl CIPipeline {
         self.stages.push(stage);
     }
 
-    pub fn update_stage_status(&mut self, stage_id: &str, status: StageStatus, duration_ms: u64) -> bool {
+    pub fn update_stage_status(
+        &mut self,
+        stage_id: &str,
+        status: StageStatus,
+        duration_ms: u64,
+    ) -> bool {
         if let Some(stage) = self.stages.iter_mut().find(|s| s.stage_id == stage_id) {
             stage.status = status;
             stage.duration_ms = duration_ms;
@@ -106,12 +111,19 @@ impl CIPipeline {
         report.push_str(&format!("  Completed: {}\n", self.completed_stages));
         report.push_str(&format!("  Failed: {}\n", self.failed_stages));
         report.push_str(&format!("Success Rate: {:.1}%\n", self.get_success_rate()));
-        report.push_str(&format!("Duration: {:.1}s\n\n", self.total_duration_ms as f32 / 1000.0));
+        report.push_str(&format!(
+            "Duration: {:.1}s\n\n",
+            self.total_duration_ms as f32 / 1000.0
+        ));
 
         report.push_str("Stages:\n");
         for stage in &self.stages {
-            report.push_str(&format!("  {} ({:?}) - {:.1}s\n", 
-                stage.stage_name, stage.status, stage.duration_ms as f32 / 1000.0));
+            report.push_str(&format!(
+                "  {} ({:?}) - {:.1}s\n",
+                stage.stage_name,
+                stage.status,
+                stage.duration_ms as f32 / 1000.0
+            ));
         }
 
         report
@@ -120,7 +132,11 @@ impl CIPipeline {
 
 impl Default for CIPipeline {
     fn default() -> Self {
-        Self::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string())
+        Self::new(
+            "pipeline-1".to_string(),
+            "main".to_string(),
+            "abc123".to_string(),
+        )
     }
 }
 
@@ -130,14 +146,22 @@ mod tests {
 
     #[test]
     fn test_pipeline_creation() {
-        let pipeline = CIPipeline::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string());
+        let pipeline = CIPipeline::new(
+            "pipeline-1".to_string(),
+            "main".to_string(),
+            "abc123".to_string(),
+        );
         assert_eq!(pipeline.pipeline_id, "pipeline-1");
         assert_eq!(pipeline.total_stages, 0);
     }
 
     #[test]
     fn test_add_stage() {
-        let mut pipeline = CIPipeline::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string());
+        let mut pipeline = CIPipeline::new(
+            "pipeline-1".to_string(),
+            "main".to_string(),
+            "abc123".to_string(),
+        );
         let stage = PipelineStage {
             stage_id: "stage-1".to_string(),
             stage_name: "Build".to_string(),
@@ -154,7 +178,11 @@ mod tests {
 
     #[test]
     fn test_update_stage_status() {
-        let mut pipeline = CIPipeline::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string());
+        let mut pipeline = CIPipeline::new(
+            "pipeline-1".to_string(),
+            "main".to_string(),
+            "abc123".to_string(),
+        );
         let stage = PipelineStage {
             stage_id: "stage-1".to_string(),
             stage_name: "Build".to_string(),
@@ -172,7 +200,11 @@ mod tests {
 
     #[test]
     fn test_is_successful() {
-        let mut pipeline = CIPipeline::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string());
+        let mut pipeline = CIPipeline::new(
+            "pipeline-1".to_string(),
+            "main".to_string(),
+            "abc123".to_string(),
+        );
         let stage = PipelineStage {
             stage_id: "stage-1".to_string(),
             stage_name: "Build".to_string(),
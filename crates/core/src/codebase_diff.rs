@@ -133,7 +133,10 @@ impl CodebaseDiffReport {
         report.push_str("File Changes:\n");
         for diff in &self.file_diffs {
             report.push_str(&format!("  {} ({:?})\n", diff.file_path, diff.status));
-            report.push_str(&format!("    +{} -{} ~{}\n", diff.lines_added, diff.lines_removed, diff.lines_modified));
+            report.push_str(&format!(
+                "    +{} -{} ~{}\n",
+                diff.lines_added, diff.lines_removed, diff.lines_modified
+            ));
         }
 
         report
@@ -142,7 +145,11 @@ impl CodebaseDiffReport {
 
 impl Default for CodebaseDiffReport {
     fn default() -> Self {
-        Self::new("1.0.0".to_string(), "1.0.0".to_string(), "1.0.0".to_string())
+        Self::new(
+            "1.0.0".to_string(),
+            "1.0.0".to_string(),
+            "1.0.0".to_string(),
+        )
     }
 }
 
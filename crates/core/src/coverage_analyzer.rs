@@ -126,20 +126,45 @@ impl CoverageReport {
         report.push_str(&format!("Report: {}\n", self.report_id));
         report.push_str(&format!("Timestamp: {}\n\n", self.timestamp));
 
-        report.push_str(&format!("Line Coverage: {:.1}%\n", self.get_line_coverage()));
-        report.push_str(&format!("  {} / {} lines\n\n", self.total_lines_covered, self.total_lines));
-
-        report.push_str(&format!("Branch Coverage: {:.1}%\n", self.get_branch_coverage()));
-        report.push_str(&format!("  {} / {} branches\n\n", self.total_branches_covered, self.total_branches));
-
-        report.push_str(&format!("Function Coverage: {:.1}%\n", self.get_function_coverage()));
-        report.push_str(&format!("  {} / {} functions\n\n", self.total_functions_covered, self.total_functions));
-
-        report.push_str(&format!("Overall Coverage: {:.1}%\n\n", self.get_overall_coverage()));
+        report.push_str(&format!(
+            "Line Coverage: {:.1}%\n",
+            self.get_line_coverage()
+        ));
+        report.push_str(&format!(
+            "  {} / {} lines\n\n",
+            self.total_lines_covered, self.total_lines
+        ));
+
+        report.push_str(&format!(
+            "Branch Coverage: {:.1}%\n",
+            self.get_branch_coverage()
+        ));
+        report.push_str(&format!(
+            "  {} / {} branches\n\n",
+            self.total_branches_covered, self.total_branches
+        ));
+
+        report.push_str(&format!(
+            "Function Coverage: {:.1}%\n",
+            self.get_function_coverage()
+        ));
+        report.push_str(&format!(
+            "  {} / {} functions\n\n",
+            self.total_functions_covered, self.total_functions
+        ));
+
+        report.push_str(&format!(
+            "Overall Coverage: {:.1}%\n\n",
+            self.get_overall_coverage()
+        ));
 
         report.push_str("Files:\n");
         for metrics in &self.metrics {
-            report.push_str(&format!("  {} - {:.1}%\n", metrics.file_path, metrics.get_overall_coverage()));
+            report.push_str(&format!(
+                "  {} - {:.1}%\n",
+                metrics.file_path,
+                metrics.get_overall_coverage()
+            ));
         }
 
         report
@@ -1,7 +1,10 @@
 use crate::error::Result;
+use aes_gcm::{
+    aead::{Aead, AeadCore, OsRng, Payload},
+    Aes256Gcm, KeyInit,
+};
 use hkdf::Hkdf;
 use sha2::Sha256;
-use aes_gcm::{Aes256Gcm, KeyInit, aead::{Aead, Payload}};
 use zeroize::Zeroize;
 
 pub struct Crypto;
@@ -24,6 +27,16 @@ impl Crypto {
         constant_time_eq(&calculated, hash)
     }
 
+    /// Fresh random 96-bit nonce for `encrypt`
+    pub fn generate_nonce() -> [u8; 12] {
+        Aes256Gcm::generate_nonce(&mut OsRng).into()
+    }
+
+    /// Fresh random 256-bit key
+    pub fn generate_key() -> SecureKey {
+        SecureKey::new(Aes256Gcm::generate_key(&mut OsRng).to_vec())
+    }
+
     pub fn encrypt(key: &[u8], plaintext: &[u8], nonce: &[u8]) -> Result<Vec<u8>> {
         if key.len() != 32 {
             return Err(crate::error::Error::CryptoError);
@@ -31,10 +44,11 @@ impl Crypto {
         if nonce.len() != 12 {
             return Err(crate::error::Error::CryptoError);
         }
-        let cipher = Aes256Gcm::new_from_slice(key)
-            .map_err(|_| crate::error::Error::CryptoError)?;
+        let cipher =
+            Aes256Gcm::new_from_slice(key).map_err(|_| crate::error::Error::CryptoError)?;
         let nonce_arr = aes_gcm::Nonce::from_slice(nonce);
-        cipher.encrypt(nonce_arr, Payload::from(plaintext))
+        cipher
+            .encrypt(nonce_arr, Payload::from(plaintext))
             .map_err(|_| crate::error::Error::CryptoError)
     }
 
@@ -45,10 +59,11 @@ impl Crypto {
         if nonce.len() != 12 {
             return Err(crate::error::Error::CryptoError);
         }
-        let cipher = Aes256Gcm::new_from_slice(key)
-            .map_err(|_| crate::error::Error::CryptoError)?;
+        let cipher =
+            Aes256Gcm::new_from_slice(key).map_err(|_| crate::error::Error::CryptoError)?;
         let nonce_arr = aes_gcm::Nonce::from_slice(nonce);
-        cipher.decrypt(nonce_arr, Payload::from(ciphertext))
+        cipher
+            .decrypt(nonce_arr, Payload::from(ciphertext))
             .map_err(|_| crate::error::Error::CryptoError)
     }
 }
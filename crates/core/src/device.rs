@@ -1,8 +1,16 @@
-use crate::state::DeviceState;
+use crate::capabilities::CapabilityMatrix;
+use crate::error::{Error, Result};
+use crate::events::{Event, EventBus, EventType};
+use crate::firmware_version_analyzer::FirmwareVersionAnalyzer;
+use crate::payload::Payload;
+use crate::protocol::Message;
+use crate::registry;
+use crate::state::{DeviceState, DeviceStateInfo};
+use crate::state_machine::{ConnectionStateMachine, StateTransition, TransitionHook};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DeviceModel {
     AirPods2,
     AirPods3,
@@ -38,20 +46,26 @@ pub struct Device {
     id: String,
     name: String,
     model: DeviceModel,
-    state: DeviceState,
+    connection: ConnectionStateMachine,
+    state_info: DeviceStateInfo,
     capabilities: Vec<DeviceCapability>,
     metadata: HashMap<String, String>,
+    #[serde(skip)]
+    event_bus: Option<EventBus>,
 }
 
 impl Device {
+    /// Create a device with the capabilities of its model
     pub fn new(id: String, name: String, model: DeviceModel) -> Self {
         Self {
             id,
             name,
             model,
-            state: DeviceState::Disconnected,
-            capabilities: Vec::new(),
+            connection: ConnectionStateMachine::new(),
+            state_info: DeviceStateInfo::new(),
+            capabilities: CapabilityMatrix::builtin().capabilities_for(model).to_vec(),
             metadata: HashMap::new(),
+            event_bus: None,
         }
     }
 
@@ -72,11 +86,70 @@ impl Device {
     }
 
     pub fn state(&self) -> &DeviceState {
-        &self.state
+        self.connection.state()
+    }
+
+    /// Move to a new connection state
+    ///
+    /// Fails with `InvalidState` for transitions the state machine does not
+    /// allow. Successful transitions are recorded in the history and published
+    /// as `StateChanged` on the attached event bus.
+    pub fn set_state(&mut self, state: DeviceState) -> Result<StateTransition> {
+        let transition = self.connection.transition(state)?;
+        self.state_info.set_connection_state(state);
+        if let Some(bus) = &self.event_bus {
+            bus.emit(Event::new(
+                EventType::StateChanged {
+                    from: transition.from,
+                    to: transition.to,
+                },
+                &self.id,
+            ));
+        }
+        Ok(transition)
+    }
+
+    /// Live state snapshot
+    pub fn state_info(&self) -> &DeviceStateInfo {
+        &self.state_info
+    }
+
+    /// Copy of the current state snapshot, for diffing against a later one
+    pub fn snapshot(&self) -> DeviceStateInfo {
+        self.state_info.clone()
+    }
+
+    /// Replace the state snapshot with one saved earlier
+    ///
+    /// The connection state is kept, since a saved connection is not live.
+    pub fn restore_snapshot(&mut self, mut snapshot: DeviceStateInfo) {
+        snapshot.connection_state = *self.connection.state();
+        self.state_info = snapshot;
+    }
+
+    /// Update the state snapshot from a decoded payload
+    pub fn apply_payload(&mut self, payload: &Payload) -> bool {
+        self.state_info.apply(payload)
+    }
+
+    /// Past connection state transitions, oldest first
+    pub fn state_history(&self) -> impl Iterator<Item = &StateTransition> {
+        self.connection.history()
     }
 
-    pub fn set_state(&mut self, state: DeviceState) {
-        self.state = state;
+    /// Register a hook run after entering `state`
+    pub fn on_state_enter(&mut self, state: DeviceState, hook: TransitionHook) {
+        self.connection.on_enter(state, hook);
+    }
+
+    /// Register a hook run before leaving `state`
+    pub fn on_state_exit(&mut self, state: DeviceState, hook: TransitionHook) {
+        self.connection.on_exit(state, hook);
+    }
+
+    /// Publish state changes on `bus`
+    pub fn attach_event_bus(&mut self, bus: EventBus) {
+        self.event_bus = Some(bus);
     }
 
     pub fn capabilities(&self) -> &[DeviceCapability] {
@@ -93,6 +166,39 @@ impl Device {
         self.capabilities.contains(&capability)
     }
 
+    /// Fail with `UnsupportedDevice` unless the device has `capability`
+    pub fn require_capability(&self, capability: DeviceCapability) -> Result<()> {
+        if self.has_capability(capability) {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedDevice)
+        }
+    }
+
+    /// Narrow the model's capabilities to what a firmware version supports
+    pub fn refine_capabilities(
+        &mut self,
+        firmware_version: &str,
+        analyzer: &FirmwareVersionAnalyzer,
+    ) {
+        self.capabilities = CapabilityMatrix::builtin().capabilities_for_firmware(
+            self.model,
+            firmware_version,
+            analyzer,
+        );
+    }
+
+    /// Encode a command for this device
+    ///
+    /// Fails with `UnsupportedDevice` if the device lacks the capability
+    /// backing the payload's message type. Unregistered opcodes are not gated.
+    pub fn command(&self, payload: &Payload) -> Result<Message> {
+        if let Some(builtin) = registry::builtin_message_for(payload.message_type()) {
+            self.require_capability(builtin.capability)?;
+        }
+        payload.to_message()
+    }
+
     pub fn set_metadata(&mut self, key: String, value: String) {
         self.metadata.insert(key, value);
     }
@@ -100,4 +206,9 @@ impl Device {
     pub fn get_metadata(&self, key: &str) -> Option<&str> {
         self.metadata.get(key).map(|s| s.as_str())
     }
+
+    /// All metadata entries
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
 }
@@ -0,0 +1,474 @@
+//! Wireshark-style dissection of raw AAP frames
+
+use crate::error::{Error, Result};
+use crate::payload::Payload;
+use crate::protocol::{FrameVersion, Message};
+use crate::registry::{FieldDescriptor, FieldKind, ProtocolRegistry};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// One node of a dissection tree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DissectedField {
+    /// Field name, as in the registry
+    pub name: String,
+    /// Byte offset from the start of the frame
+    pub offset: usize,
+    /// Length in bytes
+    pub length: usize,
+    /// Human-readable decoded value
+    pub value: String,
+    /// Fields nested inside this one
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub children: Vec<DissectedField>,
+}
+
+impl DissectedField {
+    fn new(name: &str, offset: usize, length: usize, value: String) -> Self {
+        Self {
+            name: name.to_string(),
+            offset,
+            length,
+            value,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// Structured view of a single frame
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dissection {
+    /// Opcode byte, `None` when the frame is too short to carry one
+    pub opcode: Option<u8>,
+    /// Registry name of the message
+    pub message_name: String,
+    /// Length of the frame in bytes
+    pub frame_length: usize,
+    /// `None` when the frame is too short to carry a CRC
+    pub crc_valid: Option<bool>,
+    /// Top-level fields, in frame order
+    pub fields: Vec<DissectedField>,
+    /// Problems found while dissecting, such as truncation
+    pub warnings: Vec<String>,
+}
+
+impl Dissection {
+    /// Render as an indented text tree
+    pub fn to_text(&self) -> String {
+        let mut out = format!(
+            "AAP Frame ({} bytes): {}\n",
+            self.frame_length, self.message_name
+        );
+        for field in &self.fields {
+            render_field(&mut out, field, 1);
+        }
+        for warning in &self.warnings {
+            out.push_str(&format!("  [warning] {}\n", warning));
+        }
+        out
+    }
+
+    /// Render as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| Error::ParseError(e.to_string()))
+    }
+}
+
+impl fmt::Display for Dissection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_text())
+    }
+}
+
+fn render_field(out: &mut String, field: &DissectedField, depth: usize) {
+    let span = if field.length <= 1 {
+        format!("[{}]", field.offset)
+    } else {
+        format!("[{}..{}]", field.offset, field.offset + field.length)
+    };
+    out.push_str(&format!(
+        "{}{} {}: {}\n",
+        "  ".repeat(depth),
+        span,
+        field.name,
+        field.value
+    ));
+    for child in &field.children {
+        render_field(out, child, depth + 1);
+    }
+}
+
+/// Turns raw frames into dissection trees using the protocol registry
+pub struct Dissector {
+    registry: ProtocolRegistry,
+    version: FrameVersion,
+}
+
+impl Dissector {
+    /// Create a dissector for `V1` frames using the built-in registry
+    pub fn new() -> Self {
+        Self::with_registry(ProtocolRegistry::builtin())
+    }
+
+    /// Create a dissector that also knows vendor opcodes
+    pub fn with_registry(registry: ProtocolRegistry) -> Self {
+        Self {
+            registry,
+            version: FrameVersion::default(),
+        }
+    }
+
+    /// Dissect frames using the given frame version
+    pub fn with_version(mut self, version: FrameVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Dissect a single raw frame
+    ///
+    /// Never fails: truncated or corrupt input yields a partial tree with
+    /// warnings explaining what is missing.
+    pub fn dissect(&self, frame: &[u8]) -> Dissection {
+        let mut dissection = Dissection {
+            opcode: frame.first().copied(),
+            message_name: "Malformed".to_string(),
+            frame_length: frame.len(),
+            crc_valid: None,
+            fields: Vec::new(),
+            warnings: Vec::new(),
+        };
+
+        let opcode = match frame.first() {
+            Some(opcode) => *opcode,
+            None => {
+                dissection.warnings.push("empty frame".to_string());
+                return dissection;
+            }
+        };
+        let descriptor = self.registry.message(opcode);
+        dissection.message_name = descriptor
+            .map(|d| d.name.clone())
+            .unwrap_or_else(|| format!("Unknown(0x{:02X})", opcode));
+        dissection.fields.push(DissectedField::new(
+            "type",
+            0,
+            1,
+            format!(
+                "0x{:02X} ({})",
+                opcode,
+                descriptor.map_or("unknown", |d| d.name.as_str())
+            ),
+        ));
+
+        let header_len = self.version.header_len();
+        if frame.len() < header_len {
+            dissection.warnings.push(format!(
+                "truncated header: {} of {} bytes",
+                frame.len(),
+                header_len
+            ));
+            return dissection;
+        }
+        let payload_len = match self.version {
+            FrameVersion::V1 => frame[1] as usize,
+            FrameVersion::V2 => u16::from_be_bytes([frame[1], frame[2]]) as usize,
+        };
+        dissection.fields.push(DissectedField::new(
+            "length",
+            1,
+            header_len - 1,
+            payload_len.to_string(),
+        ));
+
+        let available = (frame.len() - header_len).min(payload_len);
+        let payload = &frame[header_len..header_len + available];
+        let mut payload_node = DissectedField::new(
+            "payload",
+            header_len,
+            available,
+            format!("{} bytes", payload_len),
+        );
+        let default_fields = [FieldDescriptor {
+            name: "data".to_string(),
+            kind: FieldKind::Bytes,
+        }];
+        let fields = descriptor
+            .map(|d| d.fields.as_slice())
+            .filter(|f| !f.is_empty())
+            .unwrap_or(&default_fields);
+        payload_node.children =
+            dissect_fields(fields, payload, header_len, &mut dissection.warnings);
+        if available < payload_len {
+            dissection.warnings.push(format!(
+                "truncated payload: {} of {} bytes",
+                available, payload_len
+            ));
+        } else if let Ok(decoded) = Payload::decode(self.registry.message_type(opcode), payload) {
+            payload_node.value = format!("{} bytes: {:?}", payload_len, decoded);
+        }
+        dissection.fields.push(payload_node);
+
+        let crc_offset = header_len + payload_len;
+        if frame.len() < crc_offset + 2 {
+            dissection.warnings.push("missing CRC".to_string());
+            return dissection;
+        }
+        let crc = u16::from_be_bytes([frame[crc_offset], frame[crc_offset + 1]]);
+        let expected = Message::calculate_crc(payload);
+        let crc_valid = crc == expected;
+        dissection.crc_valid = Some(crc_valid);
+        dissection.fields.push(DissectedField::new(
+            "crc",
+            crc_offset,
+            2,
+            if crc_valid {
+                format!("0x{:04X} (valid)", crc)
+            } else {
+                format!("0x{:04X} (invalid, expected 0x{:04X})", crc, expected)
+            },
+        ));
+
+        if frame.len() > crc_offset + 2 {
+            dissection.warnings.push(format!(
+                "{} trailing bytes after frame",
+                frame.len() - crc_offset - 2
+            ));
+        }
+        dissection
+    }
+
+    /// Dissect a frame given as a hex string, ignoring whitespace and `:`
+    pub fn dissect_hex(&self, hex: &str) -> Result<Dissection> {
+        Ok(self.dissect(&parse_hex(hex)?))
+    }
+}
+
+impl Default for Dissector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn dissect_fields(
+    fields: &[FieldDescriptor],
+    payload: &[u8],
+    base_offset: usize,
+    warnings: &mut Vec<String>,
+) -> Vec<DissectedField> {
+    let mut nodes = Vec::new();
+    let mut cursor = 0;
+    for field in fields {
+        let rest = &payload[cursor..];
+        let width = match field.kind {
+            FieldKind::U8 | FieldKind::Bool | FieldKind::Flags | FieldKind::Enum => 1,
+            FieldKind::U16 => 2,
+            FieldKind::U32 | FieldKind::F32 => 4,
+            FieldKind::U64 | FieldKind::F64 => 8,
+            FieldKind::Utf8 | FieldKind::Bytes => rest.len(),
+        };
+        if rest.len() < width {
+            // Optional trailing fields such as the battery charging flag
+            if !rest.is_empty() {
+                warnings.push(format!("field {} truncated", field.name));
+            }
+            break;
+        }
+        let bytes = &rest[..width];
+        let value = match field.kind {
+            FieldKind::U8 | FieldKind::Enum => bytes[0].to_string(),
+            FieldKind::Bool => (bytes[0] != 0).to_string(),
+            FieldKind::Flags => format!("0b{:08b}", bytes[0]),
+            FieldKind::U16 => u16::from_be_bytes([bytes[0], bytes[1]]).to_string(),
+            FieldKind::U32 => be_uint(bytes).to_string(),
+            FieldKind::U64 => be_uint(bytes).to_string(),
+            FieldKind::F32 => f32::from_bits(be_uint(bytes) as u32).to_string(),
+            FieldKind::F64 => f64::from_bits(be_uint(bytes)).to_string(),
+            FieldKind::Utf8 => format!("{:?}", String::from_utf8_lossy(bytes)),
+            FieldKind::Bytes => to_hex(bytes),
+        };
+        nodes.push(DissectedField::new(
+            &field.name,
+            base_offset + cursor,
+            width,
+            value,
+        ));
+        cursor += width;
+    }
+    if cursor < payload.len() && !nodes.is_empty() {
+        warnings.push(format!("{} unparsed payload bytes", payload.len() - cursor));
+    }
+    nodes
+}
+
+fn be_uint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return "(empty)".to_string();
+    }
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse a hex string such as `01 03 32:3C 46`
+pub fn parse_hex(hex: &str) -> Result<Vec<u8>> {
+    let digits: String = hex
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect();
+    let digits = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+        .unwrap_or(&digits);
+    if !digits.is_ascii() {
+        return Err(Error::ParseError("non-ASCII character in hex".to_string()));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err(Error::ParseError("odd number of hex digits".to_string()));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| Error::ParseError(format!("invalid hex: {}", &digits[i..i + 2])))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::MessageType;
+
+    fn battery_frame() -> Vec<u8> {
+        Message::new(MessageType::BatteryStatus, vec![50, 60, 70, 1])
+            .serialize()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_dissect_valid_frame() {
+        let dissection = Dissector::new().dissect(&battery_frame());
+        assert_eq!(dissection.message_name, "BatteryStatus");
+        assert_eq!(dissection.crc_valid, Some(true));
+        assert!(dissection.warnings.is_empty());
+
+        let payload = &dissection.fields[2];
+        assert_eq!(payload.children.len(), 4);
+        assert_eq!(payload.children[1].name, "right_bud");
+        assert_eq!(payload.children[1].offset, 3);
+        assert_eq!(payload.children[1].value, "60");
+        assert_eq!(payload.children[3].value, "true");
+
+        let text = dissection.to_text();
+        assert!(text.contains("[0] type: 0x01 (BatteryStatus)"));
+        assert!(text.contains("(valid)"));
+    }
+
+    #[test]
+    fn test_dissect_bad_crc_and_truncation() {
+        let mut frame = battery_frame();
+        let last = frame.len() - 1;
+        frame[last] ^= 0xFF;
+        let dissection = Dissector::new().dissect(&frame);
+        assert_eq!(dissection.crc_valid, Some(false));
+        assert!(dissection.to_text().contains("invalid, expected"));
+
+        let truncated = Dissector::new().dissect(&battery_frame()[..4]);
+        assert_eq!(truncated.crc_valid, None);
+        assert!(truncated
+            .warnings
+            .iter()
+            .any(|w| w.contains("truncated payload")));
+    }
+
+    #[test]
+    fn test_dissect_unknown_opcode() {
+        let dissection = Dissector::new().dissect_hex("42 02 DE AD").unwrap();
+        assert_eq!(dissection.message_name, "Unknown(0x42)");
+        assert_eq!(dissection.fields[2].children[0].value, "DE AD");
+    }
+
+    #[test]
+    fn test_dissect_vendor_opcode() {
+        let mut registry = ProtocolRegistry::builtin();
+        registry
+            .load_json(
+                r#"{ "messages": [{
+                    "opcode": 66,
+                    "name": "VendorSleep",
+                    "fields": [{ "name": "asleep", "kind": "bool" }]
+                }] }"#,
+            )
+            .unwrap();
+        let frame = Message::new(MessageType::Vendor(0x42), vec![1])
+            .serialize()
+            .unwrap();
+
+        let dissection = Dissector::with_registry(registry).dissect(&frame);
+        assert_eq!(dissection.message_name, "VendorSleep");
+        assert_eq!(dissection.crc_valid, Some(true));
+        assert_eq!(dissection.fields[2].children[0].name, "asleep");
+        assert_eq!(dissection.fields[2].children[0].value, "true");
+    }
+
+    #[test]
+    fn test_dissect_vendor_multibyte_fields() {
+        let mut registry = ProtocolRegistry::builtin();
+        registry
+            .load_json(
+                r#"{ "messages": [{
+                    "opcode": 67,
+                    "name": "VendorUptime",
+                    "fields": [
+                        { "name": "boots", "kind": "u16" },
+                        { "name": "seconds", "kind": "u32" }
+                    ]
+                }] }"#,
+            )
+            .unwrap();
+        let frame = Message::new(
+            MessageType::Vendor(0x43),
+            vec![0x01, 0x02, 0, 0, 0x01, 0x00],
+        )
+        .serialize()
+        .unwrap();
+
+        let dissection = Dissector::with_registry(registry).dissect(&frame);
+        let fields = &dissection.fields[2].children;
+        assert_eq!(
+            (fields[0].name.as_str(), fields[0].value.as_str()),
+            ("boots", "258")
+        );
+        assert_eq!(
+            (fields[1].name.as_str(), fields[1].value.as_str()),
+            ("seconds", "256")
+        );
+        assert_eq!(fields[1].offset, 4);
+        assert!(dissection.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_dissect_json() {
+        let json = Dissector::new()
+            .dissect(&battery_frame())
+            .to_json()
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["message_name"], "BatteryStatus");
+        assert_eq!(value["crc_valid"], true);
+        assert_eq!(value["fields"][2]["children"][0]["name"], "left_bud");
+    }
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex("0x01 03:32").unwrap(), vec![0x01, 0x03, 0x32]);
+        assert!(parse_hex("123").is_err());
+        assert!(parse_hex("zz").is_err());
+    }
+}
@@ -55,7 +55,8 @@ impl DMCAScanner {
     }
 
     pub fn check_for_takedown(&self, repo_name: &str) -> bool {
-        self.known_notices.contains_key(repo_name) || self.blocked_repositories.contains(&repo_name.to_string())
+        self.known_notices.contains_key(repo_name)
+            || self.blocked_repositories.contains(&repo_name.to_string())
     }
 
     pub fn get_notice(&self, repo_name: &str) -> Option<&DMCANotice> {
@@ -0,0 +1,1316 @@
+//! Device orchestrator shared by the CLI, FFI and daemon
+
+use crate::advertisement::ProximityPairing;
+use crate::battery::{self, BatteryHistory};
+use crate::bluetooth::{AsyncBluetoothBackend, BluetoothDevice, NotificationStream, ScanStream};
+use crate::device::{Device, DeviceCapability};
+use crate::error::{Error, Result};
+use crate::events::{Event, EventBus, EventType};
+use crate::firmware_version_analyzer::FirmwareVersionAnalyzer;
+use crate::models::{AncMode, MultipointInfo};
+use crate::multipoint;
+use crate::payload::Payload;
+use crate::protocol::{
+    FrameDecoder, Message, MessageType, AAP_CHARACTERISTIC_UUID, AAP_SERVICE_UUID,
+};
+use crate::protocol_analyzer::ProtocolAnalyzer;
+use crate::reconnect::{ReconnectPolicy, ReconnectSupervisor};
+use crate::request::{RequestManager, RequestOptions};
+use crate::state::{BatteryInfo, DeviceState};
+use crate::state_machine::StateTransition;
+use crate::store::DeviceStore;
+use futures_util::StreamExt;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// How long [`Engine::battery`] waits for the device to report
+pub const BATTERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Correlates a connected device's commands with its acknowledgements
+type Requests = Arc<RequestManager<dyn AsyncBluetoothBackend>>;
+
+/// Core engine for managing AirPods devices
+///
+/// The engine owns the Bluetooth backend, the registry of known devices and
+/// the event bus. Every method takes `&self`, so one engine can be shared
+/// behind an `Arc` by any number of threads and tasks. Device ids are the
+/// Bluetooth addresses passed to the backend.
+///
+/// Connecting a device starts a task feeding the backend's notification
+/// stream to [`Engine::handle_notification`]; data received some other way
+/// can be passed to it directly. Commands wait for the device to
+/// acknowledge them, as configured by [`Engine::with_request_options`].
+pub struct Engine {
+    backend: Option<Arc<dyn AsyncBluetoothBackend>>,
+    devices: RwLock<HashMap<String, Device>>,
+    notification_tasks: Mutex<HashMap<String, JoinHandle<()>>>,
+    decoders: Mutex<HashMap<String, FrameDecoder>>,
+    requests: Mutex<HashMap<String, Requests>>,
+    request_options: RequestOptions,
+    battery_histories: Mutex<BTreeMap<String, BatteryHistory>>,
+    event_bus: EventBus,
+    protocol_analyzer: Mutex<ProtocolAnalyzer>,
+    firmware_analyzer: FirmwareVersionAnalyzer,
+}
+
+impl Engine {
+    /// Create an engine without a backend
+    ///
+    /// Messages can still be handled, but operations that talk to a device
+    /// fail with `BluetoothError`.
+    pub fn new() -> Self {
+        Self {
+            backend: None,
+            devices: RwLock::new(HashMap::new()),
+            notification_tasks: Mutex::new(HashMap::new()),
+            decoders: Mutex::new(HashMap::new()),
+            requests: Mutex::new(HashMap::new()),
+            request_options: RequestOptions::default(),
+            battery_histories: Mutex::new(BTreeMap::new()),
+            event_bus: EventBus::new(),
+            protocol_analyzer: Mutex::new(ProtocolAnalyzer::new()),
+            firmware_analyzer: FirmwareVersionAnalyzer::new(),
+        }
+    }
+
+    /// Create an engine driving devices through `backend`
+    pub fn with_backend(backend: impl AsyncBluetoothBackend + 'static) -> Self {
+        let mut engine = Self::new();
+        engine.backend = Some(Arc::new(backend));
+        engine
+    }
+
+    /// Set how long commands wait for an acknowledgement and how often they
+    /// are resent
+    pub fn with_request_options(mut self, options: RequestOptions) -> Self {
+        self.request_options = options;
+        self
+    }
+
+    /// Get protocol analyzer holding drift statistics for received messages
+    pub fn protocol_analyzer(&self) -> MutexGuard<'_, ProtocolAnalyzer> {
+        self.protocol_analyzer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Get reference to event bus
+    pub fn event_bus(&self) -> &EventBus {
+        &self.event_bus
+    }
+
+    /// Register a device, replacing any device with the same id
+    ///
+    /// The device's state changes are published on the engine's event bus.
+    pub fn register_device(&self, mut device: Device) {
+        device.attach_event_bus(self.event_bus.clone());
+        self.write_devices().insert(device.id().to_string(), device);
+    }
+
+    /// Forget a device, returning it if it was registered
+    pub fn remove_device(&self, id: &str) -> Option<Device> {
+        self.decoders().remove(id);
+        if let Some(requests) = self.requests().remove(id) {
+            requests.cancel_all();
+        }
+        self.battery_histories().remove(id);
+        self.write_devices().remove(id)
+    }
+
+    /// Get a copy of a device by ID
+    pub fn get_device(&self, id: &str) -> Option<Device> {
+        self.read_devices().get(id).cloned()
+    }
+
+    /// Run `f` on a registered device, returning its result
+    pub fn update_device<R>(&self, id: &str, f: impl FnOnce(&mut Device) -> R) -> Option<R> {
+        self.write_devices().get_mut(id).map(f)
+    }
+
+    /// List copies of all devices
+    pub fn devices(&self) -> impl Iterator<Item = Device> {
+        let devices: Vec<Device> = self.read_devices().values().cloned().collect();
+        devices.into_iter()
+    }
+
+    /// List copies of the devices currently connected
+    pub fn connected_devices(&self) -> Vec<Device> {
+        self.devices()
+            .filter(|d| *d.state() == DeviceState::Connected)
+            .collect()
+    }
+
+    /// Update device state, failing with `InvalidState` for illegal transitions
+    pub fn set_device_state(&self, id: &str, state: DeviceState) -> Result<StateTransition> {
+        self.update_device(id, |device| device.set_state(state))
+            .ok_or(Error::DeviceNotConnected)?
+    }
+
+    /// Register every device saved in `store`, returning how many were restored
+    pub fn restore_devices(&self, store: &DeviceStore) -> usize {
+        let devices = store.restore_devices();
+        let count = devices.len();
+        for device in devices {
+            self.register_device(device);
+        }
+        count
+    }
+
+    /// Save every registered device to `store` and write it to disk
+    pub fn persist_devices(&self, store: &mut DeviceStore) -> Result<()> {
+        for device in self.read_devices().values() {
+            store.save_device(device);
+        }
+        store.flush()
+    }
+
+    /// Copy of the battery reports received from a device
+    pub fn battery_history(&self, id: &str) -> Option<BatteryHistory> {
+        self.battery_histories().get(id).cloned()
+    }
+
+    /// Load battery histories saved with [`Engine::save_battery_history`]
+    ///
+    /// Replaces the histories of the devices found in the file, returning
+    /// how many there were. A missing file loads nothing.
+    pub fn load_battery_history(&self, path: &Path) -> Result<usize> {
+        let loaded = battery::load_histories(path)?;
+        let count = loaded.len();
+        self.battery_histories().extend(loaded);
+        Ok(count)
+    }
+
+    /// Write every device's battery history to `path`
+    pub fn save_battery_history(&self, path: &Path) -> Result<()> {
+        battery::save_histories(path, &self.battery_histories())
+    }
+
+    /// Connect a registered device and start handling its AAP notifications
+    ///
+    /// The device moves through `Connecting` to `Connected`, or to `Error` if
+    /// the backend fails. Notifications are handled by a task holding a weak
+    /// reference to the engine, so it stops when the engine is dropped. If
+    /// the stream ends while the device is connected, the link is taken as
+    /// lost and the device moves to `Disconnected`.
+    pub async fn connect(self: &Arc<Self>, device_id: &str) -> Result<()> {
+        let backend = self.backend()?;
+        self.set_device_state(device_id, DeviceState::Connecting)?;
+
+        let result = async {
+            backend.connect(device_id).await?;
+            backend
+                .notifications(device_id, AAP_SERVICE_UUID, AAP_CHARACTERISTIC_UUID)
+                .await
+        }
+        .await;
+
+        match result {
+            Ok(notifications) => {
+                let mut requests = RequestManager::new(backend, device_id);
+                requests.set_default_options(self.request_options);
+                self.requests()
+                    .insert(device_id.to_string(), Arc::new(requests));
+                self.set_device_state(device_id, DeviceState::Connected)?;
+                self.event_bus
+                    .emit(Event::new(EventType::DeviceConnected, device_id));
+                self.spawn_notification_task(device_id, notifications);
+                Ok(())
+            }
+            Err(err) => {
+                log::debug!("connecting {} failed: {}", device_id, err);
+                self.set_device_state(device_id, DeviceState::Error)?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Disconnect a connected device
+    pub async fn disconnect(&self, device_id: &str) -> Result<()> {
+        let backend = self.backend()?;
+        self.set_device_state(device_id, DeviceState::Disconnecting)?;
+
+        let result = backend.disconnect(device_id).await;
+        let task = self.notification_tasks().remove(device_id);
+        if let Some(task) = task {
+            // Wait for the abort so no notification is handled after this
+            task.abort();
+            let _ = task.await;
+        }
+        self.decoders().remove(device_id);
+        if let Some(requests) = self.requests().remove(device_id) {
+            requests.cancel_all();
+        }
+
+        match result {
+            Ok(()) => {
+                self.set_device_state(device_id, DeviceState::Disconnected)?;
+                self.event_bus
+                    .emit(Event::new(EventType::DeviceDisconnected, device_id));
+                Ok(())
+            }
+            Err(err) => {
+                self.set_device_state(device_id, DeviceState::Error)?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Switch a connected device's noise control mode
+    ///
+    /// Completes once the device acknowledges the new mode.
+    pub async fn set_anc(&self, device_id: &str, mode: AncMode) -> Result<()> {
+        self.request(device_id, &Payload::AncControl(mode)).await?;
+        Ok(())
+    }
+
+    /// Rename a connected device
+    ///
+    /// Devices do not acknowledge a rename, so this completes once it is sent.
+    pub async fn rename(&self, device_id: &str, name: &str) -> Result<()> {
+        let message = self.with_connected(device_id, |device| {
+            device.command(&Payload::DeviceRename(name.to_string()))
+        })?;
+        self.request_manager(device_id)?.send(&message).await?;
+        self.update_device(device_id, |device| device.set_name(name.to_string()));
+        Ok(())
+    }
+
+    /// Ask a connected device for its battery levels and wait for the report
+    ///
+    /// The report arrives through [`Engine::handle_notification`]. Fails with
+    /// `Timeout` if none arrives within [`BATTERY_TIMEOUT`].
+    pub async fn battery(&self, device_id: &str) -> Result<BatteryInfo> {
+        let request = self.with_connected(device_id, |device| {
+            device.require_capability(DeviceCapability::BatteryMonitoring)?;
+            Ok(Message::new(MessageType::BatteryStatus, Vec::new()))
+        })?;
+        let options = RequestOptions {
+            timeout: BATTERY_TIMEOUT,
+            retries: 0,
+            response_type: None,
+        };
+        let report = self
+            .request_manager(device_id)?
+            .request_with(request, options)
+            .await?;
+        match report.decode_payload()? {
+            Payload::BatteryStatus(info) => Ok(info),
+            other => Err(Error::ParseError(format!(
+                "expected a battery report, got {:?}",
+                other
+            ))),
+        }
+    }
+
+    /// Last multipoint session reported by a device
+    pub fn multipoint(&self, device_id: &str) -> Result<Option<MultipointInfo>> {
+        self.read_devices()
+            .get(device_id)
+            .map(|device| device.state_info().multipoint.clone())
+            .ok_or(Error::DeviceNotConnected)
+    }
+
+    /// Hosts a device is connected to, as of its last session report
+    pub fn hosts(&self, device_id: &str) -> Result<Vec<String>> {
+        Ok(self
+            .multipoint(device_id)?
+            .map(|session| session.connected_devices)
+            .unwrap_or_default())
+    }
+
+    /// Host currently playing audio through a device, if known
+    pub fn active_host(&self, device_id: &str) -> Result<Option<String>> {
+        Ok(self
+            .multipoint(device_id)?
+            .and_then(|session| session.active_device))
+    }
+
+    /// Ask a connected device to move its audio to `host`, usually this one
+    ///
+    /// The outcome arrives as a session report and `Multipoint` events.
+    pub async fn request_handoff(&self, device_id: &str, host: &str) -> Result<()> {
+        self.request(device_id, &multipoint::handoff_command(host))
+            .await?;
+        Ok(())
+    }
+
+    /// Allow or block a connected device switching hosts on its own
+    pub async fn set_auto_switch(&self, device_id: &str, enabled: bool) -> Result<()> {
+        self.request(device_id, &multipoint::auto_switch_command(enabled))
+            .await?;
+        Ok(())
+    }
+
+    /// Feed a chunk of notification data received from `device_id`
+    ///
+    /// Chunks may split or join frames; each complete frame is handled as by
+    /// [`Engine::handle_message`] and its result returned in order.
+    pub fn handle_notification(&self, device_id: &str, chunk: &[u8]) -> Vec<Result<Payload>> {
+        let messages = self
+            .decoders()
+            .entry(device_id.to_string())
+            .or_default()
+            .push(chunk);
+        messages
+            .into_iter()
+            .map(|message| message.and_then(|m| self.handle_message(device_id, &m)))
+            .collect()
+    }
+
+    /// Handle a message received from a device
+    ///
+    /// Every opcode is recorded for drift statistics, and the raw message
+    /// completes the oldest command waiting for a reply of its type, if any,
+    /// so a reply that fails to decode still reaches its waiter. Decoded
+    /// payloads are published on the event bus as typed events; messages with
+    /// unknown opcodes are forwarded with their raw payload rather than failing.
+    /// Registered devices update their state snapshot from the payload, and
+    /// a firmware version report refines their capabilities; a multipoint
+    /// session report publishes what changed since the last one. Battery reports
+    /// are added to the device's battery history, raising `LowBattery`
+    /// events when a threshold is crossed.
+    pub fn handle_message(&self, device_id: &str, message: &Message) -> Result<Payload> {
+        self.protocol_analyzer()
+            .record_message_type(message.msg_type.opcode());
+
+        if let MessageType::Unknown(opcode) = message.msg_type {
+            log::debug!("unknown opcode 0x{:02X} from {}", opcode, device_id);
+        }
+        let requests = self.requests().get(device_id).cloned();
+        if let Some(requests) = requests {
+            requests.handle_message(message.clone());
+        }
+
+        let payload = message.decode_payload()?;
+        let previous_session = self.update_device(device_id, |device| {
+            let previous = device.state_info().multipoint.clone();
+            device.apply_payload(&payload);
+            if let Payload::FirmwareInfo(version) = &payload {
+                device.refine_capabilities(version, &self.firmware_analyzer);
+            }
+            previous
+        });
+        if let Some(event_type) = EventType::from_payload(&payload) {
+            self.event_bus.emit(Event::new(event_type, device_id));
+        }
+        if let Payload::MultipointControl(session) = &payload {
+            let previous = previous_session.flatten();
+            for change in multipoint::session_changes(previous.as_ref(), session) {
+                self.event_bus
+                    .emit(Event::new(EventType::Multipoint(change), device_id));
+            }
+        }
+        if let Payload::BatteryStatus(info) = &payload {
+            let model = self.read_devices().get(device_id).map(Device::model);
+            let alerts = self
+                .battery_histories()
+                .entry(device_id.to_string())
+                .or_insert_with(|| {
+                    model.map_or_else(BatteryHistory::new, BatteryHistory::for_model)
+                })
+                .record(info.clone(), chrono::Utc::now().timestamp_millis() as u64);
+            for alert in alerts {
+                self.event_bus
+                    .emit(Event::new(EventType::LowBattery(alert), device_id));
+            }
+        }
+        Ok(payload)
+    }
+
+    /// Scan for devices, applying their advertisements as they arrive
+    ///
+    /// Each advertised device passes through [`Engine::handle_advertisement`]
+    /// before the returned stream yields it. The stream holds a weak
+    /// reference to the engine and runs until [`Engine::stop_scan`].
+    pub async fn scan(self: &Arc<Self>) -> Result<ScanStream> {
+        let devices = self.backend()?.scan().await?;
+        let engine = Arc::downgrade(self);
+        Ok(Box::pin(devices.inspect(move |seen| {
+            if let Some(engine) = engine.upgrade() {
+                engine.handle_advertisement(seen);
+            }
+        })))
+    }
+
+    /// Stop a scan started with [`Engine::scan`]
+    pub async fn stop_scan(&self) -> Result<()> {
+        self.backend()?.stop_scan().await
+    }
+
+    /// Apply a device's advertisement, returning its proximity pairing message
+    ///
+    /// A supported model seen for the first time is registered under its
+    /// address and announced with `DeviceDiscovered`. Advertised battery
+    /// levels update the snapshot of registered devices that are not
+    /// connected, publishing `BatteryUpdated` when they change. Being coarse,
+    /// they are not added to the battery history; connected devices report
+    /// exact levels instead.
+    pub fn handle_advertisement(&self, seen: &BluetoothDevice) -> Option<ProximityPairing> {
+        let pairing = seen.proximity_pairing()?;
+        let id = seen.address.as_str();
+        if let Some(model) = pairing.model {
+            if self.get_device(id).is_none() {
+                self.register_device(Device::new(id.to_string(), seen.name.clone(), model));
+                self.event_bus
+                    .emit(Event::new(EventType::DeviceDiscovered, id));
+            }
+        }
+
+        let update = self
+            .update_device(id, |device| {
+                if *device.state() == DeviceState::Connected {
+                    return None;
+                }
+                let known = device.state_info().battery.clone();
+                let mut info = pairing.battery_info()?;
+                // Components out of range keep their last known level
+                if let Some(known) = &known {
+                    if pairing.left.is_none() {
+                        info.left_bud = known.left_bud;
+                    }
+                    if pairing.right.is_none() {
+                        info.right_bud = known.right_bud;
+                    }
+                    if pairing.case.is_none() {
+                        info.case = known.case;
+                    }
+                }
+                if known.as_ref() == Some(&info) {
+                    return None;
+                }
+                device.apply_payload(&Payload::BatteryStatus(info.clone()));
+                Some(info)
+            })
+            .flatten();
+        if let Some(info) = update {
+            self.event_bus
+                .emit(Event::new(EventType::BatteryUpdated(info), id));
+        }
+        Some(pairing)
+    }
+
+    /// Encode a command for a registered device, gated on its capabilities
+    pub fn command(&self, device_id: &str, payload: &Payload) -> Result<Message> {
+        self.read_devices()
+            .get(device_id)
+            .ok_or(Error::DeviceNotConnected)?
+            .command(payload)
+    }
+
+    /// Create a reconnect supervisor that reconnects through [`Engine::connect`]
+    ///
+    /// A reconnected device is back in `Connected` with its notifications
+    /// handled again. The supervisor starts from the device's last known ear
+    /// detection state, and holds a weak reference to the engine;
+    /// attempts fail with `Cancelled` once the engine is dropped.
+    pub fn reconnect_supervisor(
+        self: &Arc<Self>,
+        device_id: &str,
+        policy: ReconnectPolicy,
+    ) -> Result<ReconnectSupervisor> {
+        self.backend()?;
+        let engine = Arc::downgrade(self);
+        let id = device_id.to_string();
+        let supervisor =
+            ReconnectSupervisor::with_connector(device_id, self.event_bus.clone(), move || {
+                let engine = engine.clone();
+                let id = id.clone();
+                Box::pin(async move {
+                    let engine = engine.upgrade().ok_or(Error::Cancelled)?;
+                    engine.connect(&id).await
+                })
+            });
+        let ear_state = self
+            .read_devices()
+            .get(device_id)
+            .and_then(|device| device.state_info().ear_detection);
+        let supervisor = match ear_state {
+            Some(state) => supervisor.with_ear_state(state),
+            None => supervisor,
+        };
+        Ok(supervisor.with_policy(policy))
+    }
+
+    fn backend(&self) -> Result<Arc<dyn AsyncBluetoothBackend>> {
+        self.backend
+            .clone()
+            .ok_or_else(|| Error::BluetoothError("no backend configured".to_string()))
+    }
+
+    /// Feed `notifications` to the engine until the stream or engine ends
+    fn spawn_notification_task(
+        self: &Arc<Self>,
+        device_id: &str,
+        mut notifications: NotificationStream,
+    ) {
+        let engine = Arc::downgrade(self);
+        let id = device_id.to_string();
+        let task = tokio::spawn(async move {
+            while let Some(chunk) = notifications.next().await {
+                let Some(engine) = engine.upgrade() else {
+                    break;
+                };
+                for result in engine.handle_notification(&id, &chunk) {
+                    if let Err(err) = result {
+                        log::debug!("notification from {} rejected: {}", id, err);
+                    }
+                }
+            }
+            if let Some(engine) = engine.upgrade() {
+                engine.link_lost(&id);
+            }
+        });
+        if let Some(previous) = self
+            .notification_tasks()
+            .insert(device_id.to_string(), task)
+        {
+            previous.abort();
+        }
+    }
+
+    /// Mark a connected device whose notification stream ended as disconnected
+    ///
+    /// Does nothing unless the device is `Connected`, so a stream ending
+    /// during [`Engine::disconnect`] is left to it.
+    fn link_lost(&self, device_id: &str) {
+        let lost = self.update_device(device_id, |device| {
+            *device.state() == DeviceState::Connected
+                && device.set_state(DeviceState::Disconnected).is_ok()
+        });
+        if lost != Some(true) {
+            return;
+        }
+        log::debug!("notification stream from {} ended", device_id);
+        self.notification_tasks().remove(device_id);
+        self.decoders().remove(device_id);
+        if let Some(requests) = self.requests().remove(device_id) {
+            requests.cancel_all();
+        }
+        self.event_bus
+            .emit(Event::new(EventType::DeviceDisconnected, device_id));
+    }
+
+    /// Run `f` on a device that must be registered and connected
+    fn with_connected<R>(
+        &self,
+        device_id: &str,
+        f: impl FnOnce(&Device) -> Result<R>,
+    ) -> Result<R> {
+        let devices = self.read_devices();
+        match devices.get(device_id) {
+            Some(device) if *device.state() == DeviceState::Connected => f(device),
+            _ => Err(Error::DeviceNotConnected),
+        }
+    }
+
+    /// Send a command to a connected device and wait for its reply
+    async fn request(&self, device_id: &str, payload: &Payload) -> Result<Message> {
+        let message = self.with_connected(device_id, |device| device.command(payload))?;
+        self.request_manager(device_id)?.request(message).await
+    }
+
+    fn request_manager(&self, device_id: &str) -> Result<Requests> {
+        self.requests()
+            .get(device_id)
+            .cloned()
+            .ok_or(Error::DeviceNotConnected)
+    }
+
+    fn read_devices(&self) -> RwLockReadGuard<'_, HashMap<String, Device>> {
+        self.devices.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_devices(&self) -> RwLockWriteGuard<'_, HashMap<String, Device>> {
+        self.devices.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn notification_tasks(&self) -> MutexGuard<'_, HashMap<String, JoinHandle<()>>> {
+        self.notification_tasks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn decoders(&self) -> MutexGuard<'_, HashMap<String, FrameDecoder>> {
+        self.decoders.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn requests(&self) -> MutexGuard<'_, HashMap<String, Requests>> {
+        self.requests.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn battery_histories(&self) -> MutexGuard<'_, BTreeMap<String, BatteryHistory>> {
+        self.battery_histories
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        for (_, task) in self.notification_tasks().drain() {
+            task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advertisement::{self, APPLE_COMPANY_ID};
+    use crate::device::DeviceModel;
+    use crate::events::{EventFilter, EventKind};
+    use crate::models::{EarDetectionState, SpatialAudioConfig};
+    use crate::state::StateChange;
+
+    use crate::bluetooth::{channel_stream, BackendFuture, ScanStream};
+    use tokio::sync::mpsc::{self, UnboundedSender};
+
+    type Writes = Arc<Mutex<Vec<Vec<u8>>>>;
+    type Notifier = Arc<Mutex<Option<UnboundedSender<Vec<u8>>>>>;
+
+    /// Records writes, can be told to refuse connections and streams
+    /// whatever is sent on `notifier` once notifications are enabled; with
+    /// `echo` every write is sent back as its acknowledgement
+    #[derive(Default)]
+    struct MockBackend {
+        writes: Writes,
+        notifier: Notifier,
+        refuse_connect: bool,
+        echo: bool,
+    }
+
+    impl AsyncBluetoothBackend for MockBackend {
+        fn scan(&self) -> BackendFuture<'_, ScanStream> {
+            Box::pin(async { Ok(Box::pin(futures_util::stream::empty()) as ScanStream) })
+        }
+
+        fn stop_scan(&self) -> BackendFuture<'_, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn connect<'a>(&'a self, _address: &'a str) -> BackendFuture<'a, ()> {
+            Box::pin(async move {
+                if self.refuse_connect {
+                    return Err(Error::BluetoothError("refused".to_string()));
+                }
+                Ok(())
+            })
+        }
+
+        fn disconnect<'a>(&'a self, _address: &'a str) -> BackendFuture<'a, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn write_characteristic<'a>(
+            &'a self,
+            _address: &'a str,
+            _service_uuid: u128,
+            _char_uuid: u128,
+            data: &'a [u8],
+        ) -> BackendFuture<'a, ()> {
+            self.writes.lock().unwrap().push(data.to_vec());
+            if self.echo {
+                if let Some(notifier) = &*self.notifier.lock().unwrap() {
+                    let _ = notifier.send(data.to_vec());
+                }
+            }
+            Box::pin(async { Ok(()) })
+        }
+
+        fn read_characteristic<'a>(
+            &'a self,
+            _address: &'a str,
+            _service_uuid: u128,
+            _char_uuid: u128,
+        ) -> BackendFuture<'a, Vec<u8>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn notifications<'a>(
+            &'a self,
+            _address: &'a str,
+            _service_uuid: u128,
+            _char_uuid: u128,
+        ) -> BackendFuture<'a, NotificationStream> {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            *self.notifier.lock().unwrap() = Some(sender);
+            Box::pin(async move { Ok(channel_stream(receiver)) })
+        }
+    }
+
+    fn engine_with(model: DeviceModel) -> (Arc<Engine>, Writes) {
+        let backend = MockBackend::default();
+        let writes = Arc::clone(&backend.writes);
+        let engine = Arc::new(Engine::with_backend(backend));
+        engine.register_device(Device::new("dev".to_string(), "AirPods".to_string(), model));
+        (engine, writes)
+    }
+
+    #[test]
+    fn test_engine_is_shareable() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Engine>();
+    }
+
+    #[test]
+    fn test_engine_creation() {
+        let engine = Engine::new();
+        assert_eq!(engine.devices().count(), 0);
+    }
+
+    #[test]
+    fn test_registry_operations() {
+        let (engine, _) = engine_with(DeviceModel::AirPods3);
+        assert!(engine.connected_devices().is_empty());
+        assert_eq!(
+            engine.set_device_state("missing", DeviceState::Connecting),
+            Err(Error::DeviceNotConnected)
+        );
+
+        engine
+            .set_device_state("dev", DeviceState::Connecting)
+            .unwrap();
+        engine
+            .set_device_state("dev", DeviceState::Connected)
+            .unwrap();
+        assert_eq!(engine.connected_devices().len(), 1);
+
+        assert!(engine.remove_device("dev").is_some());
+        assert!(engine.get_device("dev").is_none());
+    }
+
+    #[test]
+    fn test_engine_publishes_state_changes() {
+        let (engine, _) = engine_with(DeviceModel::AirPodsProGen2);
+        let mut events = engine.event_bus().subscribe();
+
+        engine
+            .set_device_state("dev", DeviceState::Connecting)
+            .unwrap();
+        assert_eq!(
+            engine.set_device_state("dev", DeviceState::Disconnecting),
+            Err(Error::InvalidState)
+        );
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(
+            event.event_type,
+            EventType::StateChanged {
+                from: DeviceState::Disconnected,
+                to: DeviceState::Connecting,
+            }
+        );
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connect_and_disconnect() {
+        let (engine, _) = engine_with(DeviceModel::AirPodsProGen2);
+        let mut events = engine
+            .event_bus()
+            .subscribe_filtered(EventFilter::new().kind(EventKind::DeviceConnected));
+
+        engine.connect("dev").await.unwrap();
+        assert_eq!(
+            *engine.get_device("dev").unwrap().state(),
+            DeviceState::Connected
+        );
+        assert_eq!(events.try_recv().unwrap().device_id, "dev");
+
+        engine.disconnect("dev").await.unwrap();
+        assert_eq!(
+            *engine.get_device("dev").unwrap().state(),
+            DeviceState::Disconnected
+        );
+        assert_eq!(
+            engine.connect("missing").await,
+            Err(Error::DeviceNotConnected)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_connect_enters_error() {
+        let engine = Arc::new(Engine::with_backend(MockBackend {
+            refuse_connect: true,
+            ..MockBackend::default()
+        }));
+        engine.register_device(Device::new(
+            "dev".to_string(),
+            "AirPods".to_string(),
+            DeviceModel::AirPods3,
+        ));
+
+        assert!(matches!(
+            engine.connect("dev").await,
+            Err(Error::BluetoothError(_))
+        ));
+        assert_eq!(
+            *engine.get_device("dev").unwrap().state(),
+            DeviceState::Error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_operations_without_backend() {
+        let engine = Arc::new(Engine::new());
+        engine.register_device(Device::new(
+            "dev".to_string(),
+            "AirPods".to_string(),
+            DeviceModel::AirPods3,
+        ));
+        assert!(matches!(
+            engine.connect("dev").await,
+            Err(Error::BluetoothError(_))
+        ));
+        assert!(engine
+            .reconnect_supervisor("dev", ReconnectPolicy::default())
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_supervisor_starts_from_known_ear_state() {
+        let engine = Arc::new(Engine::with_backend(MockBackend::default()));
+        engine.register_device(Device::new(
+            "dev".to_string(),
+            "AirPods".to_string(),
+            DeviceModel::AirPodsProGen2,
+        ));
+        engine
+            .handle_message("dev", &Message::new(MessageType::EarDetection, vec![3]))
+            .unwrap();
+
+        let supervisor = engine
+            .reconnect_supervisor("dev", ReconnectPolicy::default())
+            .unwrap();
+        assert_eq!(supervisor.ear_state(), EarDetectionState::BothEarsIn);
+    }
+
+    #[tokio::test]
+    async fn test_set_anc_and_rename() {
+        let backend = MockBackend {
+            echo: true,
+            ..MockBackend::default()
+        };
+        let writes = Arc::clone(&backend.writes);
+        let engine = Arc::new(Engine::with_backend(backend));
+        engine.register_device(Device::new(
+            "dev".to_string(),
+            "AirPods".to_string(),
+            DeviceModel::AirPodsProGen2,
+        ));
+        assert_eq!(
+            engine.set_anc("dev", AncMode::Active).await,
+            Err(Error::DeviceNotConnected)
+        );
+
+        engine.connect("dev").await.unwrap();
+        engine.set_anc("dev", AncMode::Transparency).await.unwrap();
+        engine.rename("dev", "Kitchen").await.unwrap();
+
+        let device = engine.get_device("dev").unwrap();
+        assert_eq!(device.snapshot().anc_mode, Some(AncMode::Transparency));
+        assert_eq!(device.name(), "Kitchen");
+
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes.len(), 2);
+        let sent = Message::parse(&writes[0]).unwrap();
+        assert_eq!(
+            sent.decode_payload().unwrap(),
+            Payload::AncControl(AncMode::Transparency)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_set_anc_waits_for_ack() {
+        let backend = MockBackend::default();
+        let writes = Arc::clone(&backend.writes);
+        let engine = Arc::new(
+            Engine::with_backend(backend).with_request_options(RequestOptions {
+                timeout: Duration::from_millis(100),
+                retries: 1,
+                response_type: None,
+            }),
+        );
+        engine.register_device(Device::new(
+            "dev".to_string(),
+            "AirPods".to_string(),
+            DeviceModel::AirPodsProGen2,
+        ));
+        engine.connect("dev").await.unwrap();
+
+        assert_eq!(
+            engine.set_anc("dev", AncMode::Active).await,
+            Err(Error::Timeout)
+        );
+        assert_eq!(writes.lock().unwrap().len(), 2);
+        assert_eq!(engine.get_device("dev").unwrap().snapshot().anc_mode, None);
+    }
+
+    #[tokio::test]
+    async fn test_set_anc_requires_capability() {
+        let (engine, writes) = engine_with(DeviceModel::AirPods2);
+        engine.connect("dev").await.unwrap();
+        assert_eq!(
+            engine.set_anc("dev", AncMode::Active).await,
+            Err(Error::UnsupportedDevice)
+        );
+        assert!(writes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_battery_waits_for_report() {
+        let (engine, writes) = engine_with(DeviceModel::AirPodsProGen2);
+        engine.connect("dev").await.unwrap();
+
+        let request = tokio::spawn({
+            let engine = Arc::clone(&engine);
+            async move { engine.battery("dev").await }
+        });
+        while writes.lock().unwrap().is_empty() {
+            tokio::task::yield_now().await;
+        }
+
+        let info = BatteryInfo {
+            left_bud: 80,
+            right_bud: 75,
+            case: 40,
+            is_charging: true,
+        };
+        let report = Payload::BatteryStatus(info.clone())
+            .to_message()
+            .unwrap()
+            .serialize()
+            .unwrap();
+        let (head, tail) = report.split_at(3);
+        assert!(engine.handle_notification("dev", head).is_empty());
+        assert_eq!(engine.handle_notification("dev", tail).len(), 1);
+
+        assert_eq!(request.await.unwrap(), Ok(info));
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_reply_completes_request() {
+        let (engine, writes) = engine_with(DeviceModel::AirPodsProGen2);
+        engine.connect("dev").await.unwrap();
+
+        let request = tokio::spawn({
+            let engine = Arc::clone(&engine);
+            async move { engine.battery("dev").await }
+        });
+        while writes.lock().unwrap().is_empty() {
+            tokio::task::yield_now().await;
+        }
+
+        let report = Message::new(MessageType::BatteryStatus, vec![80]);
+        let error = engine.handle_message("dev", &report).unwrap_err();
+
+        assert_eq!(request.await.unwrap(), Err(error));
+    }
+
+    #[tokio::test]
+    async fn test_handles_notification_stream() {
+        let backend = MockBackend::default();
+        let notifier = Arc::clone(&backend.notifier);
+        let engine = Arc::new(Engine::with_backend(backend));
+        engine.register_device(Device::new(
+            "dev".to_string(),
+            "AirPods".to_string(),
+            DeviceModel::AirPodsMax,
+        ));
+        let mut events = engine
+            .event_bus()
+            .subscribe_filtered(EventFilter::new().kind(EventKind::AncChanged));
+
+        engine.connect("dev").await.unwrap();
+        let report = Payload::AncControl(AncMode::Transparency)
+            .to_message()
+            .unwrap()
+            .serialize()
+            .unwrap();
+        let sender = notifier.lock().unwrap().clone().unwrap();
+        sender.send(report).unwrap();
+
+        let event = events.recv().await.unwrap();
+        assert_eq!(
+            event.event_type,
+            EventType::AncChanged(AncMode::Transparency)
+        );
+
+        engine.disconnect("dev").await.unwrap();
+        assert!(sender.is_closed());
+    }
+
+    #[tokio::test]
+    async fn test_stream_end_disconnects() {
+        let backend = MockBackend::default();
+        let notifier = Arc::clone(&backend.notifier);
+        let engine = Arc::new(Engine::with_backend(backend));
+        engine.register_device(Device::new(
+            "dev".to_string(),
+            "AirPods".to_string(),
+            DeviceModel::AirPodsProGen2,
+        ));
+        let mut events = engine.event_bus().subscribe_filtered(
+            EventFilter::new()
+                .kind(EventKind::StateChanged)
+                .kind(EventKind::DeviceDisconnected),
+        );
+
+        engine.connect("dev").await.unwrap();
+        let report = Payload::AncControl(AncMode::Adaptive)
+            .to_message()
+            .unwrap()
+            .serialize()
+            .unwrap();
+        // Leave half a frame behind, then drop the link
+        let sender = notifier.lock().unwrap().take().unwrap();
+        sender.send(report[..3].to_vec()).unwrap();
+        drop(sender);
+
+        let mut seen = Vec::new();
+        while seen.last() != Some(&EventType::DeviceDisconnected) {
+            seen.push(events.recv().await.unwrap().event_type);
+        }
+        assert!(seen.contains(&EventType::StateChanged {
+            from: DeviceState::Connected,
+            to: DeviceState::Disconnected,
+        }));
+        assert_eq!(
+            *engine.get_device("dev").unwrap().state(),
+            DeviceState::Disconnected
+        );
+        assert!(engine.decoders().get("dev").is_none());
+        assert!(engine.notification_tasks().get("dev").is_none());
+
+        engine.connect("dev").await.unwrap();
+        assert_eq!(
+            *engine.get_device("dev").unwrap().state(),
+            DeviceState::Connected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_advertisement_updates_battery() {
+        let (engine, _) = engine_with(DeviceModel::AirPodsProGen2);
+        let mut events = engine.event_bus().subscribe();
+        let pairing = ProximityPairing {
+            model_id: advertisement::model_id(DeviceModel::AirPodsMax),
+            model: Some(DeviceModel::AirPodsMax),
+            left: Some(60),
+            right: Some(70),
+            case: Some(50),
+            left_charging: false,
+            right_charging: false,
+            case_charging: false,
+            lid_open: true,
+        };
+        let advertised = |address: &str, pairing: &ProximityPairing| BluetoothDevice {
+            address: address.to_string(),
+            name: "Headphones".to_string(),
+            rssi: -50,
+            is_connected: false,
+            manufacturer_data: HashMap::from([(APPLE_COMPANY_ID, pairing.encode())]),
+        };
+
+        // A new device is registered with its advertised model
+        assert_eq!(
+            engine.handle_advertisement(&advertised("max", &pairing)),
+            Some(pairing.clone())
+        );
+        let max = engine.get_device("max").unwrap();
+        assert_eq!(max.model(), DeviceModel::AirPodsMax);
+        assert_eq!(max.state_info().battery.as_ref().unwrap().left_bud, 60);
+        let kinds: Vec<EventKind> = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| event.kind())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![EventKind::DeviceDiscovered, EventKind::BatteryUpdated]
+        );
+
+        // Repeated advertisements publish nothing new
+        engine.handle_advertisement(&advertised("max", &pairing));
+        assert!(events.try_recv().is_err());
+        assert!(engine.battery_history("max").is_none());
+
+        // A closed case keeps its last known level
+        let closed = ProximityPairing {
+            left: Some(50),
+            case: None,
+            lid_open: false,
+            ..pairing.clone()
+        };
+        engine.handle_advertisement(&advertised("max", &closed));
+        assert_eq!(
+            events.try_recv().unwrap().event_type,
+            EventType::BatteryUpdated(BatteryInfo {
+                left_bud: 50,
+                right_bud: 70,
+                case: 50,
+                is_charging: false,
+            })
+        );
+
+        // Connected devices keep their exact levels
+        engine.connect("dev").await.unwrap();
+        while events.try_recv().is_ok() {}
+        engine.handle_advertisement(&advertised("dev", &pairing));
+        assert!(events.try_recv().is_err());
+        assert!(engine
+            .get_device("dev")
+            .unwrap()
+            .state_info()
+            .battery
+            .is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_battery_times_out() {
+        let (engine, _) = engine_with(DeviceModel::AirPodsProGen2);
+        engine.connect("dev").await.unwrap();
+        assert_eq!(engine.battery("dev").await, Err(Error::Timeout));
+    }
+
+    #[test]
+    fn test_battery_reports_build_history() {
+        let (engine, _) = engine_with(DeviceModel::AirPodsProGen2);
+        let mut alerts = engine
+            .event_bus()
+            .subscribe_filtered(EventFilter::new().kind(EventKind::LowBattery));
+
+        for level in [30, 18] {
+            let report = Payload::BatteryStatus(BatteryInfo {
+                left_bud: level,
+                right_bud: 50,
+                case: 90,
+                is_charging: false,
+            });
+            engine
+                .handle_message("dev", &report.to_message().unwrap())
+                .unwrap();
+        }
+
+        assert_eq!(engine.battery_history("dev").unwrap().len(), 2);
+        match alerts.try_recv().unwrap().event_type {
+            EventType::LowBattery(alert) => {
+                assert_eq!(alert.component, battery::BatteryComponent::Left);
+                assert_eq!(alert.threshold, 20);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(alerts.try_recv().is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(battery::BATTERY_FILE);
+        engine.save_battery_history(&path).unwrap();
+        let restarted = Engine::new();
+        assert_eq!(restarted.load_battery_history(&path).unwrap(), 1);
+        assert_eq!(
+            restarted.battery_history("dev"),
+            engine.battery_history("dev")
+        );
+    }
+
+    #[test]
+    fn test_firmware_report_refines_capabilities() {
+        let (engine, _) = engine_with(DeviceModel::AirPodsProGen2);
+        let spatial = Payload::SpatialAudio(SpatialAudioConfig {
+            enabled: true,
+            head_tracking: true,
+            dynamic_head_tracking: false,
+        });
+        assert!(engine.command("dev", &spatial).is_ok());
+
+        let report = Payload::FirmwareInfo("5D134".to_string())
+            .to_message()
+            .unwrap();
+        engine.handle_message("dev", &report).unwrap();
+
+        assert_eq!(
+            engine.command("dev", &spatial),
+            Err(Error::UnsupportedDevice)
+        );
+        assert!(engine
+            .command("dev", &Payload::AncControl(AncMode::Active))
+            .is_ok());
+        assert_eq!(
+            engine.command("missing", &spatial),
+            Err(Error::DeviceNotConnected)
+        );
+    }
+
+    #[test]
+    fn test_engine_updates_device_snapshot() {
+        let (engine, _) = engine_with(DeviceModel::AirPodsMax);
+        let before = engine.get_device("dev").unwrap().snapshot();
+
+        let message = Payload::AncControl(AncMode::Transparency)
+            .to_message()
+            .unwrap();
+        engine.handle_message("dev", &message).unwrap();
+
+        let after = engine.get_device("dev").unwrap().snapshot();
+        assert_eq!(after.anc_mode, Some(AncMode::Transparency));
+        assert_eq!(
+            before.diff(&after),
+            vec![StateChange::AncMode {
+                from: None,
+                to: Some(AncMode::Transparency),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_engine_forwards_unknown_message() {
+        let engine = Engine::new();
+        let mut events = engine.event_bus().subscribe();
+
+        let message = Message::new(MessageType::Unknown(0x42), vec![1, 2]);
+        let payload = engine.handle_message("dev", &message).unwrap();
+        assert_eq!(
+            payload,
+            Payload::Unknown {
+                opcode: 0x42,
+                data: vec![1, 2]
+            }
+        );
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(
+            event.event_type,
+            EventType::UnknownMessage {
+                opcode: 0x42,
+                data: vec![1, 2]
+            }
+        );
+        assert!(events.try_recv().is_err());
+        assert_eq!(
+            engine.protocol_analyzer().observed_unknown_types(),
+            vec![(0x42, 1)]
+        );
+    }
+
+    #[test]
+    fn test_engine_publishes_typed_events() {
+        let engine = Engine::new();
+        let mut events = engine
+            .event_bus()
+            .subscribe_filtered(EventFilter::new().kind(EventKind::AncChanged));
+
+        let message = Payload::AncControl(AncMode::Active).to_message().unwrap();
+        engine.handle_message("dev", &message).unwrap();
+
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.event_type, EventType::AncChanged(AncMode::Active));
+        assert_eq!(event.device_id, "dev");
+    }
+}
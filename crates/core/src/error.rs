@@ -1,3 +1,4 @@
+use crate::frame::FrameError;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -26,6 +27,9 @@ pub enum Error {
     PermissionDenied,
     #[error("Operation timeout")]
     Timeout,
+    /// The operation was abandoned before it completed
+    #[error("Operation cancelled")]
+    Cancelled,
     #[error("Parse error: {0}")]
     ParseError(String),
     #[error("Configuration error: {0}")]
@@ -34,6 +38,25 @@ pub enum Error {
     IoError(String),
 }
 
+impl From<FrameError> for Error {
+    fn from(err: FrameError) -> Self {
+        match err {
+            FrameError::InvalidLength | FrameError::BufferFull => Error::InvalidLength,
+            FrameError::CrcMismatch => Error::CrcMismatch,
+        }
+    }
+}
+
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "mpris", feature = "bluetooth-linux")
+))]
+impl From<zbus::Error> for Error {
+    fn from(err: zbus::Error) -> Self {
+        Error::IoError(format!("D-Bus: {}", err))
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
         Error::IoError(err.to_string())
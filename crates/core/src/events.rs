@@ -1,51 +1,232 @@
+use crate::battery::LowBattery;
+use crate::models::{AncMode, ConversationAwarenessState, EarDetectionState};
+use crate::multipoint::MultipointEvent;
+use crate::payload::Payload;
+use crate::reconnect::ReconnectStatus;
+use crate::state::{BatteryInfo, DeviceState};
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+pub use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+/// Events buffered per subscriber before the slowest one starts lagging
+pub const DEFAULT_EVENT_CAPACITY: usize = 256;
+
+/// What happened to a device, with the data that came with it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum EventType {
+    /// A supported device was seen for the first time
+    DeviceDiscovered,
+    /// The device connected
+    DeviceConnected,
+    /// The device disconnected or the link dropped
+    DeviceDisconnected,
+    /// The device reported new battery levels
+    BatteryUpdated(BatteryInfo),
+    /// A battery dropped to a low-battery threshold while discharging
+    LowBattery(LowBattery),
+    /// The noise control mode changed
+    AncChanged(AncMode),
+    /// Buds were put in or taken out
+    EarDetection(EarDetectionState),
+    /// Conversation awareness changed
+    ConversationAwareness(ConversationAwarenessState),
+    /// The device reported its firmware version
+    FirmwareVersion(String),
+    /// A host connected, disconnected or took over
+    Multipoint(MultipointEvent),
+    /// The connection state changed
+    StateChanged {
+        /// State before the change
+        from: DeviceState,
+        /// State after the change
+        to: DeviceState,
+    },
+    /// Progress of a supervised reconnect
+    Reconnect(ReconnectStatus),
+    /// A message with an opcode the registry does not know, with its raw payload
+    UnknownMessage {
+        /// The unknown opcode
+        opcode: u8,
+        /// Payload bytes after the header
+        data: Vec<u8>,
+    },
+    /// Something went wrong handling the device
+    Error(String),
+}
+
+/// Event type without its data, used for filtering subscriptions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventKind {
+    /// [`EventType::DeviceDiscovered`]
     DeviceDiscovered,
+    /// [`EventType::DeviceConnected`]
     DeviceConnected,
+    /// [`EventType::DeviceDisconnected`]
     DeviceDisconnected,
+    /// [`EventType::BatteryUpdated`]
     BatteryUpdated,
+    /// [`EventType::LowBattery`]
+    LowBattery,
+    /// [`EventType::AncChanged`]
+    AncChanged,
+    /// [`EventType::EarDetection`]
+    EarDetection,
+    /// [`EventType::ConversationAwareness`]
+    ConversationAwareness,
+    /// [`EventType::FirmwareVersion`]
+    FirmwareVersion,
+    /// [`EventType::Multipoint`]
+    Multipoint,
+    /// [`EventType::StateChanged`]
     StateChanged,
+    /// [`EventType::Reconnect`]
+    Reconnect,
+    /// [`EventType::UnknownMessage`]
+    UnknownMessage,
+    /// [`EventType::Error`]
     Error,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl EventType {
+    /// Kind of this event for filtering
+    pub fn kind(&self) -> EventKind {
+        match self {
+            EventType::DeviceDiscovered => EventKind::DeviceDiscovered,
+            EventType::DeviceConnected => EventKind::DeviceConnected,
+            EventType::DeviceDisconnected => EventKind::DeviceDisconnected,
+            EventType::BatteryUpdated(_) => EventKind::BatteryUpdated,
+            EventType::LowBattery(_) => EventKind::LowBattery,
+            EventType::AncChanged(_) => EventKind::AncChanged,
+            EventType::EarDetection(_) => EventKind::EarDetection,
+            EventType::ConversationAwareness(_) => EventKind::ConversationAwareness,
+            EventType::FirmwareVersion(_) => EventKind::FirmwareVersion,
+            EventType::Multipoint(_) => EventKind::Multipoint,
+            EventType::StateChanged { .. } => EventKind::StateChanged,
+            EventType::Reconnect(_) => EventKind::Reconnect,
+            EventType::UnknownMessage { .. } => EventKind::UnknownMessage,
+            EventType::Error(_) => EventKind::Error,
+        }
+    }
+
+    /// Event announcing a decoded payload, if the payload has one
+    pub fn from_payload(payload: &Payload) -> Option<Self> {
+        match payload {
+            Payload::BatteryStatus(info) => Some(EventType::BatteryUpdated(info.clone())),
+            Payload::AncControl(mode) => Some(EventType::AncChanged(*mode)),
+            Payload::EarDetection(state) => Some(EventType::EarDetection(*state)),
+            Payload::ConversationAwareness(state) => Some(EventType::ConversationAwareness(*state)),
+            Payload::FirmwareInfo(version) => Some(EventType::FirmwareVersion(version.clone())),
+            Payload::Unknown { opcode, data } => Some(EventType::UnknownMessage {
+                opcode: *opcode,
+                data: data.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Event {
     pub event_type: EventType,
     pub device_id: String,
-    pub payload: Vec<u8>,
     pub timestamp: u64,
 }
 
-pub type EventListener = Arc<Mutex<dyn Fn(&Event) + Send + Sync>>;
+impl Event {
+    /// Create an event stamped with the current time in milliseconds
+    pub fn new(event_type: EventType, device_id: &str) -> Self {
+        Self {
+            event_type,
+            device_id: device_id.to_string(),
+            timestamp: chrono::Utc::now().timestamp_millis() as u64,
+        }
+    }
+
+    /// Kind of this event for filtering
+    pub fn kind(&self) -> EventKind {
+        self.event_type.kind()
+    }
+}
+
+/// Selects which events a subscription receives
+///
+/// An empty filter matches everything; each device or kind added narrows it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EventFilter {
+    devices: Vec<String>,
+    kinds: Vec<EventKind>,
+}
+
+impl EventFilter {
+    /// Filter matching every event
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only pass events from this device (may be repeated)
+    pub fn device(mut self, device_id: &str) -> Self {
+        self.devices.push(device_id.to_string());
+        self
+    }
+
+    /// Only pass events of this kind (may be repeated)
+    pub fn kind(mut self, kind: EventKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    /// Whether `event` passes the filter
+    pub fn matches(&self, event: &Event) -> bool {
+        (self.devices.is_empty() || self.devices.contains(&event.device_id))
+            && (self.kinds.is_empty() || self.kinds.contains(&event.kind()))
+    }
+}
 
+/// Broadcasts events to any number of async subscribers
+///
+/// Every subscriber gets its own copy of each event. A subscriber that falls
+/// more than the bus capacity behind loses the oldest events and is told how
+/// many it missed instead of slowing down the emitter.
+#[derive(Debug, Clone)]
 pub struct EventBus {
-    listeners: Vec<EventListener>,
+    sender: broadcast::Sender<Event>,
 }
 
 impl EventBus {
+    /// Create a bus buffering [`DEFAULT_EVENT_CAPACITY`] events per subscriber
     pub fn new() -> Self {
-        Self {
-            listeners: Vec::new(),
-        }
+        Self::with_capacity(DEFAULT_EVENT_CAPACITY)
     }
 
-    pub fn subscribe(&mut self, listener: EventListener) {
-        self.listeners.push(listener);
+    /// Create a bus buffering up to `capacity` events per subscriber
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
     }
 
-    pub fn emit(&self, event: &Event) {
-        for listener in &self.listeners {
-            if let Ok(callback) = listener.lock() {
-                callback(event);
-            }
+    /// Subscribe to every event
+    pub fn subscribe(&self) -> Subscription {
+        self.subscribe_filtered(EventFilter::new())
+    }
+
+    /// Subscribe to events matching `filter`
+    pub fn subscribe_filtered(&self, filter: EventFilter) -> Subscription {
+        Subscription {
+            receiver: self.sender.subscribe(),
+            filter,
+            missed: 0,
         }
     }
 
-    pub fn clear(&mut self) {
-        self.listeners.clear();
+    /// Send an event to all current subscribers, returning how many there are
+    pub fn emit(&self, event: Event) -> usize {
+        self.sender.send(event).unwrap_or(0)
+    }
+
+    /// Number of live subscriptions
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
     }
 }
 
@@ -54,3 +235,157 @@ impl Default for EventBus {
         Self::new()
     }
 }
+
+/// Handle to a stream of events; dropping it unsubscribes
+#[derive(Debug)]
+pub struct Subscription {
+    receiver: broadcast::Receiver<Event>,
+    filter: EventFilter,
+    missed: u64,
+}
+
+impl Subscription {
+    /// Wait for the next matching event
+    ///
+    /// Returns `RecvError::Lagged(n)` once if this subscriber fell behind and
+    /// `n` events were dropped, then continues with the oldest retained event.
+    /// Returns `RecvError::Closed` after the bus is dropped and drained.
+    pub async fn recv(&mut self) -> Result<Event, RecvError> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.filter.matches(&event) => return Ok(event),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(n)) => {
+                    self.missed += n;
+                    return Err(RecvError::Lagged(n));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Take the next matching event without waiting
+    pub fn try_recv(&mut self) -> Result<Event, TryRecvError> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) if self.filter.matches(&event) => return Ok(event),
+                Ok(_) => continue,
+                Err(TryRecvError::Lagged(n)) => {
+                    self.missed += n;
+                    return Err(TryRecvError::Lagged(n));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Total events dropped because this subscriber lagged
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    /// Filter the subscription was created with
+    pub fn filter(&self) -> &EventFilter {
+        &self.filter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn battery(level: u8) -> EventType {
+        EventType::BatteryUpdated(BatteryInfo {
+            left_bud: level,
+            right_bud: level,
+            case: level,
+            is_charging: false,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_receive_typed_events() {
+        let bus = EventBus::new();
+        let mut first = bus.subscribe();
+        let mut second = bus.subscribe();
+
+        assert_eq!(
+            bus.emit(Event::new(EventType::AncChanged(AncMode::Active), "dev")),
+            2
+        );
+
+        for sub in [&mut first, &mut second] {
+            let event = sub.recv().await.unwrap();
+            assert_eq!(event.event_type, EventType::AncChanged(AncMode::Active));
+            assert_eq!(event.device_id, "dev");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filter_by_device_and_kind() {
+        let bus = EventBus::new();
+        let mut sub = bus.subscribe_filtered(
+            EventFilter::new()
+                .device("left")
+                .kind(EventKind::BatteryUpdated),
+        );
+
+        bus.emit(Event::new(battery(10), "right"));
+        bus.emit(Event::new(EventType::DeviceConnected, "left"));
+        bus.emit(Event::new(battery(20), "left"));
+
+        let event = sub.recv().await.unwrap();
+        assert_eq!(event.event_type, battery(20));
+        assert!(matches!(sub.try_recv(), Err(TryRecvError::Empty)));
+    }
+
+    #[test]
+    fn test_drop_unsubscribes() {
+        let bus = EventBus::new();
+        let sub = bus.subscribe();
+        let _other = bus.subscribe();
+        assert_eq!(bus.subscriber_count(), 2);
+
+        drop(sub);
+        assert_eq!(bus.subscriber_count(), 1);
+        assert_eq!(bus.emit(Event::new(EventType::DeviceDiscovered, "dev")), 1);
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_reports_lag() {
+        let bus = EventBus::with_capacity(2);
+        let mut sub = bus.subscribe();
+
+        for level in 0..5 {
+            bus.emit(Event::new(battery(level), "dev"));
+        }
+
+        assert_eq!(sub.recv().await, Err(RecvError::Lagged(3)));
+        assert_eq!(sub.missed(), 3);
+        assert_eq!(sub.recv().await.unwrap().event_type, battery(3));
+        assert_eq!(sub.recv().await.unwrap().event_type, battery(4));
+    }
+
+    #[tokio::test]
+    async fn test_closed_after_bus_dropped() {
+        let bus = EventBus::new();
+        let mut sub = bus.subscribe();
+        bus.emit(Event::new(EventType::DeviceDisconnected, "dev"));
+        drop(bus);
+
+        assert!(sub.recv().await.is_ok());
+        assert_eq!(sub.recv().await, Err(RecvError::Closed));
+    }
+
+    #[test]
+    fn test_event_from_payload() {
+        assert_eq!(
+            EventType::from_payload(&Payload::AncControl(AncMode::Transparency)),
+            Some(EventType::AncChanged(AncMode::Transparency))
+        );
+        assert_eq!(
+            EventType::from_payload(&Payload::AdaptiveTransparency(true)),
+            None
+        );
+    }
+}
@@ -36,11 +36,36 @@ impl FirmwareAnalyzer {
 
     fn initialize_known_versions(&mut self) {
         let versions = vec![
-            ("5E135", "AirPodsProGen2", "2025-11-21", vec!["BatteryMonitoring", "ANC", "SpatialAudio"]),
-            ("5D134", "AirPodsProGen2", "2025-10-15", vec!["BatteryMonitoring", "ANC"]),
-            ("5C133", "AirPodsMax", "2025-09-20", vec!["BatteryMonitoring", "ANC", "AdaptiveTransparency"]),
-            ("5B132", "AirPods4", "2025-08-10", vec!["BatteryMonitoring", "ANC"]),
-            ("5A131", "AirPods3", "2025-07-05", vec!["BatteryMonitoring", "ANC"]),
+            (
+                "5E135",
+                "AirPodsProGen2",
+                "2025-11-21",
+                vec!["BatteryMonitoring", "ANC", "SpatialAudio"],
+            ),
+            (
+                "5D134",
+                "AirPodsProGen2",
+                "2025-10-15",
+                vec!["BatteryMonitoring", "ANC"],
+            ),
+            (
+                "5C133",
+                "AirPodsMax",
+                "2025-09-20",
+                vec!["BatteryMonitoring", "ANC", "AdaptiveTransparency"],
+            ),
+            (
+                "5B132",
+                "AirPods4",
+                "2025-08-10",
+                vec!["BatteryMonitoring", "ANC"],
+            ),
+            (
+                "5A131",
+                "AirPods3",
+                "2025-07-05",
+                vec!["BatteryMonitoring", "ANC"],
+            ),
         ];
 
         for (version, model, date, features) in versions {
@@ -70,7 +95,9 @@ impl FirmwareAnalyzer {
     }
 
     pub fn get_latest_version(&self) -> Option<&FirmwareInfo> {
-        self.known_versions.values().max_by_key(|f| f.release_date.as_str())
+        self.known_versions
+            .values()
+            .max_by_key(|f| f.release_date.as_str())
     }
 
     pub fn get_versions_for_model(&self, model: &str) -> Vec<&FirmwareInfo> {
@@ -80,7 +107,11 @@ impl FirmwareAnalyzer {
             .collect()
     }
 
-    pub fn detect_version_changes(&self, old_versions: Vec<String>, new_versions: Vec<String>) -> (Vec<String>, Vec<String>) {
+    pub fn detect_version_changes(
+        &self,
+        old_versions: Vec<String>,
+        new_versions: Vec<String>,
+    ) -> (Vec<String>, Vec<String>) {
         let old_set: std::collections::HashSet<_> = old_versions.into_iter().collect();
         let new_set: std::collections::HashSet<_> = new_versions.into_iter().collect();
 
@@ -130,7 +161,10 @@ impl FirmwareAnalyzer {
         versions.sort_by(|a, b| b.release_date.cmp(&a.release_date));
 
         for fw in versions {
-            report.push_str(&format!("  {} ({}) - {}\n", fw.version, fw.device_model, fw.release_date));
+            report.push_str(&format!(
+                "  {} ({}) - {}\n",
+                fw.version, fw.device_model, fw.release_date
+            ));
         }
 
         report
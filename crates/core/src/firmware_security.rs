@@ -60,7 +60,9 @@ impl FirmwareSecurityAnalyzer {
     }
 
     fn detect_nda_protection(&self, data: &str) -> bool {
-        self.nda_keywords.iter().any(|keyword| data.contains(keyword))
+        self.nda_keywords
+            .iter()
+            .any(|keyword| data.contains(keyword))
     }
 
     fn detect_proprietary(&self, data: &str) -> bool {
@@ -110,9 +112,15 @@ impl FirmwareSecurityAnalyzer {
         report.push_str(&format!("NDA Protected: {}\n", analysis.is_nda_protected));
         report.push_str(&format!("Proprietary: {}\n", analysis.is_proprietary));
         report.push_str(&format!("Risk Level: {:?}\n", analysis.risk_level));
-        report.push_str(&format!("Safe to Distribute: {}\n\n", self.is_safe_to_distribute(analysis)));
-
-        report.push_str(&format!("Assessment: {}\n", self.get_risk_assessment(analysis)));
+        report.push_str(&format!(
+            "Safe to Distribute: {}\n\n",
+            self.is_safe_to_distribute(analysis)
+        ));
+
+        report.push_str(&format!(
+            "Assessment: {}\n",
+            self.get_risk_assessment(analysis)
+        ));
 
         report
     }
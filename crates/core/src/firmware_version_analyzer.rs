@@ -45,11 +45,46 @@ impl FirmwareVersionAnalyzer {
 
     fn initialize_versions(&mut self) {
         let versions = vec![
-            ("5E135", "AirPodsProGen2", "2025-11-21", vec!["BatteryMonitoring", "ANC", "SpatialAudio"], "5E135", "1.0.0"),
-            ("5D134", "AirPodsProGen2", "2025-10-15", vec!["BatteryMonitoring", "ANC"], "5D134", "1.0.0"),
-            ("5C133", "AirPodsMax", "2025-09-20", vec!["BatteryMonitoring", "ANC", "AdaptiveTransparency"], "5C133", "1.0.0"),
-            ("5B132", "AirPods4", "2025-08-10", vec!["BatteryMonitoring", "ANC"], "5B132", "1.0.0"),
-            ("5A131", "AirPods3", "2025-07-05", vec!["BatteryMonitoring", "ANC"], "5A131", "1.0.0"),
+            (
+                "5E135",
+                "AirPodsProGen2",
+                "2025-11-21",
+                vec!["BatteryMonitoring", "ANC", "SpatialAudio"],
+                "5E135",
+                "1.0.0",
+            ),
+            (
+                "5D134",
+                "AirPodsProGen2",
+                "2025-10-15",
+                vec!["BatteryMonitoring", "ANC"],
+                "5D134",
+                "1.0.0",
+            ),
+            (
+                "5C133",
+                "AirPodsMax",
+                "2025-09-20",
+                vec!["BatteryMonitoring", "ANC", "AdaptiveTransparency"],
+                "5C133",
+                "1.0.0",
+            ),
+            (
+                "5B132",
+                "AirPods4",
+                "2025-08-10",
+                vec!["BatteryMonitoring", "ANC"],
+                "5B132",
+                "1.0.0",
+            ),
+            (
+                "5A131",
+                "AirPods3",
+                "2025-07-05",
+                vec!["BatteryMonitoring", "ANC"],
+                "5A131",
+                "1.0.0",
+            ),
         ];
 
         for (version, model, date, features, build, proto_version) in versions {
@@ -138,7 +173,9 @@ impl FirmwareVersionAnalyzer {
     }
 
     pub fn get_latest_version(&self) -> Option<&FirmwareVersionInfo> {
-        self.known_versions.values().max_by_key(|v| v.release_date.as_str())
+        self.known_versions
+            .values()
+            .max_by_key(|v| v.release_date.as_str())
     }
 
     pub fn extract_features_from_version(&self, version: &str) -> Vec<String> {
@@ -189,7 +226,10 @@ impl FirmwareVersionAnalyzer {
         versions.sort_by(|a, b| b.release_date.cmp(&a.release_date));
 
         for fw in versions {
-            report.push_str(&format!("  {} ({}) - {}\n", fw.version, fw.device_model, fw.release_date));
+            report.push_str(&format!(
+                "  {} ({}) - {}\n",
+                fw.version, fw.device_model, fw.release_date
+            ));
         }
 
         report
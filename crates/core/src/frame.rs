@@ -0,0 +1,226 @@
+//! Zero-copy AAP frame parsing usable without `std`
+//!
+//! `MessageRef` borrows its payload from the input buffer and serializes into
+//! a fixed-capacity `heapless::Vec`, so this module needs neither `std` nor an
+//! allocator. It is the only module built under the `no_std` feature.
+
+use core::fmt;
+
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+
+/// Size of the trailing CRC in bytes
+pub const FRAME_CRC_LEN: usize = 2;
+
+const CRC: crc::Crc<u16> = crc::Crc::<u16>::new(&crc::CRC_16_IBM_SDLC);
+
+/// CRC-16 used to verify AAP payloads
+pub fn crc16(data: &[u8]) -> u16 {
+    CRC.checksum(data)
+}
+
+/// Errors from the allocation-free frame path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    /// Frame is truncated or the payload does not fit the length field
+    InvalidLength,
+    /// Payload does not match the trailing CRC
+    CrcMismatch,
+    /// Output buffer is too small for the encoded frame
+    BufferFull,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::InvalidLength => write!(f, "Invalid message length"),
+            FrameError::CrcMismatch => write!(f, "CRC checksum mismatch"),
+            FrameError::BufferFull => write!(f, "Frame buffer full"),
+        }
+    }
+}
+
+/// AAP frame format version
+///
+/// `V1` is the original format with an 8-bit length field. `V2` widens the
+/// length field to 16 bits for large payloads such as hearing-aid audiograms
+/// and must be negotiated with the peer before use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[repr(u8)]
+pub enum FrameVersion {
+    /// 8-bit payload length
+    #[default]
+    V1 = 1,
+    /// 16-bit big-endian payload length
+    V2 = 2,
+}
+
+impl FrameVersion {
+    /// All versions supported by this implementation, oldest first
+    pub const SUPPORTED: [FrameVersion; 2] = [FrameVersion::V1, FrameVersion::V2];
+
+    /// Size of the frame header (type + length) in bytes
+    pub fn header_len(self) -> usize {
+        match self {
+            FrameVersion::V1 => 2,
+            FrameVersion::V2 => 3,
+        }
+    }
+
+    /// Largest payload the length field can describe
+    pub fn max_payload_len(self) -> usize {
+        match self {
+            FrameVersion::V1 => u8::MAX as usize,
+            FrameVersion::V2 => u16::MAX as usize,
+        }
+    }
+
+    /// Read the payload length from a frame header of at least `header_len` bytes
+    pub(crate) fn payload_len(self, header: &[u8]) -> usize {
+        match self {
+            FrameVersion::V1 => header[1] as usize,
+            FrameVersion::V2 => u16::from_be_bytes([header[1], header[2]]) as usize,
+        }
+    }
+}
+
+/// AAP message borrowing its payload from the frame it was parsed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageRef<'a> {
+    /// Raw message type opcode
+    pub opcode: u8,
+    /// Payload bytes within the parsed frame
+    pub payload: &'a [u8],
+    /// CRC-16 of the payload
+    pub crc: u16,
+}
+
+impl<'a> MessageRef<'a> {
+    /// Create a message, computing the CRC of `payload`
+    pub fn new(opcode: u8, payload: &'a [u8]) -> Self {
+        Self {
+            opcode,
+            payload,
+            crc: crc16(payload),
+        }
+    }
+
+    /// Parse a `V1` frame without copying the payload
+    pub fn parse(data: &'a [u8]) -> Result<Self, FrameError> {
+        Self::parse_versioned(data, FrameVersion::V1)
+    }
+
+    /// Parse a frame encoded with the given frame version
+    ///
+    /// Bytes after the CRC are ignored.
+    pub fn parse_versioned(data: &'a [u8], version: FrameVersion) -> Result<Self, FrameError> {
+        let header_len = version.header_len();
+        if data.len() < header_len {
+            return Err(FrameError::InvalidLength);
+        }
+
+        let len = version.payload_len(data);
+        let crc_offset = header_len + len;
+        if data.len() < crc_offset + FRAME_CRC_LEN {
+            return Err(FrameError::InvalidLength);
+        }
+
+        let payload = &data[header_len..crc_offset];
+        let crc = u16::from_be_bytes([data[crc_offset], data[crc_offset + 1]]);
+        if crc16(payload) != crc {
+            return Err(FrameError::CrcMismatch);
+        }
+
+        Ok(Self {
+            opcode: data[0],
+            payload,
+            crc,
+        })
+    }
+
+    /// Encoded size of this message in the given frame version
+    pub fn frame_len(&self, version: FrameVersion) -> usize {
+        version.header_len() + self.payload.len() + FRAME_CRC_LEN
+    }
+
+    /// Serialize using the legacy `V1` frame format into a buffer of capacity `N`
+    pub fn serialize<const N: usize>(&self) -> Result<heapless::Vec<u8, N>, FrameError> {
+        self.serialize_versioned(FrameVersion::V1)
+    }
+
+    /// Serialize using the given frame version into a buffer of capacity `N`
+    ///
+    /// Fails with `InvalidLength` if the payload does not fit the version's
+    /// length field and with `BufferFull` if the frame exceeds `N` bytes.
+    pub fn serialize_versioned<const N: usize>(
+        &self,
+        version: FrameVersion,
+    ) -> Result<heapless::Vec<u8, N>, FrameError> {
+        if self.payload.len() > version.max_payload_len() {
+            return Err(FrameError::InvalidLength);
+        }
+
+        let len = (self.payload.len() as u16).to_be_bytes();
+        let header_v1 = [self.opcode, len[1]];
+        let header_v2 = [self.opcode, len[0], len[1]];
+        let header: &[u8] = match version {
+            FrameVersion::V1 => &header_v1,
+            FrameVersion::V2 => &header_v2,
+        };
+        let crc = self.crc.to_be_bytes();
+
+        let mut result = heapless::Vec::new();
+        for part in [header, self.payload, &crc] {
+            result
+                .extend_from_slice(part)
+                .map_err(|_| FrameError::BufferFull)?;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_borrows_payload() {
+        let frame = MessageRef::new(0x01, &[50, 60, 70, 1])
+            .serialize::<16>()
+            .unwrap();
+        let msg = MessageRef::parse(&frame).unwrap();
+        assert_eq!(msg.opcode, 0x01);
+        assert_eq!(msg.payload, &[50, 60, 70, 1]);
+        assert_eq!(msg.payload.as_ptr(), frame[2..].as_ptr());
+    }
+
+    #[test]
+    fn test_v2_round_trip() {
+        let payload = [0xAB; 300];
+        let frame = MessageRef::new(0x09, &payload)
+            .serialize_versioned::<320>(FrameVersion::V2)
+            .unwrap();
+        assert_eq!(frame.len(), 305);
+        let msg = MessageRef::parse_versioned(&frame, FrameVersion::V2).unwrap();
+        assert_eq!(msg.payload.len(), 300);
+    }
+
+    #[test]
+    fn test_serialize_reports_full_buffer() {
+        let msg = MessageRef::new(0x01, &[1, 2, 3, 4]);
+        assert_eq!(msg.serialize::<7>(), Err(FrameError::BufferFull));
+        assert_eq!(msg.serialize::<8>().map(|f| f.len()), Ok(8));
+    }
+
+    #[test]
+    fn test_parse_rejects_corrupt_frames() {
+        let mut frame = MessageRef::new(0x01, &[1, 2]).serialize::<8>().unwrap();
+        assert_eq!(
+            MessageRef::parse(&frame[..5]),
+            Err(FrameError::InvalidLength)
+        );
+        frame[2] ^= 0xFF;
+        assert_eq!(MessageRef::parse(&frame), Err(FrameError::CrcMismatch));
+    }
+}
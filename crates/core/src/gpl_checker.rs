@@ -19,12 +19,16 @@ impl GPLChecker {
     }
 
     fn initialize_compatibility(&mut self) {
-        self.license_compatibility.insert("GPL-3.0".to_string(), true);
-        self.license_compatibility.insert("GPL-2.0".to_string(), false);
+        self.license_compatibility
+            .insert("GPL-3.0".to_string(), true);
+        self.license_compatibility
+            .insert("GPL-2.0".to_string(), false);
         self.license_compatibility.insert("MIT".to_string(), true);
-        self.license_compatibility.insert("Apache-2.0".to_string(), true);
+        self.license_compatibility
+            .insert("Apache-2.0".to_string(), true);
         self.license_compatibility.insert("BSD".to_string(), true);
-        self.license_compatibility.insert("Proprietary".to_string(), false);
+        self.license_compatibility
+            .insert("Proprietary".to_string(), false);
     }
 
     pub fn check_license_header(&self, file_path: &str, content: &str) -> LicenseHeader {
@@ -100,7 +104,10 @@ impl GPLChecker {
                 violations.push(GPLViolation {
                     file: file.file_path.clone(),
                     violation_type: "Incompatible license".to_string(),
-                    description: format!("File has {:?} license, expected GPL-3.0", file.license_type),
+                    description: format!(
+                        "File has {:?} license, expected GPL-3.0",
+                        file.license_type
+                    ),
                     severity: LegalRisk::High,
                 });
             }
@@ -109,7 +116,10 @@ impl GPLChecker {
         violations
     }
 
-    pub fn check_dependency_licenses(&self, dependencies: Vec<(String, String)>) -> Vec<GPLViolation> {
+    pub fn check_dependency_licenses(
+        &self,
+        dependencies: Vec<(String, String)>,
+    ) -> Vec<GPLViolation> {
         let mut violations = Vec::new();
 
         for (dep_name, license) in dependencies {
@@ -117,7 +127,10 @@ impl GPLChecker {
                 violations.push(GPLViolation {
                     file: format!("Cargo.toml ({})", dep_name),
                     violation_type: "Incompatible dependency license".to_string(),
-                    description: format!("Dependency {} has incompatible license: {}", dep_name, license),
+                    description: format!(
+                        "Dependency {} has incompatible license: {}",
+                        dep_name, license
+                    ),
                     severity: LegalRisk::High,
                 });
             }
@@ -133,13 +146,19 @@ impl GPLChecker {
         let (with_headers, total) = self.verify_all_files_have_headers(files.clone());
         let percentage = (with_headers as f32 / total as f32) * 100.0;
 
-        report.push_str(&format!("Files with GPL headers: {}/{} ({:.1}%)\n", with_headers, total, percentage));
+        report.push_str(&format!(
+            "Files with GPL headers: {}/{} ({:.1}%)\n",
+            with_headers, total, percentage
+        ));
 
         let violations = self.check_for_violations(files);
         report.push_str(&format!("Violations found: {}\n\n", violations.len()));
 
         for violation in violations {
-            report.push_str(&format!("  {} ({})\n", violation.file, violation.violation_type));
+            report.push_str(&format!(
+                "  {} ({})\n",
+                violation.file, violation.violation_type
+            ));
             report.push_str(&format!("    {}\n", violation.description));
         }
 
@@ -160,7 +179,10 @@ mod tests {
     #[test]
     fn test_checker_creation() {
         let checker = GPLChecker::new();
-        assert_eq!(checker.required_header, "GPL-3.0-or-later WITH Classpath-exception-2.0");
+        assert_eq!(
+            checker.required_header,
+            "GPL-3.0-or-later WITH Classpath-exception-2.0"
+        );
     }
 
     #[test]
@@ -127,7 +127,10 @@ impl SprintPlan {
     }
 
     pub fn get_tasks_by_category(&self, category: TaskCategory) -> Vec<&ImplementationTask> {
-        self.tasks.iter().filter(|t| t.category == category).collect()
+        self.tasks
+            .iter()
+            .filter(|t| t.category == category)
+            .collect()
     }
 
     pub fn serialize_to_json(&self) -> Result<String, serde_json::Error> {
@@ -139,22 +142,40 @@ impl SprintPlan {
         report.push_str("=== IMPLEMENTATION SPRINT REPORT ===\n\n");
 
         report.push_str(&format!("Sprint: {}\n", self.sprint_name));
-        report.push_str(&format!("Period: {} to {}\n\n", self.start_date, self.end_date));
+        report.push_str(&format!(
+            "Period: {} to {}\n\n",
+            self.start_date, self.end_date
+        ));
 
         report.push_str(&format!("Total Tasks: {}\n", self.total_tasks));
         report.push_str(&format!("  Completed: {}\n", self.completed_tasks));
-        report.push_str(&format!("  In Progress: {}\n", self.get_tasks_by_status(TaskStatus::InProgress).len()));
+        report.push_str(&format!(
+            "  In Progress: {}\n",
+            self.get_tasks_by_status(TaskStatus::InProgress).len()
+        ));
         report.push_str(&format!("  Blocked: {}\n", self.blocked_tasks));
-        report.push_str(&format!("  Pending: {}\n\n", self.get_tasks_by_status(TaskStatus::Pending).len()));
+        report.push_str(&format!(
+            "  Pending: {}\n\n",
+            self.get_tasks_by_status(TaskStatus::Pending).len()
+        ));
 
-        report.push_str(&format!("Completion: {}%\n", self.get_completion_percentage()));
-        report.push_str(&format!("Effort: {:.1}h / {:.1}h ({:.1}%)\n\n", 
-            self.completed_effort_hours, self.total_effort_hours, self.get_effort_completion_percentage()));
+        report.push_str(&format!(
+            "Completion: {}%\n",
+            self.get_completion_percentage()
+        ));
+        report.push_str(&format!(
+            "Effort: {:.1}h / {:.1}h ({:.1}%)\n\n",
+            self.completed_effort_hours,
+            self.total_effort_hours,
+            self.get_effort_completion_percentage()
+        ));
 
         report.push_str("Tasks:\n");
         for task in &self.tasks {
-            report.push_str(&format!("  [{}] {} ({:?}) - {}%\n", 
-                task.priority, task.title, task.status, task.completion_percentage));
+            report.push_str(&format!(
+                "  [{}] {} ({:?}) - {}%\n",
+                task.priority, task.title, task.status, task.completion_percentage
+            ));
         }
 
         report
@@ -163,7 +184,10 @@ impl SprintPlan {
 
 impl Default for SprintPlan {
     fn default() -> Self {
-        Self::new("sprint-1".to_string(), "Implementation Sprint 1".to_string())
+        Self::new(
+            "sprint-1".to_string(),
+            "Implementation Sprint 1".to_string(),
+        )
     }
 }
 
@@ -228,7 +252,11 @@ mod tests {
                 description: "Test".to_string(),
                 category: TaskCategory::BluetoothBackends,
                 priority: 1,
-                status: if i < 2 { TaskStatus::Completed } else { TaskStatus::Pending },
+                status: if i < 2 {
+                    TaskStatus::Completed
+                } else {
+                    TaskStatus::Pending
+                },
                 effort_hours: 1.0,
                 assigned_to: "dev".to_string(),
                 dependencies: Vec::new(),
@@ -1,5 +1,7 @@
 use crate::error::{Error, Result};
-use crate::upstream::{Commit, FirmwareVersion, ProtocolDefinition, Release, Tag, UpstreamDiff, UpstreamRepository};
+use crate::upstream::{
+    Commit, FirmwareVersion, ProtocolDefinition, Release, Tag, UpstreamDiff, UpstreamRepository,
+};
 use std::collections::HashMap;
 
 pub struct DataIngestionEngine {
@@ -75,10 +77,14 @@ impl DataIngestionEngine {
         Ok(())
     }
 
-    pub fn ingest_firmware_versions(&mut self, firmware_versions: Vec<FirmwareVersion>) -> Result<()> {
+    pub fn ingest_firmware_versions(
+        &mut self,
+        firmware_versions: Vec<FirmwareVersion>,
+    ) -> Result<()> {
         for firmware in firmware_versions {
             self.upstream_diff.add_firmware(firmware.clone());
-            self.firmware_cache.insert(firmware.version.clone(), firmware);
+            self.firmware_cache
+                .insert(firmware.version.clone(), firmware);
         }
         Ok(())
     }
@@ -141,44 +147,84 @@ impl DataIngestionEngine {
         let mut report = String::new();
         report.push_str("=== UPSTREAM DATA INGESTION REPORT ===\n\n");
 
-        report.push_str(&format!("Repository: {}\n", self.upstream_diff.repository.full_name));
+        report.push_str(&format!(
+            "Repository: {}\n",
+            self.upstream_diff.repository.full_name
+        ));
         report.push_str(&format!("Owner: {}\n", self.upstream_diff.repository.owner));
         report.push_str(&format!("Stars: {}\n", self.upstream_diff.repository.stars));
         report.push_str(&format!("Forks: {}\n", self.upstream_diff.repository.forks));
-        report.push_str(&format!("Open Issues: {}\n\n", self.upstream_diff.repository.open_issues));
-
-        report.push_str(&format!("Latest Commits: {}\n", self.upstream_diff.latest_commits.len()));
+        report.push_str(&format!(
+            "Open Issues: {}\n\n",
+            self.upstream_diff.repository.open_issues
+        ));
+
+        report.push_str(&format!(
+            "Latest Commits: {}\n",
+            self.upstream_diff.latest_commits.len()
+        ));
         if let Some(commit) = self.get_latest_commit() {
-            report.push_str(&format!("  Latest: {} ({})\n", commit.sha[..8].to_string(), commit.date));
+            report.push_str(&format!(
+                "  Latest: {} ({})\n",
+                commit.sha[..8].to_string(),
+                commit.date
+            ));
         }
 
-        report.push_str(&format!("\nLatest Releases: {}\n", self.upstream_diff.latest_releases.len()));
+        report.push_str(&format!(
+            "\nLatest Releases: {}\n",
+            self.upstream_diff.latest_releases.len()
+        ));
         if let Some(release) = self.get_latest_release() {
-            report.push_str(&format!("  Latest: {} ({})\n", release.tag_name, release.published_at));
+            report.push_str(&format!(
+                "  Latest: {} ({})\n",
+                release.tag_name, release.published_at
+            ));
         }
 
         report.push_str(&format!("\nTags: {}\n", self.upstream_diff.tags.len()));
-        report.push_str(&format!("Firmware Versions: {}\n", self.upstream_diff.firmware_versions.len()));
-
-        report.push_str(&format!("\nProtocol Drift: {}\n", self.has_protocol_drift()));
-        report.push_str(&format!("Breaking Changes: {}\n", self.has_breaking_changes()));
-
-        report.push_str(&format!("\nNew Message Types: {}\n", self.upstream_diff.new_message_types.len()));
+        report.push_str(&format!(
+            "Firmware Versions: {}\n",
+            self.upstream_diff.firmware_versions.len()
+        ));
+
+        report.push_str(&format!(
+            "\nProtocol Drift: {}\n",
+            self.has_protocol_drift()
+        ));
+        report.push_str(&format!(
+            "Breaking Changes: {}\n",
+            self.has_breaking_changes()
+        ));
+
+        report.push_str(&format!(
+            "\nNew Message Types: {}\n",
+            self.upstream_diff.new_message_types.len()
+        ));
         for msg_type in &self.upstream_diff.new_message_types {
             report.push_str(&format!("  - {}\n", msg_type));
         }
 
-        report.push_str(&format!("\nNew UUIDs: {}\n", self.upstream_diff.new_uuids.len()));
+        report.push_str(&format!(
+            "\nNew UUIDs: {}\n",
+            self.upstream_diff.new_uuids.len()
+        ));
         for uuid in &self.upstream_diff.new_uuids {
             report.push_str(&format!("  - {}\n", uuid));
         }
 
-        report.push_str(&format!("\nNew Features: {}\n", self.upstream_diff.new_features.len()));
+        report.push_str(&format!(
+            "\nNew Features: {}\n",
+            self.upstream_diff.new_features.len()
+        ));
         for feature in &self.upstream_diff.new_features {
             report.push_str(&format!("  - {}\n", feature));
         }
 
-        report.push_str(&format!("\nDependency Changes: {}\n", self.upstream_diff.dependency_changes.len()));
+        report.push_str(&format!(
+            "\nDependency Changes: {}\n",
+            self.upstream_diff.dependency_changes.len()
+        ));
         for (dep, version) in &self.upstream_diff.dependency_changes {
             report.push_str(&format!("  - {}: {}\n", dep, version));
         }
@@ -118,7 +118,8 @@ impl LegalScanResult {
 
     pub fn add_trademark_usage(&mut self, usage: TrademarkUsage) {
         if !usage.compliant {
-            self.findings.push(format!("Non-compliant trademark usage: {}", usage.term));
+            self.findings
+                .push(format!("Non-compliant trademark usage: {}", usage.term));
         }
         self.trademark_usage.push(usage);
     }
@@ -141,7 +142,10 @@ impl LegalScanResult {
 
     pub fn has_critical_issues(&self) -> bool {
         !self.dmca_notices.is_empty()
-            || self.gpl_violations.iter().any(|v| v.severity == LegalRisk::Critical)
+            || self
+                .gpl_violations
+                .iter()
+                .any(|v| v.severity == LegalRisk::Critical)
             || self.firmware_analysis.is_nda_protected
     }
 
@@ -169,38 +173,68 @@ impl LegalScanResult {
 
         report.push_str(&format!("Scan Date: {}\n", self.scan_date));
         report.push_str(&format!("Overall Risk Level: {:?}\n", self.overall_risk));
-        report.push_str(&format!("Safe to Distribute: {}\n\n", self.is_safe_to_distribute));
+        report.push_str(&format!(
+            "Safe to Distribute: {}\n\n",
+            self.is_safe_to_distribute
+        ));
 
         report.push_str(&format!("DMCA Notices: {}\n", self.dmca_notices.len()));
         for notice in &self.dmca_notices {
             report.push_str(&format!("  - {} ({})\n", notice.repository, notice.date));
         }
 
-        report.push_str(&format!("\nGPL Violations: {}\n", self.gpl_violations.len()));
+        report.push_str(&format!(
+            "\nGPL Violations: {}\n",
+            self.gpl_violations.len()
+        ));
         for violation in &self.gpl_violations {
-            report.push_str(&format!("  - {} ({:?})\n", violation.file, violation.severity));
+            report.push_str(&format!(
+                "  - {} ({:?})\n",
+                violation.file, violation.severity
+            ));
         }
 
-        report.push_str(&format!("\nLicense Headers: {}\n", self.license_headers.len()));
-        report.push_str(&format!("  Consistency: {:.1}%\n", self.get_license_consistency()));
-
-        report.push_str(&format!("\nTrademark Usage: {}\n", self.trademark_usage.len()));
+        report.push_str(&format!(
+            "\nLicense Headers: {}\n",
+            self.license_headers.len()
+        ));
+        report.push_str(&format!(
+            "  Consistency: {:.1}%\n",
+            self.get_license_consistency()
+        ));
+
+        report.push_str(&format!(
+            "\nTrademark Usage: {}\n",
+            self.trademark_usage.len()
+        ));
         for usage in &self.trademark_usage {
             let status = if usage.compliant { "✓" } else { "✗" };
             report.push_str(&format!("  {} {}\n", status, usage.term));
         }
 
         report.push_str(&format!("\nFirmware Analysis:\n"));
-        report.push_str(&format!("  Proprietary: {}\n", self.firmware_analysis.is_proprietary));
-        report.push_str(&format!("  NDA Protected: {}\n", self.firmware_analysis.is_nda_protected));
-        report.push_str(&format!("  Apple Firmware: {}\n", self.firmware_analysis.is_apple_firmware));
+        report.push_str(&format!(
+            "  Proprietary: {}\n",
+            self.firmware_analysis.is_proprietary
+        ));
+        report.push_str(&format!(
+            "  NDA Protected: {}\n",
+            self.firmware_analysis.is_nda_protected
+        ));
+        report.push_str(&format!(
+            "  Apple Firmware: {}\n",
+            self.firmware_analysis.is_apple_firmware
+        ));
 
         report.push_str(&format!("\nFindings: {}\n", self.findings.len()));
         for finding in &self.findings {
             report.push_str(&format!("  - {}\n", finding));
         }
 
-        report.push_str(&format!("\nRecommendations: {}\n", self.recommendations.len()));
+        report.push_str(&format!(
+            "\nRecommendations: {}\n",
+            self.recommendations.len()
+        ));
         for rec in &self.recommendations {
             report.push_str(&format!("  - {}\n", rec));
         }
@@ -1,111 +1,153 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![forbid(unsafe_code)]
 #![warn(missing_docs)]
 #![warn(clippy::all)]
 
 //! LibrePods Core Engine - Production-grade Rust implementation
 
-pub mod error;
-pub mod protocol;
-pub mod device;
-pub mod state;
-pub mod crypto;
+pub mod frame;
+
+pub use frame::{FrameError, FrameVersion, MessageRef};
+
+// Everything else needs `std`; without it only the frame module is built
+#[cfg(feature = "std")]
+pub mod advertisement;
+#[cfg(feature = "std")]
+pub mod automation;
+#[cfg(feature = "std")]
+pub mod backends;
+#[cfg(feature = "std")]
+pub mod battery;
+#[cfg(feature = "std")]
 pub mod bluetooth;
+#[cfg(feature = "std")]
+pub mod capabilities;
+#[cfg(feature = "std")]
+pub mod capture;
+#[cfg(feature = "std")]
+pub mod change_applier;
+#[cfg(feature = "std")]
+pub mod ci_pipeline;
+#[cfg(feature = "std")]
+pub mod codebase_diff;
+#[cfg(feature = "std")]
+pub mod coverage_analyzer;
+#[cfg(feature = "std")]
+pub mod crypto;
+#[cfg(feature = "std")]
+pub mod device;
+#[cfg(feature = "std")]
+pub mod dissector;
+#[cfg(feature = "std")]
+pub mod dmca_scanner;
+#[cfg(feature = "std")]
+pub mod engine;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
 pub mod events;
-pub mod models;
-pub mod parser;
-pub mod backends;
-pub mod upstream;
-pub mod ingestion;
-pub mod protocol_analyzer;
+#[cfg(feature = "std")]
 pub mod firmware_analyzer;
-pub mod legal_scan;
-pub mod dmca_scanner;
-pub mod gpl_checker;
-pub mod trademark_checker;
+#[cfg(feature = "std")]
 pub mod firmware_security;
-pub mod protocol_drift;
-pub mod protocol_comparator;
+#[cfg(feature = "std")]
 pub mod firmware_version_analyzer;
-pub mod codebase_diff;
-pub mod merge_planner;
-pub mod three_way_diff;
+#[cfg(feature = "std")]
+pub mod gpl_checker;
+#[cfg(feature = "std")]
 pub mod implementation_executor;
-pub mod change_applier;
-pub mod verification;
-pub mod ci_pipeline;
-pub mod test_runner;
-pub mod coverage_analyzer;
+#[cfg(feature = "std")]
+pub mod ingestion;
+#[cfg(feature = "std")]
+pub mod legal_scan;
+#[cfg(feature = "std")]
+pub mod merge_planner;
+#[cfg(feature = "std")]
+pub mod models;
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+pub mod mpris;
+#[cfg(feature = "std")]
+pub mod multipoint;
+#[cfg(feature = "std")]
+pub mod parser;
+#[cfg(feature = "std")]
+pub mod payload;
+#[cfg(feature = "std")]
+pub mod protocol;
+#[cfg(feature = "std")]
+pub mod protocol_analyzer;
+#[cfg(feature = "std")]
+pub mod protocol_comparator;
+#[cfg(feature = "std")]
+pub mod protocol_drift;
+#[cfg(feature = "std")]
+pub mod reconnect;
+#[cfg(feature = "std")]
+pub mod registry;
+#[cfg(feature = "std")]
 pub mod release_manager;
-pub mod sbom_generator;
+#[cfg(feature = "std")]
 pub mod release_notes;
+#[cfg(feature = "std")]
+pub mod request;
+#[cfg(feature = "std")]
+pub mod sbom_generator;
+#[cfg(feature = "std")]
 pub mod security;
+#[cfg(feature = "std")]
+pub mod simulator;
+#[cfg(feature = "std")]
+pub mod state;
+#[cfg(feature = "std")]
+pub mod state_machine;
+#[cfg(feature = "std")]
+pub mod store;
+#[cfg(feature = "std")]
+pub mod test_runner;
+#[cfg(feature = "std")]
+pub mod three_way_diff;
+#[cfg(feature = "std")]
+pub mod trademark_checker;
+#[cfg(feature = "std")]
+pub mod transport;
+#[cfg(feature = "std")]
+pub mod upstream;
+#[cfg(feature = "std")]
+pub mod verification;
 
+#[cfg(feature = "std")]
+pub use automation::{EarAction, EarCondition, EarRule, EarRuleEngine};
+#[cfg(feature = "std")]
+pub use battery::{BatteryComponent, BatteryHistory};
+#[cfg(feature = "std")]
+pub use capabilities::CapabilityMatrix;
+#[cfg(feature = "std")]
+pub use capture::{Capture, CapturingBackend};
+#[cfg(feature = "std")]
+pub use device::{Device, DeviceCapability, DeviceModel};
+#[cfg(feature = "std")]
+pub use dissector::{Dissection, Dissector};
+#[cfg(feature = "std")]
+pub use engine::Engine;
+#[cfg(feature = "std")]
 pub use error::{Error, Result};
-pub use device::{Device, DeviceModel, DeviceCapability};
-pub use protocol::{Message, MessageType};
-pub use state::DeviceState;
-pub use events::{Event, EventBus};
+#[cfg(feature = "std")]
+pub use events::{Event, EventBus, EventFilter, EventKind, EventType, Subscription};
+#[cfg(feature = "std")]
 pub use models::*;
-
-/// Core engine for managing AirPods devices
-pub struct Engine {
-    devices: std::collections::HashMap<String, Device>,
-    event_bus: EventBus,
-}
-
-impl Engine {
-    /// Create a new engine instance
-    pub fn new() -> Self {
-        Self {
-            devices: std::collections::HashMap::new(),
-            event_bus: EventBus::new(),
-        }
-    }
-
-    /// Get mutable reference to event bus
-    pub fn event_bus_mut(&mut self) -> &mut EventBus {
-        &mut self.event_bus
-    }
-
-    /// Get reference to event bus
-    pub fn event_bus(&self) -> &EventBus {
-        &self.event_bus
-    }
-
-    /// Register a device
-    pub fn register_device(&mut self, device: Device) {
-        self.devices.insert(device.id().to_string(), device);
-    }
-
-    /// Get device by ID
-    pub fn get_device(&self, id: &str) -> Option<&Device> {
-        self.devices.get(id)
-    }
-
-    /// Get mutable device reference
-    pub fn get_device_mut(&mut self, id: &str) -> Option<&mut Device> {
-        self.devices.get_mut(id)
-    }
-
-    /// List all devices
-    pub fn devices(&self) -> impl Iterator<Item = &Device> {
-        self.devices.values()
-    }
-}
-
-impl Default for Engine {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_engine_creation() {
-        let engine = Engine::new();
-        assert_eq!(engine.devices().count(), 0);
-    }
-}
+#[cfg(feature = "std")]
+pub use payload::Payload;
+#[cfg(feature = "std")]
+pub use protocol::{FrameDecoder, Message, MessageType};
+#[cfg(feature = "std")]
+pub use reconnect::{ReconnectPolicy, ReconnectSupervisor};
+#[cfg(feature = "std")]
+pub use registry::ProtocolRegistry;
+#[cfg(feature = "std")]
+pub use request::{RequestManager, RequestOptions};
+#[cfg(feature = "std")]
+pub use simulator::{SimulatedBackend, SimulatedDevice};
+#[cfg(feature = "std")]
+pub use state::{DeviceState, DeviceStateInfo, StateChange};
+#[cfg(feature = "std")]
+pub use store::{DevicePreferences, DeviceStore};
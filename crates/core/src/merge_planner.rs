@@ -1,4 +1,6 @@
-use crate::codebase_diff::{CodebaseDiffReport, DiffHunk, FileStatus, FileDiff, MergeDecision, MergeHunk};
+use crate::codebase_diff::{
+    CodebaseDiffReport, DiffHunk, FileDiff, FileStatus, MergeDecision, MergeHunk,
+};
 use std::collections::HashMap;
 
 pub struct MergePlanner {
@@ -18,7 +20,8 @@ impl MergePlanner {
     }
 
     fn initialize_priorities(&mut self) {
-        self.priority_map.insert("Bluetooth backends".to_string(), 1);
+        self.priority_map
+            .insert("Bluetooth backends".to_string(), 1);
         self.priority_map.insert("Protocol messages".to_string(), 2);
         self.priority_map.insert("FFI bridge".to_string(), 3);
         self.priority_map.insert("Android merge".to_string(), 4);
@@ -31,11 +34,16 @@ impl MergePlanner {
     }
 
     fn initialize_rules(&mut self) {
-        self.decision_rules.insert(FileStatus::Added, MergeDecision::Adopt);
-        self.decision_rules.insert(FileStatus::Modified, MergeDecision::Adapt);
-        self.decision_rules.insert(FileStatus::Deleted, MergeDecision::Skip);
-        self.decision_rules.insert(FileStatus::Renamed, MergeDecision::Adapt);
-        self.decision_rules.insert(FileStatus::Conflict, MergeDecision::Manual);
+        self.decision_rules
+            .insert(FileStatus::Added, MergeDecision::Adopt);
+        self.decision_rules
+            .insert(FileStatus::Modified, MergeDecision::Adapt);
+        self.decision_rules
+            .insert(FileStatus::Deleted, MergeDecision::Skip);
+        self.decision_rules
+            .insert(FileStatus::Renamed, MergeDecision::Adapt);
+        self.decision_rules
+            .insert(FileStatus::Conflict, MergeDecision::Manual);
     }
 
     pub fn generate_merge_plan(&self, diff_report: &CodebaseDiffReport) -> Vec<MergeHunk> {
@@ -112,12 +120,18 @@ impl MergePlanner {
         }
     }
 
-    pub fn categorize_changes(&self, diff_report: &CodebaseDiffReport) -> HashMap<String, Vec<FileDiff>> {
+    pub fn categorize_changes(
+        &self,
+        diff_report: &CodebaseDiffReport,
+    ) -> HashMap<String, Vec<FileDiff>> {
         let mut categories = HashMap::new();
 
         for diff in &diff_report.file_diffs {
             let category = self.categorize_file(&diff.file_path);
-            categories.entry(category).or_insert_with(Vec::new).push(diff.clone());
+            categories
+                .entry(category)
+                .or_insert_with(Vec::new)
+                .push(diff.clone());
         }
 
         categories
@@ -184,7 +198,10 @@ impl MergePlanner {
 
         report.push_str("\nMerge Strategy:\n");
         for hunk in &merge_hunks {
-            report.push_str(&format!("  [{}] {} ({:?})\n", hunk.priority, hunk.hunk.file_path, hunk.decision));
+            report.push_str(&format!(
+                "  [{}] {} ({:?})\n",
+                hunk.priority, hunk.hunk.file_path, hunk.decision
+            ));
         }
 
         report
@@ -1,9 +1,11 @@
 //! Data models for AirPods features
 
+use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
 
-/// ANC (Active Noise Cancellation) modes
+/// ANC (Active Noise Cancellation) modes, with their wire values
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
 pub enum AncMode {
     Off = 0,
     Active = 1,
@@ -11,26 +13,56 @@ pub enum AncMode {
     Adaptive = 3,
 }
 
+impl AncMode {
+    /// Parse a wire value, failing with `ParseError` for unknown modes
+    pub fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(AncMode::Off),
+            1 => Ok(AncMode::Active),
+            2 => Ok(AncMode::Transparency),
+            3 => Ok(AncMode::Adaptive),
+            _ => Err(Error::ParseError(format!("invalid ANC mode: {}", value))),
+        }
+    }
+
+    /// Wire value of this mode
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+}
+
 /// Ear detection state
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EarDetectionState {
-    Unknown,
-    LeftEarIn,
-    RightEarIn,
-    BothEarsIn,
-    BothEarsOut,
+    Unknown = 0,
+    LeftEarIn = 1,
+    RightEarIn = 2,
+    BothEarsIn = 3,
+    BothEarsOut = 4,
+}
+
+impl EarDetectionState {
+    /// Whether at least one bud is in an ear
+    pub fn is_in_ear(self) -> bool {
+        matches!(
+            self,
+            EarDetectionState::LeftEarIn
+                | EarDetectionState::RightEarIn
+                | EarDetectionState::BothEarsIn
+        )
+    }
 }
 
 /// Conversation awareness state
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ConversationAwarenessState {
-    Inactive,
-    Active,
-    Speaking,
+    Inactive = 0,
+    Active = 1,
+    Speaking = 2,
 }
 
 /// Spatial audio configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SpatialAudioConfig {
     pub enabled: bool,
     pub head_tracking: bool,
@@ -38,7 +70,7 @@ pub struct SpatialAudioConfig {
 }
 
 /// Hearing aid mode configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HearingAidConfig {
     pub enabled: bool,
     pub amplification_level: u8,
@@ -46,14 +78,14 @@ pub struct HearingAidConfig {
 }
 
 /// Device rename request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RenameRequest {
     pub device_id: String,
     pub new_name: String,
 }
 
 /// Multipoint connection info
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MultipointInfo {
     pub enabled: bool,
     pub connected_devices: Vec<String>,
@@ -61,7 +93,7 @@ pub struct MultipointInfo {
 }
 
 /// Heart rate measurement
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HeartRateMeasurement {
     pub bpm: u16,
     pub confidence: u8,
@@ -69,7 +101,7 @@ pub struct HeartRateMeasurement {
 }
 
 /// FindMy location data
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FindMyLocation {
     pub latitude: f64,
     pub longitude: f64,
@@ -80,16 +112,16 @@ pub struct FindMyLocation {
 /// Long press action configuration
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LongPressAction {
-    Siri,
-    PlayPause,
-    NextTrack,
-    PreviousTrack,
-    VolumeUp,
-    VolumeDown,
+    Siri = 0,
+    PlayPause = 1,
+    NextTrack = 2,
+    PreviousTrack = 3,
+    VolumeUp = 4,
+    VolumeDown = 5,
 }
 
 /// Custom transparency configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CustomTransparencyConfig {
     pub enabled: bool,
     pub ambient_mix_level: u8,
@@ -97,7 +129,7 @@ pub struct CustomTransparencyConfig {
 }
 
 /// Head gesture configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct HeadGestureConfig {
     pub double_tap_enabled: bool,
     pub double_tap_action: Option<LongPressAction>,
@@ -0,0 +1,346 @@
+//! Media player control over MPRIS on the D-Bus session bus
+
+use crate::automation::{ActionFuture, EarAction, EarCondition, EarRule, EarTransition};
+use crate::error::Result;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use zbus::zvariant::OwnedValue;
+use zbus::Connection;
+
+/// Bus name prefix shared by all MPRIS players
+pub const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+/// Object path every MPRIS player serves
+pub const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// Interface with the playback controls
+pub const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+/// What an [`MprisAction`] does to the players
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MprisCommand {
+    /// Pause every playing player and remember which ones they were
+    Pause,
+    /// Resume the players paused by `Pause` that are still paused
+    Resume,
+    /// Toggle every player
+    PlayPause,
+    /// Stop every player
+    Stop,
+}
+
+/// Drives the MPRIS players on a D-Bus connection
+///
+/// Pausing remembers the players that were playing, so resuming never
+/// starts a player the user had paused themselves.
+pub struct MprisController {
+    connection: Connection,
+    paused: Mutex<Vec<String>>,
+}
+
+impl MprisController {
+    /// Connect to the session bus
+    pub async fn session() -> Result<Self> {
+        Ok(Self::with_connection(Connection::session().await?))
+    }
+
+    /// Use an existing connection
+    pub fn with_connection(connection: Connection) -> Self {
+        Self {
+            connection,
+            paused: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Bus names of the players currently on the bus
+    pub async fn players(&self) -> Result<Vec<String>> {
+        let reply = self
+            .connection
+            .call_method(
+                Some("org.freedesktop.DBus"),
+                "/org/freedesktop/DBus",
+                Some("org.freedesktop.DBus"),
+                "ListNames",
+                &(),
+            )
+            .await?;
+        let names: Vec<String> = reply.body().deserialize()?;
+        Ok(names
+            .into_iter()
+            .filter(|name| name.starts_with(MPRIS_PREFIX))
+            .collect())
+    }
+
+    /// A player's `PlaybackStatus`: `Playing`, `Paused` or `Stopped`
+    pub async fn playback_status(&self, player: &str) -> Result<String> {
+        let reply = self
+            .connection
+            .call_method(
+                Some(player),
+                MPRIS_PATH,
+                Some("org.freedesktop.DBus.Properties"),
+                "Get",
+                &(PLAYER_INTERFACE, "PlaybackStatus"),
+            )
+            .await?;
+        let value: OwnedValue = reply.body().deserialize()?;
+        Ok(String::try_from(value).map_err(zbus::Error::from)?)
+    }
+
+    /// Pause the playing players, returning how many this call paused
+    ///
+    /// Players that fail to report their status or to pause are skipped.
+    pub async fn pause(&self) -> Result<usize> {
+        let mut paused = self.paused.lock().await;
+        let mut count = 0;
+        for player in self.players().await? {
+            let result = match self.playback_status(&player).await {
+                Ok(status) if status == "Playing" => self.call(&player, "Pause").await,
+                Ok(_) => continue,
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(()) => {
+                    count += 1;
+                    if !paused.contains(&player) {
+                        paused.push(player);
+                    }
+                }
+                Err(err) => log::debug!("skipping player {}: {}", player, err),
+            }
+        }
+        Ok(count)
+    }
+
+    /// Resume the players paused by [`MprisController::pause`]
+    ///
+    /// Players that have since gone away or changed state are skipped.
+    /// Returns how many were resumed.
+    pub async fn resume(&self) -> Result<usize> {
+        let players = std::mem::take(&mut *self.paused.lock().await);
+        let mut resumed = 0;
+        for player in players {
+            match self.playback_status(&player).await {
+                Ok(status) if status == "Paused" => {
+                    self.call(&player, "Play").await?;
+                    resumed += 1;
+                }
+                Ok(_) => {}
+                Err(err) => log::debug!("skipping player {}: {}", player, err),
+            }
+        }
+        Ok(resumed)
+    }
+
+    /// Run `command` against the players
+    pub async fn execute(&self, command: MprisCommand) -> Result<()> {
+        match command {
+            MprisCommand::Pause => self.pause().await.map(|_| ()),
+            MprisCommand::Resume => self.resume().await.map(|_| ()),
+            MprisCommand::PlayPause => self.call_all("PlayPause").await,
+            MprisCommand::Stop => self.call_all("Stop").await,
+        }
+    }
+
+    /// Rules pausing when a bud is removed and resuming when both are in
+    pub fn default_rules(self: &Arc<Self>) -> Vec<EarRule> {
+        vec![
+            EarRule::new(
+                "pause on removal",
+                EarCondition::BudRemoved,
+                MprisAction::new(Arc::clone(self), MprisCommand::Pause),
+            ),
+            EarRule::new(
+                "resume on insertion",
+                EarCondition::BothInserted,
+                MprisAction::new(Arc::clone(self), MprisCommand::Resume),
+            ),
+        ]
+    }
+
+    async fn call_all(&self, method: &str) -> Result<()> {
+        for player in self.players().await? {
+            self.call(&player, method).await?;
+        }
+        Ok(())
+    }
+
+    async fn call(&self, player: &str, method: &str) -> Result<()> {
+        self.connection
+            .call_method(
+                Some(player),
+                MPRIS_PATH,
+                Some(PLAYER_INTERFACE),
+                method,
+                &(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Ear detection action sending an MPRIS command
+pub struct MprisAction {
+    controller: Arc<MprisController>,
+    command: MprisCommand,
+}
+
+impl MprisAction {
+    /// Action sending `command` through `controller`
+    pub fn new(controller: Arc<MprisController>, command: MprisCommand) -> Self {
+        Self {
+            controller,
+            command,
+        }
+    }
+}
+
+impl EarAction for MprisAction {
+    fn run<'a>(&'a self, _device_id: &'a str, _transition: EarTransition) -> ActionFuture<'a> {
+        Box::pin(self.controller.execute(self.command))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::automation::EarRuleEngine;
+    use crate::models::EarDetectionState;
+    use std::sync::Mutex as StdMutex;
+    use tokio::net::UnixStream;
+    use zbus::{connection, interface, Guid};
+
+    /// Stands in for the session bus daemon
+    struct MockBus {
+        names: Vec<String>,
+    }
+
+    #[interface(name = "org.freedesktop.DBus")]
+    impl MockBus {
+        fn list_names(&self) -> Vec<String> {
+            self.names.clone()
+        }
+    }
+
+    struct MockPlayer {
+        status: Arc<StdMutex<String>>,
+        /// Pause calls left to fail before pausing works
+        failing_pauses: u32,
+    }
+
+    #[interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl MockPlayer {
+        fn play(&self) {
+            *self.status.lock().unwrap() = "Playing".to_string();
+        }
+
+        fn pause(&mut self) -> zbus::fdo::Result<()> {
+            if self.failing_pauses > 0 {
+                self.failing_pauses -= 1;
+                return Err(zbus::fdo::Error::Failed("not responding".to_string()));
+            }
+            *self.status.lock().unwrap() = "Paused".to_string();
+            Ok(())
+        }
+
+        #[zbus(property)]
+        fn playback_status(&self) -> String {
+            self.status.lock().unwrap().clone()
+        }
+    }
+
+    /// A controller talking to a mock bus with one player in `status`
+    async fn mock_session(status: &str) -> (MprisController, Arc<StdMutex<String>>, Connection) {
+        mock_players(status, &["mock"], 0).await
+    }
+
+    /// A controller talking to a mock bus listing `players`
+    ///
+    /// The bus is peer to peer, so every name reaches the same player, whose
+    /// first `failing_pauses` pause calls fail.
+    async fn mock_players(
+        status: &str,
+        players: &[&str],
+        failing_pauses: u32,
+    ) -> (MprisController, Arc<StdMutex<String>>, Connection) {
+        let status = Arc::new(StdMutex::new(status.to_string()));
+        let (server, client) = UnixStream::pair().unwrap();
+        let mut names = vec!["org.freedesktop.DBus".to_string()];
+        names.extend(players.iter().map(|p| format!("{}{}", MPRIS_PREFIX, p)));
+        let bus = MockBus { names };
+        let player = MockPlayer {
+            status: Arc::clone(&status),
+            failing_pauses,
+        };
+
+        let server = connection::Builder::unix_stream(server)
+            .server(Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at("/org/freedesktop/DBus", bus)
+            .unwrap()
+            .serve_at(MPRIS_PATH, player)
+            .unwrap()
+            .build();
+        let client = connection::Builder::unix_stream(client).p2p().build();
+        let (server, client) = tokio::join!(server, client);
+
+        (
+            MprisController::with_connection(client.unwrap()),
+            status,
+            server.unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_lists_players() {
+        let (controller, _status, _bus) = mock_session("Stopped").await;
+        assert_eq!(
+            controller.players().await.unwrap(),
+            vec!["org.mpris.MediaPlayer2.mock".to_string()]
+        );
+        assert_eq!(
+            controller
+                .playback_status("org.mpris.MediaPlayer2.mock")
+                .await
+                .unwrap(),
+            "Stopped"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resume_only_what_was_paused() {
+        let (controller, status, _bus) = mock_session("Paused").await;
+        assert_eq!(controller.pause().await.unwrap(), 0);
+        assert_eq!(controller.resume().await.unwrap(), 0);
+        assert_eq!(*status.lock().unwrap(), "Paused");
+    }
+
+    #[tokio::test]
+    async fn test_pause_skips_failing_players() {
+        let (controller, status, _bus) = mock_players("Playing", &["broken", "working"], 1).await;
+        assert_eq!(controller.pause().await.unwrap(), 1);
+        assert_eq!(*status.lock().unwrap(), "Paused");
+
+        // Only players paused by this call are counted
+        assert_eq!(controller.pause().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_ear_rules_drive_player() {
+        let (controller, status, _bus) = mock_session("Playing").await;
+        let controller = Arc::new(controller);
+        let mut rules = EarRuleEngine::new();
+        for rule in controller.default_rules() {
+            rules.add_rule(rule);
+        }
+
+        rules.handle("dev", EarDetectionState::BothEarsIn).await;
+        let outcomes = rules.handle("dev", EarDetectionState::LeftEarIn).await;
+        assert_eq!(outcomes, vec![("pause on removal".to_string(), Ok(()))]);
+        assert_eq!(*status.lock().unwrap(), "Paused");
+
+        rules.handle("dev", EarDetectionState::BothEarsIn).await;
+        assert_eq!(*status.lock().unwrap(), "Playing");
+    }
+}
@@ -0,0 +1,144 @@
+//! Multipoint host sessions
+//!
+//! Buds paired with several hosts report their session in a
+//! `MultipointControl` message: the hosts they are connected to, the host
+//! currently playing audio, and in `enabled` whether they switch hosts
+//! automatically. Hosts send the same message as a command. With
+//! `active_device` set it asks for audio to move to that host; without it,
+//! it turns automatic switching on or off. Commands carry no host list.
+
+use crate::models::MultipointInfo;
+use crate::payload::Payload;
+use serde::{Deserialize, Serialize};
+
+/// Change to a multipoint session, published as `EventType::Multipoint`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MultipointEvent {
+    /// A host joined the session
+    HostConnected(String),
+    /// A host left the session
+    HostDisconnected(String),
+    /// Audio moved to another host
+    ActiveHostChanged {
+        /// Host audio moved away from
+        from: Option<String>,
+        /// Host now playing audio
+        to: Option<String>,
+    },
+    /// Automatic switching was turned on or off
+    AutoSwitchChanged(bool),
+}
+
+/// Command asking the buds to move audio to `host`
+pub fn handoff_command(host: &str) -> Payload {
+    Payload::MultipointControl(MultipointInfo {
+        enabled: true,
+        connected_devices: Vec::new(),
+        active_device: Some(host.to_string()),
+    })
+}
+
+/// Command turning automatic host switching on or off
+pub fn auto_switch_command(enabled: bool) -> Payload {
+    Payload::MultipointControl(MultipointInfo {
+        enabled,
+        connected_devices: Vec::new(),
+        active_device: None,
+    })
+}
+
+/// Events describing the step from one session report to the next
+///
+/// Without a previous report the session is taken to have had no hosts and
+/// automatic switching on.
+pub fn session_changes(
+    before: Option<&MultipointInfo>,
+    after: &MultipointInfo,
+) -> Vec<MultipointEvent> {
+    let empty = MultipointInfo {
+        enabled: true,
+        connected_devices: Vec::new(),
+        active_device: None,
+    };
+    let before = before.unwrap_or(&empty);
+
+    let mut events: Vec<MultipointEvent> = after
+        .connected_devices
+        .iter()
+        .filter(|host| !before.connected_devices.contains(host))
+        .map(|host| MultipointEvent::HostConnected(host.clone()))
+        .collect();
+    events.extend(
+        before
+            .connected_devices
+            .iter()
+            .filter(|host| !after.connected_devices.contains(host))
+            .map(|host| MultipointEvent::HostDisconnected(host.clone())),
+    );
+    if before.active_device != after.active_device {
+        events.push(MultipointEvent::ActiveHostChanged {
+            from: before.active_device.clone(),
+            to: after.active_device.clone(),
+        });
+    }
+    if before.enabled != after.enabled {
+        events.push(MultipointEvent::AutoSwitchChanged(after.enabled));
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(hosts: &[&str], active: Option<&str>, enabled: bool) -> MultipointInfo {
+        MultipointInfo {
+            enabled,
+            connected_devices: hosts.iter().map(|h| h.to_string()).collect(),
+            active_device: active.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_first_report() {
+        let events = session_changes(None, &session(&["phone", "laptop"], Some("phone"), true));
+        assert_eq!(
+            events,
+            vec![
+                MultipointEvent::HostConnected("phone".to_string()),
+                MultipointEvent::HostConnected("laptop".to_string()),
+                MultipointEvent::ActiveHostChanged {
+                    from: None,
+                    to: Some("phone".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handoff_and_block() {
+        let before = session(&["phone", "laptop"], Some("phone"), true);
+        let after = session(&["laptop", "tablet"], Some("laptop"), false);
+        assert_eq!(
+            session_changes(Some(&before), &after),
+            vec![
+                MultipointEvent::HostConnected("tablet".to_string()),
+                MultipointEvent::HostDisconnected("phone".to_string()),
+                MultipointEvent::ActiveHostChanged {
+                    from: Some("phone".to_string()),
+                    to: Some("laptop".to_string()),
+                },
+                MultipointEvent::AutoSwitchChanged(false),
+            ]
+        );
+        assert!(session_changes(Some(&after), &after).is_empty());
+    }
+
+    #[test]
+    fn test_commands_encode() {
+        for command in [handoff_command("laptop"), auto_switch_command(false)] {
+            let message = command.to_message().unwrap();
+            assert_eq!(message.decode_payload().unwrap(), command);
+        }
+    }
+}
@@ -2,29 +2,48 @@
 
 use crate::error::Result;
 use crate::protocol::{Message, MessageType};
-use nom::{
-    bytes::complete::take,
-    number::complete::be_u8,
-    IResult,
-};
+use crate::registry::ProtocolRegistry;
+use nom::{bytes::complete::take, number::complete::be_u8, IResult};
 
 /// Parse AAP message from bytes
 pub fn parse_message(input: &[u8]) -> IResult<&[u8], Message> {
+    parse_with(input, MessageType::from_opcode)
+}
+
+/// Parse AAP message from bytes, looking its opcode up in `registry`
+///
+/// Vendor opcodes loaded into the registry parse as `MessageType::Vendor`.
+pub fn parse_message_with<'a>(
+    registry: &ProtocolRegistry,
+    input: &'a [u8],
+) -> IResult<&'a [u8], Message> {
+    parse_with(input, |opcode| registry.message_type(opcode))
+}
+
+fn parse_with(input: &[u8], lookup: impl Fn(u8) -> MessageType) -> IResult<&[u8], Message> {
     let (input, msg_type_byte) = be_u8(input)?;
-    let msg_type = MessageType::from_u8(msg_type_byte)
-        .map_err(|_| nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Verify)))?;
-    
+    let msg_type = lookup(msg_type_byte);
+
     let (input, length) = be_u8(input)?;
     let (input, payload) = take(length as usize)(input)?;
-    let (input, crc_bytes) = take(2usize)(input)?;
-    
+    let (rest, crc_bytes) = take(2usize)(input)?;
+
     let crc = u16::from_be_bytes([crc_bytes[0], crc_bytes[1]]);
-    
-    Ok((input, Message {
-        msg_type,
-        payload: payload.to_vec(),
-        crc,
-    }))
+    if Message::calculate_crc(payload) != crc {
+        return Err(nom::Err::Error(nom::error::Error::new(
+            input,
+            nom::error::ErrorKind::Verify,
+        )));
+    }
+
+    Ok((
+        rest,
+        Message {
+            msg_type,
+            payload: payload.to_vec(),
+            crc,
+        },
+    ))
 }
 
 /// Parse battery status message
@@ -39,6 +58,36 @@ pub fn parse_battery_status(payload: &[u8]) -> Result<(u8, u8, u8)> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_message_verifies_crc() {
+        let mut bytes = Message::new(MessageType::BatteryStatus, vec![50, 60, 70])
+            .serialize()
+            .unwrap();
+        let (rest, msg) = parse_message(&bytes).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(msg.payload, vec![50, 60, 70]);
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        assert!(parse_message(&bytes).is_err());
+    }
+
+    #[test]
+    fn test_parse_vendor_opcode() {
+        let mut registry = ProtocolRegistry::builtin();
+        registry
+            .load_json(r#"{ "messages": [{ "opcode": 32, "name": "VendorSleep" }] }"#)
+            .unwrap();
+        let bytes = Message::new(MessageType::Unknown(0x20), vec![1])
+            .serialize()
+            .unwrap();
+
+        let (_, msg) = parse_message(&bytes).unwrap();
+        assert_eq!(msg.msg_type, MessageType::Unknown(0x20));
+        let (_, msg) = parse_message_with(&registry, &bytes).unwrap();
+        assert_eq!(msg.msg_type, MessageType::Vendor(0x20));
+    }
+
     #[test]
     fn test_parse_battery_status() {
         let payload = vec![50, 60, 70];
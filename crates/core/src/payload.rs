@@ -0,0 +1,528 @@
+//! Typed payload codecs for AAP messages
+
+use crate::error::{Error, Result};
+use crate::models::*;
+use crate::protocol::{Message, MessageType};
+use crate::state::BatteryInfo;
+use serde::{Deserialize, Serialize};
+
+/// Marker for an absent optional byte value
+const NONE_BYTE: u8 = 0xFF;
+
+/// Decoded payload, one variant per message type
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Payload {
+    /// Battery levels and charging state
+    BatteryStatus(BatteryInfo),
+    /// Noise control mode
+    AncControl(AncMode),
+    /// Which buds are in ear
+    EarDetection(EarDetectionState),
+    /// Firmware version string
+    FirmwareInfo(String),
+    /// Spatial audio settings
+    SpatialAudio(SpatialAudioConfig),
+    /// Heart rate reading
+    HeartRate(HeartRateMeasurement),
+    /// Find My location report
+    FindMy(FindMyLocation),
+    /// Conversation awareness state
+    ConversationAwareness(ConversationAwarenessState),
+    /// Hearing aid settings
+    HearingAid(HearingAidConfig),
+    /// New device name
+    DeviceRename(String),
+    /// Multipoint session
+    MultipointControl(MultipointInfo),
+    /// Whether adaptive transparency is on
+    AdaptiveTransparency(bool),
+    /// Long press action of each bud
+    LongPressActions {
+        /// Action of the left bud
+        left: LongPressAction,
+        /// Action of the right bud
+        right: LongPressAction,
+    },
+    /// Custom transparency settings
+    CustomTransparency(CustomTransparencyConfig),
+    /// Head gesture settings
+    HeadGestures(HeadGestureConfig),
+    /// Opaque payload of an opcode without a built-in codec
+    Unknown {
+        /// The message's opcode
+        opcode: u8,
+        /// Payload bytes after the header
+        data: Vec<u8>,
+    },
+}
+
+impl Payload {
+    /// Message type this payload is carried by
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            Payload::BatteryStatus(_) => MessageType::BatteryStatus,
+            Payload::AncControl(_) => MessageType::AncControl,
+            Payload::EarDetection(_) => MessageType::EarDetection,
+            Payload::FirmwareInfo(_) => MessageType::FirmwareInfo,
+            Payload::SpatialAudio(_) => MessageType::SpatialAudio,
+            Payload::HeartRate(_) => MessageType::HeartRate,
+            Payload::FindMy(_) => MessageType::FindMy,
+            Payload::ConversationAwareness(_) => MessageType::ConversationAwareness,
+            Payload::HearingAid(_) => MessageType::HearingAid,
+            Payload::DeviceRename(_) => MessageType::DeviceRename,
+            Payload::MultipointControl(_) => MessageType::MultipointControl,
+            Payload::AdaptiveTransparency(_) => MessageType::AdaptiveTransparency,
+            Payload::LongPressActions { .. } => MessageType::LongPressActions,
+            Payload::CustomTransparency(_) => MessageType::CustomTransparency,
+            Payload::HeadGestures(_) => MessageType::HeadGestures,
+            Payload::Unknown { opcode, .. } => MessageType::Unknown(*opcode),
+        }
+    }
+
+    /// Decode raw payload bytes for the given message type
+    pub fn decode(msg_type: MessageType, data: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(data);
+        let payload = match msg_type {
+            MessageType::BatteryStatus => {
+                let left_bud = reader.u8()?;
+                let right_bud = reader.u8()?;
+                let case = reader.u8()?;
+                // Older firmware omits the charging flag byte
+                let is_charging = reader.remaining() > 0 && reader.u8()? != 0;
+                Payload::BatteryStatus(BatteryInfo {
+                    left_bud,
+                    right_bud,
+                    case,
+                    is_charging,
+                })
+            }
+            MessageType::AncControl => Payload::AncControl(AncMode::from_u8(reader.u8()?)?),
+            MessageType::EarDetection => Payload::EarDetection(decode_ear_detection(reader.u8()?)?),
+            MessageType::FirmwareInfo => Payload::FirmwareInfo(reader.utf8_rest()?),
+            MessageType::SpatialAudio => {
+                let flags = reader.u8()?;
+                Payload::SpatialAudio(SpatialAudioConfig {
+                    enabled: flags & 0x01 != 0,
+                    head_tracking: flags & 0x02 != 0,
+                    dynamic_head_tracking: flags & 0x04 != 0,
+                })
+            }
+            MessageType::HeartRate => Payload::HeartRate(HeartRateMeasurement {
+                bpm: reader.u16()?,
+                confidence: reader.u8()?,
+                timestamp: reader.u64()?,
+            }),
+            MessageType::FindMy => Payload::FindMy(FindMyLocation {
+                latitude: f64::from_bits(reader.u64()?),
+                longitude: f64::from_bits(reader.u64()?),
+                accuracy: f32::from_bits(reader.u32()?),
+                timestamp: reader.u64()?,
+            }),
+            MessageType::ConversationAwareness => {
+                Payload::ConversationAwareness(decode_conversation_awareness(reader.u8()?)?)
+            }
+            MessageType::HearingAid => Payload::HearingAid(HearingAidConfig {
+                enabled: reader.bool()?,
+                amplification_level: reader.u8()?,
+                frequency_response: reader.rest().to_vec(),
+            }),
+            MessageType::DeviceRename => Payload::DeviceRename(reader.utf8_rest()?),
+            MessageType::MultipointControl => {
+                let flags = reader.u8()?;
+                let active_device = if flags & 0x02 != 0 {
+                    Some(reader.short_string()?)
+                } else {
+                    None
+                };
+                let count = reader.u8()?;
+                let mut connected_devices = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    connected_devices.push(reader.short_string()?);
+                }
+                Payload::MultipointControl(MultipointInfo {
+                    enabled: flags & 0x01 != 0,
+                    connected_devices,
+                    active_device,
+                })
+            }
+            MessageType::AdaptiveTransparency => Payload::AdaptiveTransparency(reader.bool()?),
+            MessageType::LongPressActions => Payload::LongPressActions {
+                left: decode_long_press_action(reader.u8()?)?,
+                right: decode_long_press_action(reader.u8()?)?,
+            },
+            MessageType::CustomTransparency => {
+                Payload::CustomTransparency(CustomTransparencyConfig {
+                    enabled: reader.bool()?,
+                    ambient_mix_level: reader.u8()?,
+                    voice_focus: reader.bool()?,
+                })
+            }
+            MessageType::HeadGestures => {
+                let double_tap_enabled = reader.bool()?;
+                let action = reader.u8()?;
+                let double_tap_action = if action == NONE_BYTE {
+                    None
+                } else {
+                    Some(decode_long_press_action(action)?)
+                };
+                Payload::HeadGestures(HeadGestureConfig {
+                    double_tap_enabled,
+                    double_tap_action,
+                })
+            }
+            MessageType::Vendor(opcode) | MessageType::Unknown(opcode) => Payload::Unknown {
+                opcode,
+                data: reader.rest().to_vec(),
+            },
+        };
+        reader.finish()?;
+        Ok(payload)
+    }
+
+    /// Encode the payload into raw bytes
+    ///
+    /// Fails with `InvalidLength` if a length-prefixed field does not fit.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        match self {
+            Payload::BatteryStatus(info) => {
+                out.extend_from_slice(&[
+                    info.left_bud,
+                    info.right_bud,
+                    info.case,
+                    info.is_charging as u8,
+                ]);
+            }
+            Payload::AncControl(mode) => out.push(mode.as_u8()),
+            Payload::EarDetection(state) => out.push(*state as u8),
+            Payload::FirmwareInfo(version) => out.extend_from_slice(version.as_bytes()),
+            Payload::SpatialAudio(config) => {
+                let mut flags = 0u8;
+                if config.enabled {
+                    flags |= 0x01;
+                }
+                if config.head_tracking {
+                    flags |= 0x02;
+                }
+                if config.dynamic_head_tracking {
+                    flags |= 0x04;
+                }
+                out.push(flags);
+            }
+            Payload::HeartRate(measurement) => {
+                out.extend_from_slice(&measurement.bpm.to_be_bytes());
+                out.push(measurement.confidence);
+                out.extend_from_slice(&measurement.timestamp.to_be_bytes());
+            }
+            Payload::FindMy(location) => {
+                out.extend_from_slice(&location.latitude.to_bits().to_be_bytes());
+                out.extend_from_slice(&location.longitude.to_bits().to_be_bytes());
+                out.extend_from_slice(&location.accuracy.to_bits().to_be_bytes());
+                out.extend_from_slice(&location.timestamp.to_be_bytes());
+            }
+            Payload::ConversationAwareness(state) => out.push(*state as u8),
+            Payload::HearingAid(config) => {
+                out.push(config.enabled as u8);
+                out.push(config.amplification_level);
+                out.extend_from_slice(&config.frequency_response);
+            }
+            Payload::DeviceRename(name) => out.extend_from_slice(name.as_bytes()),
+            Payload::MultipointControl(info) => {
+                let mut flags = 0u8;
+                if info.enabled {
+                    flags |= 0x01;
+                }
+                if info.active_device.is_some() {
+                    flags |= 0x02;
+                }
+                out.push(flags);
+                if let Some(active) = &info.active_device {
+                    write_short_string(&mut out, active)?;
+                }
+                let count =
+                    u8::try_from(info.connected_devices.len()).map_err(|_| Error::InvalidLength)?;
+                out.push(count);
+                for device in &info.connected_devices {
+                    write_short_string(&mut out, device)?;
+                }
+            }
+            Payload::AdaptiveTransparency(enabled) => out.push(*enabled as u8),
+            Payload::LongPressActions { left, right } => {
+                out.extend_from_slice(&[*left as u8, *right as u8]);
+            }
+            Payload::CustomTransparency(config) => {
+                out.extend_from_slice(&[
+                    config.enabled as u8,
+                    config.ambient_mix_level,
+                    config.voice_focus as u8,
+                ]);
+            }
+            Payload::HeadGestures(config) => {
+                out.push(config.double_tap_enabled as u8);
+                out.push(config.double_tap_action.map_or(NONE_BYTE, |a| a as u8));
+            }
+            Payload::Unknown { data, .. } => out.extend_from_slice(data),
+        }
+        Ok(out)
+    }
+
+    /// Decode the payload carried by a message
+    pub fn from_message(message: &Message) -> Result<Self> {
+        Self::decode(message.msg_type, &message.payload)
+    }
+
+    /// Encode into a complete message with CRC
+    pub fn to_message(&self) -> Result<Message> {
+        Ok(Message::new(self.message_type(), self.encode()?))
+    }
+}
+
+impl Message {
+    /// Decode this message's payload into its typed form
+    pub fn decode_payload(&self) -> Result<Payload> {
+        Payload::from_message(self)
+    }
+}
+
+fn decode_ear_detection(value: u8) -> Result<EarDetectionState> {
+    match value {
+        0 => Ok(EarDetectionState::Unknown),
+        1 => Ok(EarDetectionState::LeftEarIn),
+        2 => Ok(EarDetectionState::RightEarIn),
+        3 => Ok(EarDetectionState::BothEarsIn),
+        4 => Ok(EarDetectionState::BothEarsOut),
+        _ => Err(Error::ParseError(format!(
+            "invalid ear detection state: {}",
+            value
+        ))),
+    }
+}
+
+fn decode_conversation_awareness(value: u8) -> Result<ConversationAwarenessState> {
+    match value {
+        0 => Ok(ConversationAwarenessState::Inactive),
+        1 => Ok(ConversationAwarenessState::Active),
+        2 => Ok(ConversationAwarenessState::Speaking),
+        _ => Err(Error::ParseError(format!(
+            "invalid conversation awareness state: {}",
+            value
+        ))),
+    }
+}
+
+fn decode_long_press_action(value: u8) -> Result<LongPressAction> {
+    match value {
+        0 => Ok(LongPressAction::Siri),
+        1 => Ok(LongPressAction::PlayPause),
+        2 => Ok(LongPressAction::NextTrack),
+        3 => Ok(LongPressAction::PreviousTrack),
+        4 => Ok(LongPressAction::VolumeUp),
+        5 => Ok(LongPressAction::VolumeDown),
+        _ => Err(Error::ParseError(format!(
+            "invalid long press action: {}",
+            value
+        ))),
+    }
+}
+
+fn write_short_string(out: &mut Vec<u8>, value: &str) -> Result<()> {
+    let len = u8::try_from(value.len()).map_err(|_| Error::InvalidLength)?;
+    out.push(len);
+    out.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+/// Bounds-checked big-endian reader over a payload
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Error::InvalidLength);
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.data)
+    }
+
+    fn utf8_rest(&mut self) -> Result<String> {
+        String::from_utf8(self.rest().to_vec())
+            .map_err(|e| Error::ParseError(format!("invalid UTF-8: {}", e)))
+    }
+
+    fn short_string(&mut self) -> Result<String> {
+        let len = self.u8()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|e| Error::ParseError(format!("invalid UTF-8: {}", e)))
+    }
+
+    fn finish(&self) -> Result<()> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidLength)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_payloads() -> Vec<Payload> {
+        vec![
+            Payload::BatteryStatus(BatteryInfo {
+                left_bud: 80,
+                right_bud: 75,
+                case: 100,
+                is_charging: true,
+            }),
+            Payload::AncControl(AncMode::Adaptive),
+            Payload::EarDetection(EarDetectionState::BothEarsIn),
+            Payload::FirmwareInfo("6A300".to_string()),
+            Payload::SpatialAudio(SpatialAudioConfig {
+                enabled: true,
+                head_tracking: false,
+                dynamic_head_tracking: true,
+            }),
+            Payload::HeartRate(HeartRateMeasurement {
+                bpm: 72,
+                confidence: 90,
+                timestamp: 1_700_000_000,
+            }),
+            Payload::FindMy(FindMyLocation {
+                latitude: 52.52,
+                longitude: 13.405,
+                accuracy: 4.5,
+                timestamp: 1_700_000_000,
+            }),
+            Payload::ConversationAwareness(ConversationAwarenessState::Speaking),
+            Payload::HearingAid(HearingAidConfig {
+                enabled: true,
+                amplification_level: 12,
+                frequency_response: vec![1, 2, 3, 4, 5],
+            }),
+            Payload::DeviceRename("Kitchen AirPods".to_string()),
+            Payload::MultipointControl(MultipointInfo {
+                enabled: true,
+                connected_devices: vec!["laptop".to_string(), "phone".to_string()],
+                active_device: Some("phone".to_string()),
+            }),
+            Payload::AdaptiveTransparency(true),
+            Payload::LongPressActions {
+                left: LongPressAction::Siri,
+                right: LongPressAction::NextTrack,
+            },
+            Payload::CustomTransparency(CustomTransparencyConfig {
+                enabled: true,
+                ambient_mix_level: 40,
+                voice_focus: false,
+            }),
+            Payload::HeadGestures(HeadGestureConfig {
+                double_tap_enabled: true,
+                double_tap_action: None,
+            }),
+            Payload::Unknown {
+                opcode: 0x42,
+                data: vec![1, 2, 3],
+            },
+        ]
+    }
+
+    #[test]
+    fn test_round_trip_all_types() {
+        for payload in sample_payloads() {
+            let bytes = payload.encode().unwrap();
+            let decoded = Payload::decode(payload.message_type(), &bytes).unwrap();
+            assert_eq!(decoded, payload);
+        }
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let payload = Payload::AncControl(AncMode::Transparency);
+        let message = payload.to_message().unwrap();
+        assert_eq!(message.msg_type, MessageType::AncControl);
+        let parsed = Message::parse(&message.serialize().unwrap()).unwrap();
+        assert_eq!(parsed.decode_payload().unwrap(), payload);
+    }
+
+    #[test]
+    fn test_battery_without_charging_flag() {
+        let decoded = Payload::decode(MessageType::BatteryStatus, &[50, 60, 70]).unwrap();
+        assert_eq!(
+            decoded,
+            Payload::BatteryStatus(BatteryInfo {
+                left_bud: 50,
+                right_bud: 60,
+                case: 70,
+                is_charging: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_input() {
+        assert_eq!(
+            Payload::decode(MessageType::HeartRate, &[0, 72]),
+            Err(Error::InvalidLength)
+        );
+        assert_eq!(
+            Payload::decode(MessageType::AncControl, &[1, 0]),
+            Err(Error::InvalidLength)
+        );
+        assert!(matches!(
+            Payload::decode(MessageType::AncControl, &[9]),
+            Err(Error::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_encode_rejects_overlong_string() {
+        let payload = Payload::MultipointControl(MultipointInfo {
+            enabled: true,
+            connected_devices: vec!["x".repeat(300)],
+            active_device: None,
+        });
+        assert_eq!(payload.encode(), Err(Error::InvalidLength));
+    }
+}
@@ -1,53 +1,87 @@
 use crate::error::{Error, Result};
+use crate::frame::{crc16, MessageRef, FRAME_CRC_LEN};
+use crate::registry;
 use serde::{Deserialize, Serialize};
 
 pub const AAP_SERVICE_UUID: u128 = 0x7DFC90007D1C495186AA8D9728F8D66C;
 pub const AAP_CHARACTERISTIC_UUID: u128 = 0x7DFC90017D1C495186AA8D9728F8D66C;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[repr(u8)]
+/// AAP message type
+///
+/// Opcodes for the known types live in the protocol registry. Opcodes the
+/// registry does not know are kept as `Unknown` so new firmware messages pass
+/// through instead of failing the stream. Opcodes added to a
+/// [`ProtocolRegistry`](crate::registry::ProtocolRegistry) at runtime are
+/// `Vendor` when looked up through that registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum MessageType {
-    BatteryStatus = 0x01,
-    AncControl = 0x02,
-    EarDetection = 0x03,
-    FirmwareInfo = 0x04,
-    SpatialAudio = 0x05,
-    HeartRate = 0x06,
-    FindMy = 0x07,
-    ConversationAwareness = 0x08,
-    HearingAid = 0x09,
-    DeviceRename = 0x0A,
-    MultipointControl = 0x0B,
-    AdaptiveTransparency = 0x0C,
-    LongPressActions = 0x0D,
-    CustomTransparency = 0x0E,
-    HeadGestures = 0x0F,
+    BatteryStatus,
+    AncControl,
+    EarDetection,
+    FirmwareInfo,
+    SpatialAudio,
+    HeartRate,
+    FindMy,
+    ConversationAwareness,
+    HearingAid,
+    DeviceRename,
+    MultipointControl,
+    AdaptiveTransparency,
+    LongPressActions,
+    CustomTransparency,
+    HeadGestures,
+    /// Opcode registered at runtime, without a built-in payload codec
+    Vendor(u8),
+    /// Opcode that is neither built in nor registered
+    Unknown(u8),
 }
 
 impl MessageType {
+    /// Strict lookup, failing with `UnknownMessageType` for unregistered opcodes
     pub fn from_u8(value: u8) -> Result<Self> {
-        match value {
-            0x01 => Ok(MessageType::BatteryStatus),
-            0x02 => Ok(MessageType::AncControl),
-            0x03 => Ok(MessageType::EarDetection),
-            0x04 => Ok(MessageType::FirmwareInfo),
-            0x05 => Ok(MessageType::SpatialAudio),
-            0x06 => Ok(MessageType::HeartRate),
-            0x07 => Ok(MessageType::FindMy),
-            0x08 => Ok(MessageType::ConversationAwareness),
-            0x09 => Ok(MessageType::HearingAid),
-            0x0A => Ok(MessageType::DeviceRename),
-            0x0B => Ok(MessageType::MultipointControl),
-            0x0C => Ok(MessageType::AdaptiveTransparency),
-            0x0D => Ok(MessageType::LongPressActions),
-            0x0E => Ok(MessageType::CustomTransparency),
-            0x0F => Ok(MessageType::HeadGestures),
-            _ => Err(Error::UnknownMessageType(value)),
+        registry::builtin_message(value)
+            .map(|m| m.msg_type)
+            .ok_or(Error::UnknownMessageType(value))
+    }
+
+    /// Lenient lookup, mapping opcodes that are not built in to `Unknown`
+    ///
+    /// Use [`ProtocolRegistry::message_type`](crate::registry::ProtocolRegistry::message_type)
+    /// to also recognise vendor opcodes.
+    pub fn from_opcode(value: u8) -> Self {
+        Self::from_u8(value).unwrap_or(MessageType::Unknown(value))
+    }
+
+    /// Wire opcode of this message type
+    pub fn opcode(self) -> u8 {
+        match self {
+            MessageType::Vendor(opcode) | MessageType::Unknown(opcode) => opcode,
+            known => registry::builtin_message_for(known)
+                .map(|m| m.opcode)
+                .expect("every known message type is registered"),
+        }
+    }
+
+    /// Whether this opcode is missing from the registry
+    pub fn is_unknown(self) -> bool {
+        matches!(self, MessageType::Unknown(_))
+    }
+
+    /// Canonical name from the protocol registry
+    ///
+    /// Vendor names live in the registry that defines them, so those read
+    /// as `Vendor` here.
+    pub fn name(self) -> &'static str {
+        match self {
+            MessageType::Vendor(_) => "Vendor",
+            known => registry::builtin_message_for(known)
+                .map(|m| m.name)
+                .unwrap_or("Unknown"),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Message {
     pub msg_type: MessageType,
     pub payload: Vec<u8>,
@@ -56,6 +90,9 @@ pub struct Message {
 
 impl Message {
     pub fn new(msg_type: MessageType, payload: Vec<u8>) -> Self {
+        // `Unknown` or `Vendor` carrying a built-in opcode parses back as the
+        // named type
+        let msg_type = MessageType::from_u8(msg_type.opcode()).unwrap_or(msg_type);
         let crc = Self::calculate_crc(&payload);
         Self {
             msg_type,
@@ -65,41 +102,459 @@ impl Message {
     }
 
     pub fn parse(data: &[u8]) -> Result<Self> {
-        if data.len() < 3 {
-            return Err(Error::InvalidLength);
+        Self::parse_versioned(data, FrameVersion::V1)
+    }
+
+    /// Parse a frame encoded with the given frame version
+    pub fn parse_versioned(data: &[u8], version: FrameVersion) -> Result<Self> {
+        Ok(MessageRef::parse_versioned(data, version)?.into())
+    }
+
+    /// Borrow this message for the allocation-free frame path
+    pub fn as_message_ref(&self) -> MessageRef<'_> {
+        MessageRef {
+            opcode: self.msg_type.opcode(),
+            payload: &self.payload,
+            crc: self.crc,
         }
+    }
 
-        let msg_type = MessageType::from_u8(data[0])?;
-        let len = data[1] as usize;
+    /// Serialize using the legacy `V1` frame format
+    ///
+    /// Fails with `InvalidLength` if the payload exceeds 255 bytes.
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        self.serialize_versioned(FrameVersion::V1)
+    }
 
-        if data.len() < 3 + len {
+    /// Serialize using the given frame version
+    ///
+    /// Fails with `InvalidLength` instead of truncating when the payload does
+    /// not fit the version's length field.
+    pub fn serialize_versioned(&self, version: FrameVersion) -> Result<Vec<u8>> {
+        if self.payload.len() > version.max_payload_len() {
             return Err(Error::InvalidLength);
         }
 
-        let payload = data[2..2 + len].to_vec();
-        let crc = u16::from_be_bytes([data[2 + len], data[3 + len]]);
+        let mut result = Vec::with_capacity(self.frame_len(version));
+        result.push(self.msg_type.opcode());
+        match version {
+            FrameVersion::V1 => result.push(self.payload.len() as u8),
+            FrameVersion::V2 => {
+                result.extend_from_slice(&(self.payload.len() as u16).to_be_bytes())
+            }
+        }
+        result.extend_from_slice(&self.payload);
+        result.extend_from_slice(&self.crc.to_be_bytes());
+        Ok(result)
+    }
+
+    /// Encoded size of this message in the given frame version
+    pub fn frame_len(&self, version: FrameVersion) -> usize {
+        version.header_len() + self.payload.len() + FRAME_CRC_LEN
+    }
+
+    pub(crate) fn calculate_crc(data: &[u8]) -> u16 {
+        crc16(data)
+    }
+}
 
-        let calculated_crc = Self::calculate_crc(&payload);
-        if calculated_crc != crc {
-            return Err(Error::CrcMismatch);
+impl From<MessageRef<'_>> for Message {
+    fn from(msg: MessageRef<'_>) -> Self {
+        Self {
+            msg_type: MessageType::from_opcode(msg.opcode),
+            payload: msg.payload.to_vec(),
+            crc: msg.crc,
         }
+    }
+}
 
-        Ok(Self {
-            msg_type,
-            payload,
-            crc,
+pub use crate::frame::FrameVersion;
+
+impl FrameVersion {
+    /// Parse a version byte advertised by the peer
+    pub fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            1 => Ok(FrameVersion::V1),
+            2 => Ok(FrameVersion::V2),
+            _ => Err(Error::VersionMismatch),
+        }
+    }
+
+    /// Pick the newest version offered by both sides
+    ///
+    /// Fails with `VersionMismatch` if the peer shares no version with us.
+    pub fn negotiate(local: &[FrameVersion], remote: &[FrameVersion]) -> Result<Self> {
+        local
+            .iter()
+            .filter(|v| remote.contains(v))
+            .max()
+            .copied()
+            .ok_or(Error::VersionMismatch)
+    }
+}
+
+/// Incremental decoder for AAP frames arriving over GATT notifications
+///
+/// Notifications may split one frame across several packets or pack several
+/// frames into one. Feed every chunk as it arrives and drain the decoded
+/// messages; corrupt frames are reported once and the decoder resyncs by
+/// scanning forward byte by byte until the next frame verifies.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    resyncing: bool,
+    version: FrameVersion,
+    max_payload_len: Option<usize>,
+}
+
+impl FrameDecoder {
+    /// Create an empty decoder for `V1` frames
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create an empty decoder for the given frame version
+    pub fn with_version(version: FrameVersion) -> Self {
+        Self {
+            version,
+            ..Self::default()
+        }
+    }
+
+    /// Frame version this decoder expects
+    pub fn version(&self) -> FrameVersion {
+        self.version
+    }
+
+    /// Switch frame version, e.g. after negotiation completes
+    pub fn set_version(&mut self, version: FrameVersion) {
+        self.version = version;
+    }
+
+    /// Treat headers announcing more than `len` payload bytes as corrupt
+    ///
+    /// Defaults to the most the version's length field can describe.
+    pub fn with_max_payload_len(mut self, len: usize) -> Self {
+        self.max_payload_len = Some(len);
+        self
+    }
+
+    /// Largest payload a header may announce before it is taken as corrupt
+    pub fn max_payload_len(&self) -> usize {
+        self.max_payload_len
+            .unwrap_or(usize::MAX)
+            .min(self.version.max_payload_len())
+    }
+
+    /// Append a chunk of received bytes
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    /// Append a chunk and decode every frame that is now complete
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Result<Message>> {
+        self.feed(chunk);
+        let mut results = Vec::new();
+        while let Some(result) = self.decode_next() {
+            results.push(result);
+        }
+        results
+    }
+
+    /// Decode the next frame from the buffer
+    ///
+    /// Returns `None` when more bytes are needed. A header announcing a
+    /// payload over [`max_payload_len`](Self::max_payload_len) is corrupt and
+    /// fails with `InvalidLength`. While resyncing, a header whose frame has
+    /// not fully arrived is skipped as soon as a later complete frame
+    /// verifies, since it may be a false resync point. While resyncing after a corrupt frame, further failures are skipped
+    /// silently so a single bad packet produces a single error. Frames with
+    /// unknown opcodes decode as `MessageType::Unknown` as long as their CRC
+    /// verifies.
+    pub fn decode_next(&mut self) -> Option<Result<Message>> {
+        loop {
+            if self.buffer.len() < self.version.header_len() {
+                return None;
+            }
+
+            // Unknown opcodes are passed through while in sync, but only a
+            // known opcode is trusted as a resync point
+            if self.resyncing && MessageType::from_opcode(self.buffer[0]).is_unknown() {
+                self.buffer.remove(0);
+                continue;
+            }
+
+            if self.version.payload_len(&self.buffer) > self.max_payload_len() {
+                self.buffer.remove(0);
+                if self.enter_resync() {
+                    return Some(Err(Error::InvalidLength));
+                }
+                continue;
+            }
+
+            let frame_len = self.candidate_len(&self.buffer);
+            if self.buffer.len() < frame_len {
+                // In sync, the rest of the frame is still on its way. A
+                // resync point may instead be a stray byte announcing a length
+                // that never arrives, so prefer a later frame that verifies.
+                if self.resyncing {
+                    if let Some(offset) = self.find_verified_frame() {
+                        self.buffer.drain(..offset);
+                        continue;
+                    }
+                }
+                return None;
+            }
+
+            match Message::parse_versioned(&self.buffer[..frame_len], self.version) {
+                Ok(message) => {
+                    self.buffer.drain(..frame_len);
+                    self.resyncing = false;
+                    return Some(Ok(message));
+                }
+                Err(err) => {
+                    self.buffer.remove(0);
+                    if self.enter_resync() {
+                        return Some(Err(err));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Flush the decoder at the end of a stream
+    ///
+    /// Fails with `InvalidLength` if a partial frame was left in the buffer.
+    pub fn finish(&mut self) -> Result<()> {
+        let truncated = !self.buffer.is_empty() && !self.resyncing;
+        self.reset();
+        if truncated {
+            Err(Error::InvalidLength)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Number of bytes waiting for the rest of their frame
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Discard all buffered bytes
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.resyncing = false;
+    }
+
+    /// Offset of the first complete, CRC-valid frame after the buffer start
+    fn find_verified_frame(&self) -> Option<usize> {
+        (1..self.buffer.len()).find(|&offset| {
+            let candidate = &self.buffer[offset..];
+            if candidate.len() < self.version.header_len()
+                || MessageType::from_opcode(candidate[0]).is_unknown()
+            {
+                return false;
+            }
+            let frame_len = self.candidate_len(candidate);
+            candidate.len() >= frame_len
+                && Message::parse_versioned(&candidate[..frame_len], self.version).is_ok()
         })
     }
 
-    pub fn serialize(&self) -> Vec<u8> {
-        let mut result = vec![self.msg_type as u8, self.payload.len() as u8];
-        result.extend_from_slice(&self.payload);
-        result.extend_from_slice(&self.crc.to_be_bytes());
-        result
+    /// Total frame length announced by a header at the start of `data`
+    fn candidate_len(&self, data: &[u8]) -> usize {
+        self.version.header_len() + self.version.payload_len(data) + FRAME_CRC_LEN
+    }
+
+    /// Enter resync mode, returning `true` if this is the first failure
+    fn enter_resync(&mut self) -> bool {
+        !std::mem::replace(&mut self.resyncing, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(msg_type: MessageType, payload: &[u8]) -> Vec<u8> {
+        Message::new(msg_type, payload.to_vec())
+            .serialize()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_decoder_single_frame() {
+        let mut decoder = FrameDecoder::new();
+        let results = decoder.push(&frame(MessageType::BatteryStatus, &[50, 60, 70]));
+        assert_eq!(results.len(), 1);
+        let msg = results[0].as_ref().unwrap();
+        assert_eq!(msg.msg_type, MessageType::BatteryStatus);
+        assert_eq!(msg.payload, vec![50, 60, 70]);
+        assert_eq!(decoder.buffered_len(), 0);
+    }
+
+    #[test]
+    fn test_decoder_fragmented_frame() {
+        let bytes = frame(MessageType::AncControl, &[1, 2, 3, 4]);
+        let mut decoder = FrameDecoder::new();
+        for byte in &bytes[..bytes.len() - 1] {
+            assert!(decoder.push(&[*byte]).is_empty());
+        }
+        let results = decoder.push(&bytes[bytes.len() - 1..]);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].as_ref().unwrap().payload, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_decoder_coalesced_frames() {
+        let mut bytes = frame(MessageType::BatteryStatus, &[10, 20, 30]);
+        bytes.extend(frame(MessageType::EarDetection, &[1]));
+        bytes.extend(frame(MessageType::FirmwareInfo, &[]));
+
+        let mut decoder = FrameDecoder::new();
+        let types: Vec<MessageType> = decoder
+            .push(&bytes)
+            .into_iter()
+            .map(|r| r.unwrap().msg_type)
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                MessageType::BatteryStatus,
+                MessageType::EarDetection,
+                MessageType::FirmwareInfo
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decoder_resyncs_after_crc_mismatch() {
+        let mut corrupt = frame(MessageType::BatteryStatus, &[10, 20, 30]);
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xFF;
+        let mut bytes = corrupt;
+        bytes.extend(frame(MessageType::AncControl, &[2]));
+
+        let mut decoder = FrameDecoder::new();
+        let results = decoder.push(&bytes);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], Err(Error::CrcMismatch));
+        assert_eq!(
+            results[1].as_ref().unwrap().msg_type,
+            MessageType::AncControl
+        );
+    }
+
+    #[test]
+    fn test_decoder_skips_corrupt_length() {
+        let mut corrupt = frame(MessageType::BatteryStatus, &[10, 20, 30]);
+        corrupt[1] = 0xF0;
+        let mut bytes = corrupt;
+        bytes.extend(frame(MessageType::AncControl, &[2]));
+
+        let mut decoder = FrameDecoder::new().with_max_payload_len(64);
+        let results = decoder.push(&bytes);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], Err(Error::InvalidLength));
+        assert_eq!(
+            results[1].as_ref().unwrap().msg_type,
+            MessageType::AncControl
+        );
+        assert_eq!(decoder.buffered_len(), 0);
+
+        // Back in sync, the next frame decodes normally
+        let results = decoder.push(&frame(MessageType::EarDetection, &[1]));
+        assert_eq!(
+            results[0].as_ref().unwrap().msg_type,
+            MessageType::EarDetection
+        );
     }
 
-    fn calculate_crc(data: &[u8]) -> u16 {
-        let crc = crc::Crc::<u16>::new(&crc::CRC_16_IBM_SDLC);
-        crc.checksum(data)
+    #[test]
+    fn test_decoder_waits_for_split_frame_with_empty_frame_inside() {
+        // `01 00 00 00` inside the payload is an empty BatteryStatus frame
+        // whose CRC verifies
+        let bytes = frame(MessageType::BatteryStatus, &[5, 1, 0, 0, 0, 9]);
+        let mut decoder = FrameDecoder::new();
+        assert!(decoder.push(&bytes[..7]).is_empty());
+        let results = decoder.push(&bytes[7..]);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].as_ref().unwrap().payload, vec![5, 1, 0, 0, 0, 9]);
+        assert_eq!(decoder.buffered_len(), 0);
+    }
+
+    #[test]
+    fn test_decoder_finish_reports_truncated_frame() {
+        let bytes = frame(MessageType::BatteryStatus, &[10, 20, 30]);
+        let mut decoder = FrameDecoder::new();
+        assert!(decoder.push(&bytes[..4]).is_empty());
+        assert_eq!(decoder.finish(), Err(Error::InvalidLength));
+        assert_eq!(decoder.buffered_len(), 0);
+        assert_eq!(decoder.finish(), Ok(()));
+    }
+
+    #[test]
+    fn test_serialize_rejects_oversized_v1_payload() {
+        let msg = Message::new(MessageType::HearingAid, vec![0xAB; 300]);
+        assert_eq!(msg.serialize(), Err(Error::InvalidLength));
+    }
+
+    #[test]
+    fn test_v2_round_trip_large_payload() {
+        let msg = Message::new(MessageType::HearingAid, vec![0xAB; 300]);
+        let bytes = msg.serialize_versioned(FrameVersion::V2).unwrap();
+        assert_eq!(bytes.len(), msg.frame_len(FrameVersion::V2));
+        assert_eq!(&bytes[1..3], &300u16.to_be_bytes());
+
+        let parsed = Message::parse_versioned(&bytes, FrameVersion::V2).unwrap();
+        assert_eq!(parsed, msg);
+
+        let mut decoder = FrameDecoder::with_version(FrameVersion::V2);
+        let (head, tail) = bytes.split_at(100);
+        assert!(decoder.push(head).is_empty());
+        let results = decoder.push(tail);
+        assert_eq!(results, vec![Ok(msg)]);
+    }
+
+    #[test]
+    fn test_frame_version_negotiation() {
+        assert_eq!(
+            FrameVersion::negotiate(&FrameVersion::SUPPORTED, &[FrameVersion::V1]),
+            Ok(FrameVersion::V1)
+        );
+        assert_eq!(
+            FrameVersion::negotiate(&FrameVersion::SUPPORTED, &FrameVersion::SUPPORTED),
+            Ok(FrameVersion::V2)
+        );
+        assert_eq!(
+            FrameVersion::negotiate(&[FrameVersion::V2], &[FrameVersion::V1]),
+            Err(Error::VersionMismatch)
+        );
+        assert_eq!(FrameVersion::from_u8(7), Err(Error::VersionMismatch));
+    }
+
+    #[test]
+    fn test_unknown_opcode_is_kept() {
+        let msg = Message::new(MessageType::Unknown(0x42), vec![9, 8, 7]);
+        let bytes = msg.serialize().unwrap();
+        assert_eq!(bytes[0], 0x42);
+
+        let parsed = Message::parse(&bytes).unwrap();
+        assert_eq!(parsed.msg_type, MessageType::Unknown(0x42));
+        assert_eq!(parsed.payload, vec![9, 8, 7]);
+        assert_eq!(
+            MessageType::from_u8(0x42),
+            Err(Error::UnknownMessageType(0x42))
+        );
+
+        let mut stream = bytes;
+        stream.extend(frame(MessageType::BatteryStatus, &[1, 2, 3]));
+        let results = FrameDecoder::new().push(&stream);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], Ok(parsed));
+        assert_eq!(
+            results[1].as_ref().unwrap().msg_type,
+            MessageType::BatteryStatus
+        );
     }
 }
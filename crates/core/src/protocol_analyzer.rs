@@ -1,3 +1,4 @@
+use crate::registry::ProtocolRegistry;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
@@ -5,62 +6,53 @@ pub struct ProtocolAnalyzer {
     known_message_types: HashMap<u8, String>,
     known_uuids: HashMap<String, String>,
     known_features: HashMap<String, bool>,
+    observed_message_types: HashMap<u8, u64>,
 }
 
 impl ProtocolAnalyzer {
     pub fn new() -> Self {
-        let mut analyzer = Self {
-            known_message_types: HashMap::new(),
-            known_uuids: HashMap::new(),
-            known_features: HashMap::new(),
-        };
-        analyzer.initialize_known_types();
-        analyzer
-    }
-
-    fn initialize_known_types(&mut self) {
-        self.known_message_types.insert(0x01, "BatteryStatus".to_string());
-        self.known_message_types.insert(0x02, "AncControl".to_string());
-        self.known_message_types.insert(0x03, "EarDetection".to_string());
-        self.known_message_types.insert(0x04, "FirmwareInfo".to_string());
-        self.known_message_types.insert(0x05, "SpatialAudio".to_string());
-        self.known_message_types.insert(0x06, "HeartRate".to_string());
-        self.known_message_types.insert(0x07, "FindMy".to_string());
-        self.known_message_types.insert(0x08, "ConversationAwareness".to_string());
-        self.known_message_types.insert(0x09, "HearingAid".to_string());
-        self.known_message_types.insert(0x0A, "DeviceRename".to_string());
-        self.known_message_types.insert(0x0B, "MultipointControl".to_string());
-        self.known_message_types.insert(0x0C, "AdaptiveTransparency".to_string());
-        self.known_message_types.insert(0x0D, "LongPressActions".to_string());
-        self.known_message_types.insert(0x0E, "CustomTransparency".to_string());
-        self.known_message_types.insert(0x0F, "HeadGestures".to_string());
-
-        self.known_uuids.insert(
-            "7DFC9000-7D1C-4951-86AA-8D9728F8D66C".to_string(),
-            "AAP_SERVICE".to_string(),
-        );
-        self.known_uuids.insert(
-            "7DFC9001-7D1C-4951-86AA-8D9728F8D66C".to_string(),
-            "AAP_CHARACTERISTIC".to_string(),
-        );
-        self.known_uuids.insert("180F".to_string(), "BATTERY_SERVICE".to_string());
-        self.known_uuids.insert("180A".to_string(), "DEVICE_INFO_SERVICE".to_string());
-
-        self.known_features.insert("BatteryMonitoring".to_string(), true);
-        self.known_features.insert("NoiseControl".to_string(), true);
-        self.known_features.insert("AdaptiveTransparency".to_string(), true);
-        self.known_features.insert("EarDetection".to_string(), true);
-        self.known_features.insert("ConversationAwareness".to_string(), true);
-        self.known_features.insert("HeadGestures".to_string(), true);
-        self.known_features.insert("HearingAid".to_string(), true);
-        self.known_features.insert("CustomTransparency".to_string(), true);
-        self.known_features.insert("DeviceRename".to_string(), true);
-        self.known_features.insert("LongPressActions".to_string(), true);
-        self.known_features.insert("Multipoint".to_string(), true);
-        self.known_features.insert("FirmwareInfo".to_string(), true);
-        self.known_features.insert("FindMy".to_string(), true);
-        self.known_features.insert("HeartRate".to_string(), true);
-        self.known_features.insert("SpatialAudio".to_string(), true);
+        Self::with_registry(&ProtocolRegistry::builtin())
+    }
+
+    /// Build an analyzer from a registry, including any vendor opcodes
+    pub fn with_registry(registry: &ProtocolRegistry) -> Self {
+        Self {
+            known_message_types: registry.message_names(),
+            known_uuids: registry.uuid_names(),
+            known_features: registry
+                .feature_names()
+                .into_iter()
+                .map(|feature| (feature, true))
+                .collect(),
+            observed_message_types: HashMap::new(),
+        }
+    }
+
+    /// Record an opcode seen on the wire for drift statistics
+    pub fn record_message_type(&mut self, msg_type: u8) {
+        *self.observed_message_types.entry(msg_type).or_insert(0) += 1;
+    }
+
+    /// How often each opcode has been seen on the wire
+    pub fn observed_message_types(&self) -> &HashMap<u8, u64> {
+        &self.observed_message_types
+    }
+
+    /// Observed opcodes that are not in the known tables, with counts
+    pub fn observed_unknown_types(&self) -> Vec<(u8, u64)> {
+        let mut unknown: Vec<(u8, u64)> = self
+            .observed_message_types
+            .iter()
+            .filter(|(msg_type, _)| !self.is_known_message_type(**msg_type))
+            .map(|(msg_type, count)| (*msg_type, *count))
+            .collect();
+        unknown.sort_unstable();
+        unknown
+    }
+
+    /// Whether any unknown opcode has been seen on the wire
+    pub fn has_observed_drift(&self) -> bool {
+        !self.observed_unknown_types().is_empty()
     }
 
     pub fn analyze_message_types(&self, incoming_types: Vec<u8>) -> (Vec<u8>, Vec<u8>) {
@@ -153,7 +145,11 @@ impl ProtocolAnalyzer {
         !unknown_types.is_empty() || !unknown_uuids.is_empty()
     }
 
-    pub fn generate_analysis_report(&self, incoming_types: Vec<u8>, incoming_uuids: Vec<String>) -> String {
+    pub fn generate_analysis_report(
+        &self,
+        incoming_types: Vec<u8>,
+        incoming_uuids: Vec<String>,
+    ) -> String {
         let (known_types, unknown_types) = self.analyze_message_types(incoming_types);
         let (known_uuids, unknown_uuids) = self.analyze_uuids(incoming_uuids);
 
@@ -184,7 +180,10 @@ impl ProtocolAnalyzer {
             report.push_str("\n");
         }
 
-        report.push_str(&format!("Protocol Drift: {}\n", self.detect_drift(known_types.clone(), known_uuids)));
+        report.push_str(&format!(
+            "Protocol Drift: {}\n",
+            self.detect_drift(known_types.clone(), known_uuids)
+        ));
 
         report
     }
@@ -240,6 +239,19 @@ mod tests {
         assert!(!analyzer.is_known_message_type(0xFF));
     }
 
+    #[test]
+    fn test_observed_unknown_types() {
+        let mut analyzer = ProtocolAnalyzer::new();
+        analyzer.record_message_type(0x01);
+        assert!(!analyzer.has_observed_drift());
+
+        analyzer.record_message_type(0x42);
+        analyzer.record_message_type(0x42);
+        assert!(analyzer.has_observed_drift());
+        assert_eq!(analyzer.observed_unknown_types(), vec![(0x42, 2)]);
+        assert_eq!(analyzer.observed_message_types().len(), 2);
+    }
+
     #[test]
     fn test_detect_drift() {
         let analyzer = ProtocolAnalyzer::new();
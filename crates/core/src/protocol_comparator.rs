@@ -1,4 +1,7 @@
-use crate::protocol_drift::{DriftStatus, FeatureDrift, MessageTypeDrift, ProtocolDriftReport, UUIDDrift};
+use crate::protocol_drift::{
+    DriftStatus, FeatureDrift, MessageTypeDrift, ProtocolDriftReport, UUIDDrift,
+};
+use crate::registry::ProtocolRegistry;
 use std::collections::{HashMap, HashSet};
 
 pub struct ProtocolComparator {
@@ -9,58 +12,16 @@ pub struct ProtocolComparator {
 
 impl ProtocolComparator {
     pub fn new() -> Self {
-        let mut comparator = Self {
-            base_message_types: HashMap::new(),
-            base_uuids: HashMap::new(),
-            base_features: HashSet::new(),
-        };
-        comparator.initialize_base_protocol();
-        comparator
+        Self::with_registry(&ProtocolRegistry::builtin())
     }
 
-    fn initialize_base_protocol(&mut self) {
-        self.base_message_types.insert(0x01, "BatteryStatus".to_string());
-        self.base_message_types.insert(0x02, "AncControl".to_string());
-        self.base_message_types.insert(0x03, "EarDetection".to_string());
-        self.base_message_types.insert(0x04, "FirmwareInfo".to_string());
-        self.base_message_types.insert(0x05, "SpatialAudio".to_string());
-        self.base_message_types.insert(0x06, "HeartRate".to_string());
-        self.base_message_types.insert(0x07, "FindMy".to_string());
-        self.base_message_types.insert(0x08, "ConversationAwareness".to_string());
-        self.base_message_types.insert(0x09, "HearingAid".to_string());
-        self.base_message_types.insert(0x0A, "DeviceRename".to_string());
-        self.base_message_types.insert(0x0B, "MultipointControl".to_string());
-        self.base_message_types.insert(0x0C, "AdaptiveTransparency".to_string());
-        self.base_message_types.insert(0x0D, "LongPressActions".to_string());
-        self.base_message_types.insert(0x0E, "CustomTransparency".to_string());
-        self.base_message_types.insert(0x0F, "HeadGestures".to_string());
-
-        self.base_uuids.insert(
-            "7DFC9000-7D1C-4951-86AA-8D9728F8D66C".to_string(),
-            "AAP_SERVICE".to_string(),
-        );
-        self.base_uuids.insert(
-            "7DFC9001-7D1C-4951-86AA-8D9728F8D66C".to_string(),
-            "AAP_CHARACTERISTIC".to_string(),
-        );
-        self.base_uuids.insert("180F".to_string(), "BATTERY_SERVICE".to_string());
-        self.base_uuids.insert("180A".to_string(), "DEVICE_INFO_SERVICE".to_string());
-
-        self.base_features.insert("BatteryMonitoring".to_string());
-        self.base_features.insert("NoiseControl".to_string());
-        self.base_features.insert("AdaptiveTransparency".to_string());
-        self.base_features.insert("EarDetection".to_string());
-        self.base_features.insert("ConversationAwareness".to_string());
-        self.base_features.insert("HeadGestures".to_string());
-        self.base_features.insert("HearingAid".to_string());
-        self.base_features.insert("CustomTransparency".to_string());
-        self.base_features.insert("DeviceRename".to_string());
-        self.base_features.insert("LongPressActions".to_string());
-        self.base_features.insert("Multipoint".to_string());
-        self.base_features.insert("FirmwareInfo".to_string());
-        self.base_features.insert("FindMy".to_string());
-        self.base_features.insert("HeartRate".to_string());
-        self.base_features.insert("SpatialAudio".to_string());
+    /// Use a registry, including any vendor opcodes, as the comparison base
+    pub fn with_registry(registry: &ProtocolRegistry) -> Self {
+        Self {
+            base_message_types: registry.message_names(),
+            base_uuids: registry.uuid_names(),
+            base_features: registry.feature_names(),
+        }
     }
 
     pub fn compare_message_types(
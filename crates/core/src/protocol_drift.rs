@@ -152,9 +152,15 @@ impl ProtocolDriftReport {
         report.push_str(&format!("Has Drift: {}\n", self.has_drift));
         report.push_str(&format!("Drift Severity: {:?}\n\n", self.drift_severity));
 
-        report.push_str(&format!("Message Type Drifts: {}\n", self.message_type_drifts.len()));
+        report.push_str(&format!(
+            "Message Type Drifts: {}\n",
+            self.message_type_drifts.len()
+        ));
         for drift in &self.message_type_drifts {
-            report.push_str(&format!("  0x{:02X} {} ({:?})\n", drift.opcode, drift.name, drift.status));
+            report.push_str(&format!(
+                "  0x{:02X} {} ({:?})\n",
+                drift.opcode, drift.name, drift.status
+            ));
         }
 
         report.push_str(&format!("\nUUID Drifts: {}\n", self.uuid_drifts.len()));
@@ -162,12 +168,18 @@ impl ProtocolDriftReport {
             report.push_str(&format!("  {} ({:?})\n", drift.name, drift.status));
         }
 
-        report.push_str(&format!("\nFeature Drifts: {}\n", self.feature_drifts.len()));
+        report.push_str(&format!(
+            "\nFeature Drifts: {}\n",
+            self.feature_drifts.len()
+        ));
         for drift in &self.feature_drifts {
             report.push_str(&format!("  {} ({:?})\n", drift.feature_name, drift.status));
         }
 
-        report.push_str(&format!("\nRecommendations: {}\n", self.recommendations.len()));
+        report.push_str(&format!(
+            "\nRecommendations: {}\n",
+            self.recommendations.len()
+        ));
         for rec in &self.recommendations {
             report.push_str(&format!("  - {}\n", rec));
         }
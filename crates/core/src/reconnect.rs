@@ -0,0 +1,575 @@
+//! Automatic reconnection with exponential backoff
+
+use crate::bluetooth::{AsyncBluetoothBackend, BackendFuture};
+use crate::error::{Error, Result};
+use crate::events::{
+    Event, EventBus, EventFilter, EventKind, EventType, RecvError, Subscription, TryRecvError,
+};
+use crate::models::EarDetectionState;
+use crate::state::DeviceState;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// When and how often to retry a dropped connection
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconnectPolicy {
+    /// Delay before the first attempt
+    pub initial_delay: Duration,
+    /// Upper bound for the delay between attempts, before jitter
+    pub max_delay: Duration,
+    /// Factor applied to the delay after each failed attempt
+    pub multiplier: f64,
+    /// Random spread applied to each delay, as a fraction of it (0.0 - 1.0)
+    pub jitter: f64,
+    /// Give up after this many attempts; `None` retries forever
+    pub max_attempts: Option<u32>,
+    /// Only attempt while the last known ear detection state has a bud in ear
+    pub require_in_ear: bool,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: 0.2,
+            max_attempts: Some(10),
+            require_in_ear: false,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before `attempt` (starting at 1) without jitter
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let delay = self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent);
+        Duration::from_secs_f64(delay.min(self.max_delay.as_secs_f64()))
+    }
+
+    /// Spread `delay` by up to `jitter` either way; `unit` is uniform in [0, 1)
+    fn apply_jitter(&self, delay: Duration, unit: f64) -> Duration {
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        delay.mul_f64(1.0 + jitter * (2.0 * unit - 1.0))
+    }
+
+    fn exhausted(&self, attempts: u32) -> bool {
+        self.max_attempts.is_some_and(|max| attempts >= max)
+    }
+}
+
+/// Progress of a reconnect, published as `EventType::Reconnect`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReconnectStatus {
+    /// Waiting for a bud to be put in ear before trying
+    WaitingForEar,
+    /// The next attempt starts after a delay
+    Scheduled {
+        /// Attempt number, starting at 1
+        attempt: u32,
+        /// Delay before the attempt, jitter included
+        delay_ms: u64,
+    },
+    /// An attempt is connecting
+    Attempting {
+        /// Attempt number, starting at 1
+        attempt: u32,
+    },
+    /// An attempt failed
+    Failed {
+        /// Attempt number, starting at 1
+        attempt: u32,
+        /// Why the connect failed
+        error: String,
+    },
+    /// The device is back
+    Connected {
+        /// Attempt that succeeded
+        attempt: u32,
+    },
+    /// The policy ran out of attempts
+    GaveUp {
+        /// Attempts made
+        attempts: u32,
+    },
+}
+
+/// Connects the supervised device once per call
+type Connector = Box<dyn Fn() -> BackendFuture<'static, ()> + Send + Sync>;
+
+/// Brings a dropped device back by retrying a connect
+///
+/// The connect is usually [`Engine::connect`](crate::Engine::connect), so a
+/// reconnected device is handled like any other; see
+/// [`Engine::reconnect_supervisor`](crate::Engine::reconnect_supervisor).
+/// Progress is reported on the event bus. The last ear detection state seen
+/// on the bus for this device gates attempts when the policy requires it.
+/// All waiting uses tokio timers, so tests can drive it on a paused clock.
+pub struct ReconnectSupervisor {
+    connect: Connector,
+    device_id: String,
+    policy: ReconnectPolicy,
+    event_bus: EventBus,
+    ear_events: Subscription,
+    ear_state: EarDetectionState,
+    rng: u64,
+}
+
+impl ReconnectSupervisor {
+    /// Create a supervisor calling `backend.connect` for the device at
+    /// `device_id` (its address)
+    ///
+    /// Nothing beyond the link is restored; use
+    /// [`ReconnectSupervisor::with_connector`] to reconnect some other way.
+    pub fn new<B>(backend: Arc<B>, device_id: &str, event_bus: EventBus) -> Self
+    where
+        B: AsyncBluetoothBackend + ?Sized + 'static,
+    {
+        let address = device_id.to_string();
+        Self::with_connector(device_id, event_bus, move || {
+            let backend = Arc::clone(&backend);
+            let address = address.clone();
+            Box::pin(async move { backend.connect(&address).await })
+        })
+    }
+
+    /// Create a supervisor for `device_id` that reconnects by calling `connect`
+    pub fn with_connector<F>(device_id: &str, event_bus: EventBus, connect: F) -> Self
+    where
+        F: Fn() -> BackendFuture<'static, ()> + Send + Sync + 'static,
+    {
+        let ear_events = event_bus.subscribe_filtered(
+            EventFilter::new()
+                .device(device_id)
+                .kind(EventKind::EarDetection),
+        );
+        let seed = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64;
+        Self {
+            connect: Box::new(connect),
+            device_id: device_id.to_string(),
+            policy: ReconnectPolicy::default(),
+            event_bus,
+            ear_events,
+            ear_state: EarDetectionState::Unknown,
+            rng: seed | 1,
+        }
+    }
+
+    /// Retry according to `policy` instead of the default one
+    pub fn with_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Start from a known ear detection state instead of `Unknown`
+    ///
+    /// No ear events arrive while the link is down, so a supervisor built
+    /// without one waits for the device to report again.
+    pub fn with_ear_state(mut self, state: EarDetectionState) -> Self {
+        self.ear_state = state;
+        self
+    }
+
+    /// Seed the jitter generator, for reproducible delays
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = seed | 1;
+        self
+    }
+
+    /// Policy the retries follow
+    pub fn policy(&self) -> &ReconnectPolicy {
+        &self.policy
+    }
+
+    /// Last ear detection state seen for this device
+    pub fn ear_state(&self) -> EarDetectionState {
+        self.ear_state
+    }
+
+    /// Retry until connected, returning the number of attempts made
+    ///
+    /// Fails with the last connect error once `max_attempts` is reached.
+    pub async fn reconnect(&mut self) -> Result<u32> {
+        let mut attempt = 0;
+        loop {
+            self.drain_ear_events();
+            if self.policy.require_in_ear && !self.ear_state.is_in_ear() {
+                self.report(ReconnectStatus::WaitingForEar);
+                self.wait_for_ear().await?;
+            }
+
+            attempt += 1;
+            let unit = self.next_unit();
+            let delay = self
+                .policy
+                .apply_jitter(self.policy.delay_for(attempt), unit);
+            self.report(ReconnectStatus::Scheduled {
+                attempt,
+                delay_ms: delay.as_millis() as u64,
+            });
+            tokio::time::sleep(delay).await;
+
+            self.drain_ear_events();
+            if self.policy.require_in_ear && !self.ear_state.is_in_ear() {
+                // Taken out while waiting; this attempt does not count
+                attempt -= 1;
+                continue;
+            }
+
+            self.report(ReconnectStatus::Attempting { attempt });
+            let result = (self.connect)().await;
+
+            match result {
+                Ok(()) => {
+                    self.report(ReconnectStatus::Connected { attempt });
+                    return Ok(attempt);
+                }
+                Err(err) => {
+                    log::debug!(
+                        "reconnect attempt {} to {} failed: {}",
+                        attempt,
+                        self.device_id,
+                        err
+                    );
+                    self.report(ReconnectStatus::Failed {
+                        attempt,
+                        error: err.to_string(),
+                    });
+                    if self.policy.exhausted(attempt) {
+                        self.report(ReconnectStatus::GaveUp { attempts: attempt });
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Spawn a task that reconnects every time this device drops
+    ///
+    /// A drop is a `StateChanged` event from `Connected` to `Disconnected` or
+    /// `Error`, as the engine publishes when a link is lost. The task runs until it is aborted or a reconnect gives up.
+    pub fn supervise(mut self) -> JoinHandle<()> {
+        let mut state_events = self.event_bus.subscribe_filtered(
+            EventFilter::new()
+                .device(&self.device_id)
+                .kind(EventKind::StateChanged),
+        );
+        tokio::spawn(async move {
+            loop {
+                match state_events.recv().await {
+                    Ok(Event {
+                        event_type:
+                            EventType::StateChanged {
+                                from: DeviceState::Connected,
+                                to: DeviceState::Disconnected | DeviceState::Error,
+                            },
+                        ..
+                    }) => {
+                        if self.reconnect().await.is_err() {
+                            return;
+                        }
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        })
+    }
+
+    async fn wait_for_ear(&mut self) -> Result<()> {
+        while !self.ear_state.is_in_ear() {
+            match self.ear_events.recv().await {
+                Ok(event) => self.observe(&event),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return Err(Error::Cancelled),
+            }
+        }
+        Ok(())
+    }
+
+    fn drain_ear_events(&mut self) {
+        loop {
+            match self.ear_events.try_recv() {
+                Ok(event) => self.observe(&event),
+                Err(TryRecvError::Lagged(_)) => continue,
+                Err(_) => return,
+            }
+        }
+    }
+
+    fn observe(&mut self, event: &Event) {
+        if let EventType::EarDetection(state) = event.event_type {
+            self.ear_state = state;
+        }
+    }
+
+    fn report(&self, status: ReconnectStatus) {
+        self.event_bus
+            .emit(Event::new(EventType::Reconnect(status), &self.device_id));
+    }
+
+    /// Uniform sample in [0, 1) from a xorshift generator
+    fn next_unit(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluetooth::{BackendFuture, NotificationStream, ScanStream};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tokio::time::Instant;
+
+    /// Fails the first `failures_left` connects and counts every attempt
+    #[derive(Default)]
+    struct FlakyBackend {
+        failures_left: AtomicU32,
+        connects: AtomicU32,
+    }
+
+    impl FlakyBackend {
+        fn connects(&self) -> u32 {
+            self.connects.load(Ordering::SeqCst)
+        }
+    }
+
+    impl AsyncBluetoothBackend for FlakyBackend {
+        fn scan(&self) -> BackendFuture<'_, ScanStream> {
+            Box::pin(async { Ok(Box::pin(futures_util::stream::empty()) as ScanStream) })
+        }
+
+        fn stop_scan(&self) -> BackendFuture<'_, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn connect<'a>(&'a self, _address: &'a str) -> BackendFuture<'a, ()> {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            let failed = self
+                .failures_left
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            Box::pin(async move {
+                if failed {
+                    return Err(Error::BluetoothError("page timeout".to_string()));
+                }
+                Ok(())
+            })
+        }
+
+        fn disconnect<'a>(&'a self, _address: &'a str) -> BackendFuture<'a, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn write_characteristic<'a>(
+            &'a self,
+            _address: &'a str,
+            _service_uuid: u128,
+            _char_uuid: u128,
+            _data: &'a [u8],
+        ) -> BackendFuture<'a, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn read_characteristic<'a>(
+            &'a self,
+            _address: &'a str,
+            _service_uuid: u128,
+            _char_uuid: u128,
+        ) -> BackendFuture<'a, Vec<u8>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn notifications<'a>(
+            &'a self,
+            _address: &'a str,
+            _service_uuid: u128,
+            _char_uuid: u128,
+        ) -> BackendFuture<'a, NotificationStream> {
+            Box::pin(async { Ok(Box::pin(futures_util::stream::pending()) as NotificationStream) })
+        }
+    }
+
+    fn no_jitter() -> ReconnectPolicy {
+        ReconnectPolicy {
+            jitter: 0.0,
+            ..ReconnectPolicy::default()
+        }
+    }
+
+    fn backend(failures: u32) -> Arc<FlakyBackend> {
+        Arc::new(FlakyBackend {
+            failures_left: AtomicU32::new(failures),
+            connects: AtomicU32::new(0),
+        })
+    }
+
+    fn statuses(sub: &mut Subscription) -> Vec<ReconnectStatus> {
+        let mut out = Vec::new();
+        while let Ok(event) = sub.try_recv() {
+            if let EventType::Reconnect(status) = event.event_type {
+                out.push(status);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = ReconnectPolicy {
+            max_delay: Duration::from_secs(10),
+            ..no_jitter()
+        };
+        assert_eq!(policy.delay_for(1), Duration::from_secs(1));
+        assert_eq!(policy.delay_for(3), Duration::from_secs(4));
+        assert_eq!(policy.delay_for(5), Duration::from_secs(10));
+        assert_eq!(policy.delay_for(u32::MAX), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let policy = ReconnectPolicy::default();
+        let base = Duration::from_secs(10);
+        assert_eq!(policy.apply_jitter(base, 0.0), Duration::from_secs(8));
+        assert_eq!(policy.apply_jitter(base, 0.5), base);
+
+        let mut supervisor =
+            ReconnectSupervisor::new(backend(0), "dev", EventBus::new()).with_seed(42);
+        for _ in 0..1000 {
+            let unit = supervisor.next_unit();
+            assert!((0.0..1.0).contains(&unit));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_reconnects_with_exponential_backoff() {
+        let bus = EventBus::new();
+        let mut events = bus.subscribe();
+        let backend = backend(2);
+        let mut supervisor =
+            ReconnectSupervisor::new(Arc::clone(&backend), "dev", bus).with_policy(no_jitter());
+
+        let start = Instant::now();
+        assert_eq!(supervisor.reconnect().await, Ok(3));
+        assert_eq!(start.elapsed(), Duration::from_secs(1 + 2 + 4));
+        assert_eq!(backend.connects(), 3);
+
+        let statuses = statuses(&mut events);
+        assert_eq!(
+            statuses.first(),
+            Some(&ReconnectStatus::Scheduled {
+                attempt: 1,
+                delay_ms: 1000
+            })
+        );
+        assert_eq!(
+            statuses.last(),
+            Some(&ReconnectStatus::Connected { attempt: 3 })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gives_up_after_max_attempts() {
+        let bus = EventBus::new();
+        let mut events = bus.subscribe();
+        let policy = ReconnectPolicy {
+            max_attempts: Some(3),
+            ..no_jitter()
+        };
+        let mut supervisor = ReconnectSupervisor::new(backend(10), "dev", bus).with_policy(policy);
+
+        assert!(matches!(
+            supervisor.reconnect().await,
+            Err(Error::BluetoothError(_))
+        ));
+        assert_eq!(
+            statuses(&mut events).last(),
+            Some(&ReconnectStatus::GaveUp { attempts: 3 })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_waits_until_in_ear() {
+        let bus = EventBus::new();
+        let backend = backend(0);
+        let policy = ReconnectPolicy {
+            require_in_ear: true,
+            ..no_jitter()
+        };
+        let mut supervisor =
+            ReconnectSupervisor::new(Arc::clone(&backend), "dev", bus.clone()).with_policy(policy);
+        bus.emit(Event::new(
+            EventType::EarDetection(EarDetectionState::BothEarsOut),
+            "dev",
+        ));
+
+        let task = tokio::spawn(async move { supervisor.reconnect().await });
+        tokio::time::sleep(Duration::from_secs(300)).await;
+        assert_eq!(backend.connects(), 0);
+
+        // Another device's ear state does not count
+        bus.emit(Event::new(
+            EventType::EarDetection(EarDetectionState::BothEarsIn),
+            "other",
+        ));
+        tokio::time::sleep(Duration::from_secs(300)).await;
+        assert_eq!(backend.connects(), 0);
+
+        bus.emit(Event::new(
+            EventType::EarDetection(EarDetectionState::LeftEarIn),
+            "dev",
+        ));
+        assert_eq!(task.await.unwrap(), Ok(1));
+        assert_eq!(backend.connects(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_ear_state_survives_lagging() {
+        let bus = EventBus::with_capacity(2);
+        let mut supervisor = ReconnectSupervisor::new(backend(0), "dev", bus.clone());
+        for state in [
+            EarDetectionState::BothEarsOut,
+            EarDetectionState::RightEarIn,
+            EarDetectionState::BothEarsOut,
+            EarDetectionState::LeftEarIn,
+        ] {
+            bus.emit(Event::new(EventType::EarDetection(state), "dev"));
+        }
+
+        supervisor.drain_ear_events();
+        assert_eq!(supervisor.ear_state(), EarDetectionState::LeftEarIn);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_supervise_reconnects_after_drop() {
+        let bus = EventBus::new();
+        let backend = backend(1);
+        let handle = ReconnectSupervisor::new(Arc::clone(&backend), "dev", bus.clone())
+            .with_policy(no_jitter())
+            .supervise();
+        let mut events = bus.subscribe_filtered(EventFilter::new().kind(EventKind::Reconnect));
+
+        bus.emit(Event::new(
+            EventType::StateChanged {
+                from: DeviceState::Connected,
+                to: DeviceState::Disconnected,
+            },
+            "dev",
+        ));
+
+        loop {
+            let event = events.recv().await.unwrap();
+            if event.event_type == EventType::Reconnect(ReconnectStatus::Connected { attempt: 2 }) {
+                break;
+            }
+        }
+        assert_eq!(backend.connects(), 2);
+        handle.abort();
+    }
+}
@@ -0,0 +1,576 @@
+//! Declarative AAP protocol registry
+//!
+//! Single source of truth for opcodes, names, UUIDs, payload layouts and the
+//! models that support each message. The protocol parser, analyzer and
+//! comparator all derive their tables from here. Vendor opcodes can be added
+//! at runtime from a JSON file.
+
+use crate::device::{DeviceCapability, DeviceModel};
+use crate::error::{Error, Result};
+use crate::protocol::{MessageType, AAP_CHARACTERISTIC_UUID, AAP_SERVICE_UUID};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+/// Wire encoding of a single payload field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    /// Unsigned byte
+    U8,
+    /// Big-endian `u16`
+    U16,
+    /// Big-endian `u32`
+    U32,
+    /// Big-endian `u64`
+    U64,
+    /// Big-endian `f32`
+    F32,
+    /// Big-endian `f64`
+    F64,
+    /// One byte, zero for false
+    Bool,
+    /// Bit field packed into one byte
+    Flags,
+    /// One-byte enumeration value
+    Enum,
+    /// UTF-8 text running to the end of the payload
+    Utf8,
+    /// Raw bytes running to the end of the payload
+    Bytes,
+}
+
+/// Compile-time description of a built-in message type
+pub struct BuiltinMessage {
+    /// Opcode byte on the wire
+    pub opcode: u8,
+    /// Message type the opcode decodes to
+    pub msg_type: MessageType,
+    /// Display name
+    pub name: &'static str,
+    /// Capability a device needs to use the message
+    pub capability: DeviceCapability,
+    /// Payload fields, in wire order
+    pub fields: &'static [(&'static str, FieldKind)],
+    /// Models that support the message
+    pub models: &'static [DeviceModel],
+}
+
+use DeviceModel::*;
+use FieldKind::*;
+
+const ALL_MODELS: &[DeviceModel] = &[
+    AirPods2,
+    AirPods3,
+    AirPods4,
+    AirPodsProGen1,
+    AirPodsProGen2,
+    AirPodsProGen3,
+    AirPodsMax,
+    BeatsFitPro,
+];
+const ANC_MODELS: &[DeviceModel] = &[
+    AirPods4,
+    AirPodsProGen1,
+    AirPodsProGen2,
+    AirPodsProGen3,
+    AirPodsMax,
+    BeatsFitPro,
+];
+const SPATIAL_MODELS: &[DeviceModel] = &[
+    AirPods3,
+    AirPods4,
+    AirPodsProGen1,
+    AirPodsProGen2,
+    AirPodsProGen3,
+    AirPodsMax,
+    BeatsFitPro,
+];
+const ADAPTIVE_MODELS: &[DeviceModel] = &[AirPods4, AirPodsProGen2, AirPodsProGen3];
+const HEARING_MODELS: &[DeviceModel] = &[AirPodsProGen2, AirPodsProGen3];
+const STEM_MODELS: &[DeviceModel] = &[
+    AirPods3,
+    AirPods4,
+    AirPodsProGen1,
+    AirPodsProGen2,
+    AirPodsProGen3,
+    BeatsFitPro,
+];
+const TRANSPARENCY_MODELS: &[DeviceModel] =
+    &[AirPodsProGen1, AirPodsProGen2, AirPodsProGen3, AirPodsMax];
+
+/// Built-in message table, ordered by opcode
+pub const BUILTIN_MESSAGES: &[BuiltinMessage] = &[
+    BuiltinMessage {
+        opcode: 0x01,
+        msg_type: MessageType::BatteryStatus,
+        name: "BatteryStatus",
+        capability: DeviceCapability::BatteryMonitoring,
+        fields: &[
+            ("left_bud", U8),
+            ("right_bud", U8),
+            ("case", U8),
+            ("is_charging", Bool),
+        ],
+        models: ALL_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x02,
+        msg_type: MessageType::AncControl,
+        name: "AncControl",
+        capability: DeviceCapability::NoiseControl,
+        fields: &[("mode", Enum)],
+        models: ANC_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x03,
+        msg_type: MessageType::EarDetection,
+        name: "EarDetection",
+        capability: DeviceCapability::EarDetection,
+        fields: &[("state", Enum)],
+        models: ALL_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x04,
+        msg_type: MessageType::FirmwareInfo,
+        name: "FirmwareInfo",
+        capability: DeviceCapability::FirmwareInfo,
+        fields: &[("version", Utf8)],
+        models: ALL_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x05,
+        msg_type: MessageType::SpatialAudio,
+        name: "SpatialAudio",
+        capability: DeviceCapability::SpatialAudio,
+        fields: &[("flags", Flags)],
+        models: SPATIAL_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x06,
+        msg_type: MessageType::HeartRate,
+        name: "HeartRate",
+        capability: DeviceCapability::HeartRate,
+        fields: &[("bpm", U16), ("confidence", U8), ("timestamp", U64)],
+        models: &[AirPodsProGen3],
+    },
+    BuiltinMessage {
+        opcode: 0x07,
+        msg_type: MessageType::FindMy,
+        name: "FindMy",
+        capability: DeviceCapability::FindMy,
+        fields: &[
+            ("latitude", F64),
+            ("longitude", F64),
+            ("accuracy", F32),
+            ("timestamp", U64),
+        ],
+        models: ALL_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x08,
+        msg_type: MessageType::ConversationAwareness,
+        name: "ConversationAwareness",
+        capability: DeviceCapability::ConversationAwareness,
+        fields: &[("state", Enum)],
+        models: ADAPTIVE_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x09,
+        msg_type: MessageType::HearingAid,
+        name: "HearingAid",
+        capability: DeviceCapability::HearingAid,
+        fields: &[
+            ("enabled", Bool),
+            ("amplification_level", U8),
+            ("frequency_response", Bytes),
+        ],
+        models: HEARING_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x0A,
+        msg_type: MessageType::DeviceRename,
+        name: "DeviceRename",
+        capability: DeviceCapability::DeviceRename,
+        fields: &[("name", Utf8)],
+        models: ALL_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x0B,
+        msg_type: MessageType::MultipointControl,
+        name: "MultipointControl",
+        capability: DeviceCapability::Multipoint,
+        fields: &[("flags", Flags), ("devices", Bytes)],
+        models: ALL_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x0C,
+        msg_type: MessageType::AdaptiveTransparency,
+        name: "AdaptiveTransparency",
+        capability: DeviceCapability::AdaptiveTransparency,
+        fields: &[("enabled", Bool)],
+        models: ADAPTIVE_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x0D,
+        msg_type: MessageType::LongPressActions,
+        name: "LongPressActions",
+        capability: DeviceCapability::LongPressActions,
+        fields: &[("left", Enum), ("right", Enum)],
+        models: STEM_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x0E,
+        msg_type: MessageType::CustomTransparency,
+        name: "CustomTransparency",
+        capability: DeviceCapability::CustomTransparency,
+        fields: &[
+            ("enabled", Bool),
+            ("ambient_mix_level", U8),
+            ("voice_focus", Bool),
+        ],
+        models: TRANSPARENCY_MODELS,
+    },
+    BuiltinMessage {
+        opcode: 0x0F,
+        msg_type: MessageType::HeadGestures,
+        name: "HeadGestures",
+        capability: DeviceCapability::HeadGestures,
+        fields: &[("double_tap_enabled", Bool), ("double_tap_action", Enum)],
+        models: ADAPTIVE_MODELS,
+    },
+];
+
+/// Look up the built-in entry for an opcode
+pub fn builtin_message(opcode: u8) -> Option<&'static BuiltinMessage> {
+    BUILTIN_MESSAGES.iter().find(|m| m.opcode == opcode)
+}
+
+/// Look up the built-in entry for a message type
+pub fn builtin_message_for(msg_type: MessageType) -> Option<&'static BuiltinMessage> {
+    BUILTIN_MESSAGES.iter().find(|m| m.msg_type == msg_type)
+}
+
+/// Format a 128-bit UUID in canonical upper-case form
+pub fn format_uuid(uuid: u128) -> String {
+    let hex = format!("{:032X}", uuid);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// A named payload field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldDescriptor {
+    /// Field name
+    pub name: String,
+    /// Wire encoding
+    pub kind: FieldKind,
+}
+
+/// Runtime description of a message type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageDescriptor {
+    /// Opcode byte on the wire
+    pub opcode: u8,
+    /// Display name
+    pub name: String,
+    /// Characteristic carrying the message, the AAP one by default
+    #[serde(default = "default_characteristic_uuid")]
+    pub characteristic_uuid: String,
+    /// Capability a device needs to use the message
+    #[serde(default)]
+    pub capability: Option<DeviceCapability>,
+    /// Payload fields, in wire order
+    #[serde(default)]
+    pub fields: Vec<FieldDescriptor>,
+    /// Models that support the message
+    #[serde(default)]
+    pub models: Vec<DeviceModel>,
+}
+
+fn default_characteristic_uuid() -> String {
+    format_uuid(AAP_CHARACTERISTIC_UUID)
+}
+
+impl From<&BuiltinMessage> for MessageDescriptor {
+    fn from(builtin: &BuiltinMessage) -> Self {
+        Self {
+            opcode: builtin.opcode,
+            name: builtin.name.to_string(),
+            characteristic_uuid: default_characteristic_uuid(),
+            capability: Some(builtin.capability),
+            fields: builtin
+                .fields
+                .iter()
+                .map(|(name, kind)| FieldDescriptor {
+                    name: name.to_string(),
+                    kind: *kind,
+                })
+                .collect(),
+            models: builtin.models.to_vec(),
+        }
+    }
+}
+
+/// A named GATT service or characteristic
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UuidDescriptor {
+    /// Hyphenated UUID
+    pub uuid: String,
+    /// Display name
+    pub name: String,
+}
+
+/// On-disk format for vendor registry extensions
+#[derive(Debug, Default, Deserialize)]
+struct RegistryFile {
+    #[serde(default)]
+    messages: Vec<MessageDescriptor>,
+    #[serde(default)]
+    uuids: Vec<UuidDescriptor>,
+}
+
+/// Registry of known message types and UUIDs
+#[derive(Debug, Clone)]
+pub struct ProtocolRegistry {
+    messages: BTreeMap<u8, MessageDescriptor>,
+    uuids: BTreeMap<String, String>,
+}
+
+impl ProtocolRegistry {
+    /// Create a registry holding only the built-in protocol
+    pub fn builtin() -> Self {
+        let messages = BUILTIN_MESSAGES
+            .iter()
+            .map(|m| (m.opcode, MessageDescriptor::from(m)))
+            .collect();
+
+        let mut uuids = BTreeMap::new();
+        uuids.insert(format_uuid(AAP_SERVICE_UUID), "AAP_SERVICE".to_string());
+        uuids.insert(
+            format_uuid(AAP_CHARACTERISTIC_UUID),
+            "AAP_CHARACTERISTIC".to_string(),
+        );
+        uuids.insert("180F".to_string(), "BATTERY_SERVICE".to_string());
+        uuids.insert("180A".to_string(), "DEVICE_INFO_SERVICE".to_string());
+
+        Self { messages, uuids }
+    }
+
+    /// Register a vendor message type
+    ///
+    /// Fails with `ConfigError` if the opcode is already taken.
+    pub fn register(&mut self, descriptor: MessageDescriptor) -> Result<()> {
+        if let Some(existing) = self.messages.get(&descriptor.opcode) {
+            return Err(Error::ConfigError(format!(
+                "opcode 0x{:02X} already registered as {}",
+                descriptor.opcode, existing.name
+            )));
+        }
+        self.messages.insert(descriptor.opcode, descriptor);
+        Ok(())
+    }
+
+    /// Register a named UUID
+    pub fn register_uuid(&mut self, uuid: String, name: String) {
+        self.uuids.insert(uuid, name);
+    }
+
+    /// Merge vendor definitions from a JSON document
+    ///
+    /// Returns the number of message types added. The document is checked
+    /// as a whole first, so on error the registry is left unchanged.
+    pub fn load_json(&mut self, json: &str) -> Result<usize> {
+        let file: RegistryFile = serde_json::from_str(json)
+            .map_err(|e| Error::ConfigError(format!("invalid protocol registry: {}", e)))?;
+
+        let mut staged = self.messages.clone();
+        for descriptor in &file.messages {
+            if let Some(existing) = staged.get(&descriptor.opcode) {
+                return Err(Error::ConfigError(format!(
+                    "opcode 0x{:02X} already registered as {}",
+                    descriptor.opcode, existing.name
+                )));
+            }
+            staged.insert(descriptor.opcode, descriptor.clone());
+        }
+
+        self.messages = staged;
+        for uuid in file.uuids {
+            self.register_uuid(uuid.uuid, uuid.name);
+        }
+        Ok(file.messages.len())
+    }
+
+    /// Merge vendor definitions from a JSON file
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<usize> {
+        let json = std::fs::read_to_string(path)?;
+        self.load_json(&json)
+    }
+
+    /// Look up a message type by opcode
+    pub fn message(&self, opcode: u8) -> Option<&MessageDescriptor> {
+        self.messages.get(&opcode)
+    }
+
+    /// All registered message types, ordered by opcode
+    pub fn messages(&self) -> impl Iterator<Item = &MessageDescriptor> {
+        self.messages.values()
+    }
+
+    /// Message type of an opcode as this registry knows it
+    ///
+    /// Built-in opcodes map to their named type, opcodes added at runtime to
+    /// `Vendor` and everything else to `Unknown`.
+    pub fn message_type(&self, opcode: u8) -> MessageType {
+        match builtin_message(opcode) {
+            Some(builtin) => builtin.msg_type,
+            None if self.messages.contains_key(&opcode) => MessageType::Vendor(opcode),
+            None => MessageType::Unknown(opcode),
+        }
+    }
+
+    /// Whether the opcode was added at runtime rather than built in
+    pub fn is_vendor(&self, opcode: u8) -> bool {
+        self.messages.contains_key(&opcode) && builtin_message(opcode).is_none()
+    }
+
+    /// Message types supported by a device model
+    pub fn supported_by(&self, model: DeviceModel) -> Vec<&MessageDescriptor> {
+        self.messages
+            .values()
+            .filter(|m| m.models.contains(&model))
+            .collect()
+    }
+
+    /// Opcode to name table
+    pub fn message_names(&self) -> HashMap<u8, String> {
+        self.messages
+            .values()
+            .map(|m| (m.opcode, m.name.clone()))
+            .collect()
+    }
+
+    /// UUID to name table
+    pub fn uuid_names(&self) -> HashMap<String, String> {
+        self.uuids.clone().into_iter().collect()
+    }
+
+    /// Names of all features backed by a registered message type
+    pub fn feature_names(&self) -> HashSet<String> {
+        self.messages
+            .values()
+            .filter_map(|m| m.capability)
+            .map(|c| format!("{:?}", c))
+            .collect()
+    }
+}
+
+impl Default for ProtocolRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_table_matches_message_type() {
+        for (index, builtin) in BUILTIN_MESSAGES.iter().enumerate() {
+            assert_eq!(builtin.opcode, index as u8 + 1);
+            assert_eq!(format!("{:?}", builtin.msg_type), builtin.name);
+            assert_eq!(builtin.msg_type.opcode(), builtin.opcode);
+            assert_eq!(MessageType::from_u8(builtin.opcode), Ok(builtin.msg_type));
+        }
+    }
+
+    #[test]
+    fn test_builtin_registry() {
+        let registry = ProtocolRegistry::builtin();
+        assert_eq!(registry.messages().count(), 15);
+        assert_eq!(registry.uuid_names().len(), 4);
+        assert_eq!(registry.feature_names().len(), 15);
+        assert_eq!(registry.message(0x01).unwrap().name, "BatteryStatus");
+        assert!(registry
+            .uuid_names()
+            .contains_key("7DFC9000-7D1C-4951-86AA-8D9728F8D66C"));
+        assert!(!registry.is_vendor(0x01));
+    }
+
+    #[test]
+    fn test_supported_by_model() {
+        let registry = ProtocolRegistry::builtin();
+        let names: Vec<&str> = registry
+            .supported_by(DeviceModel::AirPods2)
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect();
+        assert!(names.contains(&"BatteryStatus"));
+        assert!(!names.contains(&"AncControl"));
+    }
+
+    #[test]
+    fn test_load_vendor_json() {
+        let mut registry = ProtocolRegistry::builtin();
+        let json = r#"{
+            "messages": [
+                {
+                    "opcode": 32,
+                    "name": "VendorSleepDetection",
+                    "fields": [{ "name": "asleep", "kind": "bool" }],
+                    "models": ["AirPodsProGen3"]
+                }
+            ],
+            "uuids": [{ "uuid": "FD44", "name": "VENDOR_SERVICE" }]
+        }"#;
+        assert_eq!(registry.load_json(json).unwrap(), 1);
+        let vendor = registry.message(0x20).unwrap();
+        assert_eq!(vendor.name, "VendorSleepDetection");
+        assert_eq!(
+            vendor.characteristic_uuid,
+            format_uuid(AAP_CHARACTERISTIC_UUID)
+        );
+        assert!(registry.is_vendor(0x20));
+        assert_eq!(registry.message_type(0x20), MessageType::Vendor(0x20));
+        assert_eq!(registry.message_type(0x01), MessageType::BatteryStatus);
+        assert_eq!(registry.message_type(0x21), MessageType::Unknown(0x21));
+        assert_eq!(registry.uuid_names().get("FD44").unwrap(), "VENDOR_SERVICE");
+    }
+
+    #[test]
+    fn test_load_rejects_duplicate_opcode() {
+        let mut registry = ProtocolRegistry::builtin();
+        let json = r#"{ "messages": [{ "opcode": 1, "name": "Clash" }] }"#;
+        assert!(matches!(
+            registry.load_json(json),
+            Err(Error::ConfigError(_))
+        ));
+
+        // Nothing from a rejected document is kept, even entries before the clash
+        let json = r#"{
+            "messages": [
+                { "opcode": 32, "name": "First" },
+                { "opcode": 32, "name": "Second" }
+            ],
+            "uuids": [{ "uuid": "FD44", "name": "VENDOR_SERVICE" }]
+        }"#;
+        assert!(matches!(
+            registry.load_json(json),
+            Err(Error::ConfigError(_))
+        ));
+        assert!(registry.message(0x20).is_none());
+        assert!(!registry.uuid_names().contains_key("FD44"));
+        assert!(matches!(
+            registry.load_json("not json"),
+            Err(Error::ConfigError(_))
+        ));
+    }
+}
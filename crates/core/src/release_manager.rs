@@ -15,14 +15,14 @@ impl ReleaseVersion {
     pub fn new(version: String) -> Self {
         let parts: Vec<&str> = version.split('-').collect();
         let base_parts: Vec<&str> = parts[0].split('.').collect();
-        
+
         let major = base_parts.get(0).and_then(|v| v.parse().ok()).unwrap_or(0);
         let minor = base_parts.get(1).and_then(|v| v.parse().ok()).unwrap_or(0);
         let patch = base_parts.get(2).and_then(|v| v.parse().ok()).unwrap_or(0);
-        
+
         let prerelease = parts.get(1).map(|s| s.to_string());
         let build_metadata = parts.get(2).map(|s| s.to_string());
-        
+
         Self {
             version,
             major,
@@ -118,7 +118,10 @@ impl Release {
     }
 
     pub fn get_artifacts_by_type(&self, artifact_type: ArtifactType) -> Vec<&ReleaseArtifact> {
-        self.artifacts.iter().filter(|a| a.artifact_type == artifact_type).collect()
+        self.artifacts
+            .iter()
+            .filter(|a| a.artifact_type == artifact_type)
+            .collect()
     }
 
     pub fn serialize_to_json(&self) -> Result<String, serde_json::Error> {
@@ -131,16 +134,26 @@ impl Release {
 
         report.push_str(&format!("Version: {}\n", self.version.version));
         report.push_str(&format!("Release Date: {}\n", self.release_date));
-        report.push_str(&format!("Status: {}\n", if self.is_draft { "Draft" } else { "Published" }));
+        report.push_str(&format!(
+            "Status: {}\n",
+            if self.is_draft { "Draft" } else { "Published" }
+        ));
         report.push_str(&format!("Prerelease: {}\n\n", self.is_prerelease));
 
         report.push_str(&format!("Artifacts: {}\n", self.artifacts.len()));
-        report.push_str(&format!("Total Size: {:.1} MB\n\n", self.get_total_size() as f32 / 1024.0 / 1024.0));
+        report.push_str(&format!(
+            "Total Size: {:.1} MB\n\n",
+            self.get_total_size() as f32 / 1024.0 / 1024.0
+        ));
 
         report.push_str("Artifacts:\n");
         for artifact in &self.artifacts {
-            report.push_str(&format!("  {} ({:?}) - {:.1} MB\n", 
-                artifact.artifact_id, artifact.artifact_type, artifact.file_size as f32 / 1024.0 / 1024.0));
+            report.push_str(&format!(
+                "  {} ({:?}) - {:.1} MB\n",
+                artifact.artifact_id,
+                artifact.artifact_type,
+                artifact.file_size as f32 / 1024.0 / 1024.0
+            ));
         }
 
         report
@@ -175,7 +188,9 @@ impl ReleaseManager {
     }
 
     pub fn get_latest_release(&self) -> Option<&Release> {
-        self.releases.values().max_by_key(|r| (r.version.major, r.version.minor, r.version.patch))
+        self.releases
+            .values()
+            .max_by_key(|r| (r.version.major, r.version.minor, r.version.patch))
     }
 
     pub fn publish_release(&mut self, version: &str) -> bool {
@@ -192,11 +207,17 @@ impl ReleaseManager {
     }
 
     pub fn get_stable_releases(&self) -> Vec<&Release> {
-        self.releases.values().filter(|r| r.version.is_stable()).collect()
+        self.releases
+            .values()
+            .filter(|r| r.version.is_stable())
+            .collect()
     }
 
     pub fn get_prerelease_releases(&self) -> Vec<&Release> {
-        self.releases.values().filter(|r| r.version.is_prerelease()).collect()
+        self.releases
+            .values()
+            .filter(|r| r.version.is_prerelease())
+            .collect()
     }
 }
 
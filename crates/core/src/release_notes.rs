@@ -72,7 +72,10 @@ impl ReleaseNotes {
     }
 
     pub fn get_changes_by_type(&self, change_type: ChangeType) -> Vec<&ChangeEntry> {
-        self.changes.iter().filter(|c| c.change_type == change_type).collect()
+        self.changes
+            .iter()
+            .filter(|c| c.change_type == change_type)
+            .collect()
     }
 
     pub fn get_feature_count(&self) -> usize {
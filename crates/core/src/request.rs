@@ -0,0 +1,478 @@
+//! Request/response correlation on top of a Bluetooth backend
+
+use crate::bluetooth::AsyncBluetoothBackend;
+use crate::error::{Error, Result};
+use crate::protocol::{
+    FrameDecoder, FrameVersion, Message, MessageType, AAP_CHARACTERISTIC_UUID, AAP_SERVICE_UUID,
+};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Per-request delivery options
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestOptions {
+    /// How long to wait for a response to each attempt
+    pub timeout: Duration,
+    /// How many times to resend the request after a timeout
+    pub retries: u32,
+    /// Message type of the expected response, defaults to the request type
+    pub response_type: Option<MessageType>,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(2),
+            retries: 2,
+            response_type: None,
+        }
+    }
+}
+
+struct Pending {
+    sequence: u64,
+    sender: oneshot::Sender<Result<Message>>,
+}
+
+struct Inner<B: ?Sized> {
+    backend: Arc<B>,
+    address: String,
+    version: FrameVersion,
+    decoder: Mutex<FrameDecoder>,
+    pending: Mutex<HashMap<MessageType, VecDeque<Pending>>>,
+    next_sequence: AtomicU64,
+}
+
+impl<B: AsyncBluetoothBackend + ?Sized> Inner<B> {
+    async fn write(&self, frame: &[u8]) -> Result<()> {
+        self.backend
+            .write_characteristic(
+                &self.address,
+                AAP_SERVICE_UUID,
+                AAP_CHARACTERISTIC_UUID,
+                frame,
+            )
+            .await
+    }
+
+    fn remove(&self, sequence: u64) -> Option<Pending> {
+        let mut pending = self.pending.lock().ok()?;
+        for queue in pending.values_mut() {
+            if let Some(index) = queue.iter().position(|p| p.sequence == sequence) {
+                return queue.remove(index);
+            }
+        }
+        None
+    }
+}
+
+/// Correlates outgoing AAP requests with their responses
+///
+/// Requests are written to the AAP characteristic and complete when a
+/// message of the expected response type arrives. The frame format carries
+/// no sequence field, so responses of one type complete outstanding
+/// requests of that type in the order they were sent.
+///
+/// The manager does not read from the device itself: whoever owns the AAP
+/// notification stream passes what arrives to
+/// [`RequestManager::handle_notification`], or decoded messages to
+/// [`RequestManager::handle_message`].
+pub struct RequestManager<B: ?Sized> {
+    inner: Arc<Inner<B>>,
+    defaults: RequestOptions,
+}
+
+impl<B: AsyncBluetoothBackend + ?Sized> RequestManager<B> {
+    /// Create a manager sending requests to `address` through `backend`
+    pub fn new(backend: Arc<B>, address: &str) -> Self {
+        Self::with_version(backend, address, FrameVersion::default())
+    }
+
+    /// Create a manager using a negotiated frame version
+    pub fn with_version(backend: Arc<B>, address: &str, version: FrameVersion) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                backend,
+                address: address.to_string(),
+                version,
+                decoder: Mutex::new(FrameDecoder::with_version(version)),
+                pending: Mutex::new(HashMap::new()),
+                next_sequence: AtomicU64::new(1),
+            }),
+            defaults: RequestOptions::default(),
+        }
+    }
+
+    /// Set the options used by [`RequestManager::request`]
+    pub fn set_default_options(&mut self, options: RequestOptions) {
+        self.defaults = options;
+    }
+
+    /// Send a request with the default options and await its response
+    pub async fn request(&self, message: Message) -> Result<Message> {
+        self.request_with(message, self.defaults).await
+    }
+
+    /// Send a request with explicit options and await its response
+    pub async fn request_with(&self, message: Message, options: RequestOptions) -> Result<Message> {
+        self.submit(message, options).await?.wait().await
+    }
+
+    /// Send a request and return a handle that can be awaited or cancelled
+    pub async fn submit(
+        &self,
+        message: Message,
+        options: RequestOptions,
+    ) -> Result<PendingRequest<B>> {
+        let frame = message.serialize_versioned(self.inner.version)?;
+        let response_type = options.response_type.unwrap_or(message.msg_type);
+        let sequence = self.inner.next_sequence.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+
+        self.inner
+            .pending
+            .lock()
+            .map_err(|_| Error::InvalidState)?
+            .entry(response_type)
+            .or_default()
+            .push_back(Pending { sequence, sender });
+
+        // Registered before writing, so the handle also cleans up if this
+        // future is dropped mid-write
+        let pending = PendingRequest {
+            inner: Arc::clone(&self.inner),
+            sequence,
+            frame,
+            options,
+            receiver,
+        };
+        self.inner.write(&pending.frame).await?;
+        Ok(pending)
+    }
+
+    /// Send a message the device does not answer
+    pub async fn send(&self, message: &Message) -> Result<()> {
+        let frame = message.serialize_versioned(self.inner.version)?;
+        self.inner.write(&frame).await
+    }
+
+    /// Feed notification bytes received from the AAP characteristic
+    ///
+    /// Returns unsolicited messages and decode errors; responses to
+    /// outstanding requests are delivered to their waiters.
+    pub fn handle_notification(&self, chunk: &[u8]) -> Vec<Result<Message>> {
+        let decoded = match self.inner.decoder.lock() {
+            Ok(mut decoder) => decoder.push(chunk),
+            Err(_) => return vec![Err(Error::InvalidState)],
+        };
+
+        let mut unhandled = Vec::new();
+        for result in decoded {
+            match result {
+                Ok(message) => {
+                    if let Some(message) = self.handle_message(message) {
+                        unhandled.push(Ok(message));
+                    }
+                }
+                Err(err) => unhandled.push(Err(err)),
+            }
+        }
+        unhandled
+    }
+
+    /// Deliver a decoded message to the oldest live waiter of its type
+    ///
+    /// Returns the message if no request was waiting for it.
+    pub fn handle_message(&self, message: Message) -> Option<Message> {
+        let mut pending = match self.inner.pending.lock() {
+            Ok(pending) => pending,
+            Err(_) => return Some(message),
+        };
+        let queue = match pending.get_mut(&message.msg_type) {
+            Some(queue) => queue,
+            None => return Some(message),
+        };
+
+        let mut message = message;
+        while let Some(entry) = queue.pop_front() {
+            match entry.sender.send(Ok(message)) {
+                Ok(()) => return None,
+                // The waiter went away; try the next one in line
+                Err(Ok(returned)) => message = returned,
+                Err(Err(_)) => unreachable!("sent value is always Ok"),
+            }
+        }
+        Some(message)
+    }
+
+    /// Cancel an outstanding request, returning `false` if it already finished
+    pub fn cancel(&self, sequence: u64) -> bool {
+        match self.inner.remove(sequence) {
+            Some(pending) => {
+                let _ = pending.sender.send(Err(Error::Cancelled));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancel every outstanding request
+    pub fn cancel_all(&self) {
+        if let Ok(mut pending) = self.inner.pending.lock() {
+            for (_, queue) in pending.drain() {
+                for entry in queue {
+                    let _ = entry.sender.send(Err(Error::Cancelled));
+                }
+            }
+        }
+    }
+
+    /// Number of requests still waiting for a response
+    pub fn pending_count(&self) -> usize {
+        self.inner
+            .pending
+            .lock()
+            .map(|pending| pending.values().map(VecDeque::len).sum())
+            .unwrap_or(0)
+    }
+
+    /// The backend requests are written through
+    pub fn backend(&self) -> &Arc<B> {
+        &self.inner.backend
+    }
+}
+
+/// Handle to a request that has been sent but not yet answered
+///
+/// Dropping the handle abandons the request.
+pub struct PendingRequest<B: AsyncBluetoothBackend + ?Sized> {
+    inner: Arc<Inner<B>>,
+    sequence: u64,
+    frame: Vec<u8>,
+    options: RequestOptions,
+    receiver: oneshot::Receiver<Result<Message>>,
+}
+
+impl<B: AsyncBluetoothBackend + ?Sized> PendingRequest<B> {
+    /// Locally assigned sequence number, usable with [`RequestManager::cancel`]
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Wait for the response, resending on timeout until retries run out
+    pub async fn wait(mut self) -> Result<Message> {
+        let mut attempts = 0;
+        loop {
+            match tokio::time::timeout(self.options.timeout, &mut self.receiver).await {
+                Ok(Ok(result)) => return result,
+                Ok(Err(_)) => return Err(Error::Cancelled),
+                Err(_) if attempts < self.options.retries => {
+                    attempts += 1;
+                    log::debug!(
+                        "request {} timed out, retrying ({}/{})",
+                        self.sequence,
+                        attempts,
+                        self.options.retries
+                    );
+                    self.inner.write(&self.frame).await?;
+                }
+                Err(_) => return Err(Error::Timeout),
+            }
+        }
+    }
+}
+
+impl<B: AsyncBluetoothBackend + ?Sized> Drop for PendingRequest<B> {
+    fn drop(&mut self) {
+        self.inner.remove(self.sequence);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluetooth::{BackendFuture, NotificationStream, ScanStream};
+
+    /// Records every write
+    #[derive(Default)]
+    struct RecordingBackend {
+        writes: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl RecordingBackend {
+        fn writes(&self) -> usize {
+            self.writes.lock().unwrap().len()
+        }
+    }
+
+    impl AsyncBluetoothBackend for RecordingBackend {
+        fn scan(&self) -> BackendFuture<'_, ScanStream> {
+            Box::pin(async { Ok(Box::pin(futures_util::stream::empty()) as ScanStream) })
+        }
+
+        fn stop_scan(&self) -> BackendFuture<'_, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn connect<'a>(&'a self, _address: &'a str) -> BackendFuture<'a, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn disconnect<'a>(&'a self, _address: &'a str) -> BackendFuture<'a, ()> {
+            Box::pin(async { Ok(()) })
+        }
+
+        fn write_characteristic<'a>(
+            &'a self,
+            _address: &'a str,
+            _service_uuid: u128,
+            _char_uuid: u128,
+            data: &'a [u8],
+        ) -> BackendFuture<'a, ()> {
+            self.writes.lock().unwrap().push(data.to_vec());
+            Box::pin(async { Ok(()) })
+        }
+
+        fn read_characteristic<'a>(
+            &'a self,
+            _address: &'a str,
+            _service_uuid: u128,
+            _char_uuid: u128,
+        ) -> BackendFuture<'a, Vec<u8>> {
+            Box::pin(async { Ok(Vec::new()) })
+        }
+
+        fn notifications<'a>(
+            &'a self,
+            _address: &'a str,
+            _service_uuid: u128,
+            _char_uuid: u128,
+        ) -> BackendFuture<'a, NotificationStream> {
+            Box::pin(async { Ok(Box::pin(futures_util::stream::pending()) as NotificationStream) })
+        }
+    }
+
+    fn manager() -> RequestManager<RecordingBackend> {
+        RequestManager::new(Arc::new(RecordingBackend::default()), "AA:BB")
+    }
+
+    fn frame(msg_type: MessageType, payload: &[u8]) -> Vec<u8> {
+        Message::new(msg_type, payload.to_vec())
+            .serialize()
+            .unwrap()
+    }
+
+    fn options(retries: u32) -> RequestOptions {
+        RequestOptions {
+            timeout: Duration::from_millis(100),
+            retries,
+            response_type: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_matches_response() {
+        let manager = manager();
+        let pending = manager
+            .submit(Message::new(MessageType::AncControl, vec![1]), options(0))
+            .await
+            .unwrap();
+        let unsolicited =
+            manager.handle_notification(&frame(MessageType::BatteryStatus, &[1, 2, 3]));
+        assert_eq!(unsolicited.len(), 1);
+        assert!(manager
+            .handle_notification(&frame(MessageType::AncControl, &[1]))
+            .is_empty());
+
+        let response = pending.wait().await.unwrap();
+        assert_eq!(response.payload, vec![1]);
+        assert_eq!(manager.pending_count(), 0);
+        assert_eq!(manager.backend().writes(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_responses_complete_in_send_order() {
+        let manager = manager();
+        let first = manager
+            .submit(Message::new(MessageType::AncControl, vec![1]), options(0))
+            .await
+            .unwrap();
+        let second = manager
+            .submit(Message::new(MessageType::AncControl, vec![2]), options(0))
+            .await
+            .unwrap();
+        assert!(first.sequence() < second.sequence());
+
+        let mut bytes = frame(MessageType::AncControl, &[1]);
+        bytes.extend(frame(MessageType::AncControl, &[2]));
+        assert!(manager.handle_notification(&bytes).is_empty());
+
+        assert_eq!(first.wait().await.unwrap().payload, vec![1]);
+        assert_eq!(second.wait().await.unwrap().payload, vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_decoded_message_completes_request() {
+        let manager = manager();
+        let request = manager.request_with(
+            Message::new(MessageType::BatteryStatus, Vec::new()),
+            options(0),
+        );
+        let respond = async {
+            while manager.pending_count() == 0 {
+                tokio::task::yield_now().await;
+            }
+            let report = Message::new(MessageType::BatteryStatus, vec![50, 60, 70]);
+            assert_eq!(manager.handle_message(report), None);
+        };
+        let (response, ()) = tokio::join!(request, respond);
+        assert_eq!(response.unwrap().payload, vec![50, 60, 70]);
+
+        manager
+            .send(&Message::new(MessageType::DeviceRename, b"Den".to_vec()))
+            .await
+            .unwrap();
+        assert_eq!(manager.backend().writes(), 2);
+        assert_eq!(manager.pending_count(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_request_retries_then_times_out() {
+        let manager = manager();
+        let result = manager
+            .request_with(Message::new(MessageType::AncControl, vec![1]), options(2))
+            .await;
+        assert_eq!(result, Err(Error::Timeout));
+        assert_eq!(manager.backend().writes(), 3);
+        assert_eq!(manager.pending_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_request() {
+        let manager = manager();
+        let pending = manager
+            .submit(Message::new(MessageType::AncControl, vec![1]), options(0))
+            .await
+            .unwrap();
+        assert!(manager.cancel(pending.sequence()));
+        assert!(!manager.cancel(pending.sequence()));
+        assert_eq!(pending.wait().await, Err(Error::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request_is_skipped() {
+        let manager = manager();
+        let abandoned = manager
+            .submit(Message::new(MessageType::AncControl, vec![1]), options(0))
+            .await
+            .unwrap();
+        drop(abandoned);
+        assert_eq!(manager.pending_count(), 0);
+
+        let unsolicited = manager.handle_notification(&frame(MessageType::AncControl, &[1]));
+        assert_eq!(unsolicited.len(), 1);
+    }
+}
@@ -72,11 +72,17 @@ impl SBOM {
     }
 
     pub fn get_components_by_type(&self, component_type: ComponentType) -> Vec<&Component> {
-        self.components.iter().filter(|c| c.component_type == component_type).collect()
+        self.components
+            .iter()
+            .filter(|c| c.component_type == component_type)
+            .collect()
     }
 
     pub fn get_components_by_license(&self, license: &str) -> Vec<&Component> {
-        self.components.iter().filter(|c| c.license == license).collect()
+        self.components
+            .iter()
+            .filter(|c| c.license == license)
+            .collect()
     }
 
     pub fn get_total_components(&self) -> usize {
@@ -99,8 +105,14 @@ impl SBOM {
         report.push_str(&format!("Version: {}\n", self.version));
         report.push_str(&format!("Created: {}\n\n", self.created_date));
 
-        report.push_str(&format!("Total Components: {}\n", self.get_total_components()));
-        report.push_str(&format!("Unique Licenses: {}\n\n", self.get_unique_licenses().len()));
+        report.push_str(&format!(
+            "Total Components: {}\n",
+            self.get_total_components()
+        ));
+        report.push_str(&format!(
+            "Unique Licenses: {}\n\n",
+            self.get_unique_licenses().len()
+        ));
 
         report.push_str("License Distribution:\n");
         for (license, count) in &self.licenses {
@@ -109,8 +121,10 @@ impl SBOM {
 
         report.push_str("\nComponents:\n");
         for component in &self.components {
-            report.push_str(&format!("  {} v{} ({:?}) - {}\n", 
-                component.name, component.version, component.component_type, component.license));
+            report.push_str(&format!(
+                "  {} v{} ({:?}) - {}\n",
+                component.name, component.version, component.component_type, component.license
+            ));
         }
 
         report
@@ -0,0 +1,994 @@
+//! Virtual AirPods for testing without hardware
+//!
+//! A [`SimulatedBackend`] stands in for the radio. Each [`SimulatedDevice`]
+//! answers AAP commands the way real buds do, drains its battery and plays
+//! a script of ear detection and connection events as simulated time
+//! advances. Faults can be injected to exercise error handling.
+
+use crate::advertisement::{self, ProximityPairing, APPLE_COMPANY_ID};
+use crate::bluetooth::{
+    channel_stream, AsyncBluetoothBackend, BackendFuture, BluetoothBackend, BluetoothDevice,
+    NotificationStream, ScanStream,
+};
+use crate::device::DeviceModel;
+use crate::error::{Error, Result};
+use crate::models::{AncMode, EarDetectionState, MultipointInfo};
+use crate::payload::Payload;
+use crate::protocol::{Message, MessageType, AAP_CHARACTERISTIC_UUID, AAP_SERVICE_UUID};
+use crate::state::BatteryInfo;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task::JoinHandle;
+
+/// Address of the device created by [`SimulatedBackend::demo`]
+pub const DEMO_ADDRESS: &str = "5A:1D:00:00:00:01";
+
+/// Something a device does on its own at a scripted time
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulatedEvent {
+    /// Report these battery levels
+    Battery(BatteryInfo),
+    /// Report a new ear detection state
+    EarDetection(EarDetectionState),
+    /// Another host claims the audio, if automatic switching allows it
+    SwitchHost(String),
+    /// Drop the connection
+    Disconnect,
+}
+
+/// A fault to inject into a device's link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Drop the connection now
+    Disconnect,
+    /// Refuse connection attempts until cleared with `RefuseConnections(false)`
+    RefuseConnections(bool),
+    /// Corrupt the CRC of the next `n` notifications
+    CorruptCrc(u32),
+    /// Hold every notification back this long in simulated time
+    Latency(Duration),
+}
+
+/// State of one virtual device
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatedDevice {
+    address: String,
+    name: String,
+    model: DeviceModel,
+    rssi: i16,
+    connected: bool,
+    notifying: bool,
+    session: MultipointInfo,
+    battery: BatteryInfo,
+    drain_per_hour: f64,
+    drained: f64,
+    anc_mode: AncMode,
+    ear_state: EarDetectionState,
+    script: VecDeque<(Duration, SimulatedEvent)>,
+    refuse_connections: bool,
+    corrupt_next: u32,
+    latency: Duration,
+}
+
+impl SimulatedDevice {
+    /// A full, idle device at `address`, paired with no hosts
+    pub fn new(address: &str, name: &str, model: DeviceModel) -> Self {
+        Self {
+            address: address.to_string(),
+            name: name.to_string(),
+            model,
+            rssi: -50,
+            connected: false,
+            notifying: false,
+            session: MultipointInfo {
+                enabled: true,
+                connected_devices: Vec::new(),
+                active_device: None,
+            },
+            battery: BatteryInfo {
+                left_bud: 100,
+                right_bud: 100,
+                case: 100,
+                is_charging: false,
+            },
+            drain_per_hour: 0.0,
+            drained: 0.0,
+            anc_mode: AncMode::Off,
+            ear_state: EarDetectionState::BothEarsOut,
+            script: VecDeque::new(),
+            refuse_connections: false,
+            corrupt_next: 0,
+            latency: Duration::ZERO,
+        }
+    }
+
+    /// Connect the device to `hosts`, with audio on `active`
+    pub fn with_hosts(mut self, hosts: &[&str], active: Option<&str>) -> Self {
+        self.session.connected_devices = hosts.iter().map(|h| h.to_string()).collect();
+        self.session.active_device = active.map(str::to_string);
+        self
+    }
+
+    /// Signal strength reported to scans
+    pub fn with_rssi(mut self, rssi: i16) -> Self {
+        self.rssi = rssi;
+        self
+    }
+
+    /// Battery levels and charging state to start from
+    pub fn with_battery(mut self, battery: BatteryInfo) -> Self {
+        self.battery = battery;
+        self
+    }
+
+    /// Drain both buds by `percent` per hour of simulated time while
+    /// connected; charging fills them at the same rate instead
+    pub fn with_drain(mut self, percent_per_hour: f64) -> Self {
+        self.drain_per_hour = percent_per_hour.max(0.0);
+        self
+    }
+
+    /// Play `event` once simulated time reaches `at`
+    ///
+    /// Steps may be added in any order.
+    pub fn with_event(mut self, at: Duration, event: SimulatedEvent) -> Self {
+        let index = self.script.partition_point(|(t, _)| *t <= at);
+        self.script.insert(index, (at, event));
+        self
+    }
+
+    /// Bluetooth address
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Advertised name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Model the device reports
+    pub fn model(&self) -> DeviceModel {
+        self.model
+    }
+
+    /// Signal strength reported to scans
+    pub fn rssi(&self) -> i16 {
+        self.rssi
+    }
+
+    /// Whether a host is connected
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Multipoint session as the device would report it
+    pub fn session(&self) -> &MultipointInfo {
+        &self.session
+    }
+
+    /// Current battery levels
+    pub fn battery(&self) -> &BatteryInfo {
+        &self.battery
+    }
+
+    /// Current noise control mode
+    pub fn anc_mode(&self) -> AncMode {
+        self.anc_mode
+    }
+
+    /// Which buds are in ear
+    pub fn ear_state(&self) -> EarDetectionState {
+        self.ear_state
+    }
+
+    /// Apply a command written by the host, returning the device's replies
+    fn handle_command(&mut self, message: &Message) -> Result<Vec<Payload>> {
+        // An empty battery message is a status request
+        if message.msg_type == MessageType::BatteryStatus && message.payload.is_empty() {
+            return Ok(vec![Payload::BatteryStatus(self.battery.clone())]);
+        }
+        match message.decode_payload()? {
+            Payload::MultipointControl(command) => {
+                match command.active_device {
+                    Some(host) => self.activate(&host),
+                    None => self.session.enabled = command.enabled,
+                }
+                Ok(vec![Payload::MultipointControl(self.session.clone())])
+            }
+            Payload::AncControl(mode) => {
+                self.anc_mode = mode;
+                Ok(vec![Payload::AncControl(mode)])
+            }
+            Payload::DeviceRename(name) => {
+                self.name = name;
+                Ok(Vec::new())
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Reports sent when notifications are enabled
+    fn initial_reports(&self) -> Vec<Payload> {
+        vec![
+            Payload::BatteryStatus(self.battery.clone()),
+            Payload::AncControl(self.anc_mode),
+            Payload::EarDetection(self.ear_state),
+            Payload::MultipointControl(self.session.clone()),
+        ]
+    }
+
+    fn activate(&mut self, host: &str) {
+        if !self.session.connected_devices.iter().any(|h| h == host) {
+            self.session.connected_devices.push(host.to_string());
+        }
+        self.session.active_device = Some(host.to_string());
+    }
+
+    /// Drain or charge the buds for `elapsed`, reporting any change
+    fn run_battery(&mut self, elapsed: Duration) -> Option<Payload> {
+        if !self.connected || self.drain_per_hour == 0.0 {
+            return None;
+        }
+        self.drained += self.drain_per_hour * elapsed.as_secs_f64() / 3600.0;
+        let steps = self.drained.floor();
+        if steps < 1.0 {
+            return None;
+        }
+        self.drained -= steps;
+        let steps = steps.min(100.0) as u8;
+        let adjust = |level: u8| {
+            if self.battery.is_charging {
+                level.saturating_add(steps).min(100)
+            } else {
+                level.saturating_sub(steps)
+            }
+        };
+        let left = adjust(self.battery.left_bud);
+        let right = adjust(self.battery.right_bud);
+        self.battery.left_bud = left;
+        self.battery.right_bud = right;
+        Some(Payload::BatteryStatus(self.battery.clone()))
+    }
+
+    /// Play a scripted event, returning what the device reports about it
+    fn play(&mut self, event: SimulatedEvent) -> Option<Payload> {
+        match event {
+            SimulatedEvent::Battery(battery) => {
+                self.battery = battery;
+                Some(Payload::BatteryStatus(self.battery.clone()))
+            }
+            SimulatedEvent::EarDetection(state) => {
+                self.ear_state = state;
+                Some(Payload::EarDetection(state))
+            }
+            SimulatedEvent::SwitchHost(host) => {
+                if !self.session.enabled {
+                    return None;
+                }
+                self.activate(&host);
+                Some(Payload::MultipointControl(self.session.clone()))
+            }
+            SimulatedEvent::Disconnect => {
+                self.drop_link();
+                None
+            }
+        }
+    }
+
+    /// How a scan sees the device, its battery in a proximity pairing message
+    pub fn advertisement(&self) -> BluetoothDevice {
+        let pairing = ProximityPairing {
+            model_id: advertisement::model_id(self.model),
+            model: Some(self.model),
+            left: Some(self.battery.left_bud),
+            right: Some(self.battery.right_bud),
+            case: Some(self.battery.case),
+            left_charging: self.battery.is_charging,
+            right_charging: self.battery.is_charging,
+            case_charging: false,
+            lid_open: true,
+        };
+        BluetoothDevice {
+            address: self.address.clone(),
+            name: self.name.clone(),
+            rssi: i32::from(self.rssi),
+            is_connected: self.connected,
+            manufacturer_data: HashMap::from([(APPLE_COMPANY_ID, pairing.encode())]),
+        }
+    }
+
+    fn drop_link(&mut self) {
+        self.connected = false;
+        self.notifying = false;
+    }
+}
+
+struct Notification {
+    address: String,
+    frame: Vec<u8>,
+    due: Duration,
+}
+
+#[derive(Default)]
+struct SimulatorState {
+    devices: BTreeMap<String, SimulatedDevice>,
+    notifications: VecDeque<Notification>,
+    /// Notification streams by device address
+    subscribers: HashMap<String, Vec<UnboundedSender<Vec<u8>>>>,
+    scanners: Vec<UnboundedSender<BluetoothDevice>>,
+    clock: Duration,
+    scanning: bool,
+}
+
+impl SimulatorState {
+    fn device_mut(&mut self, address: &str) -> Result<&mut SimulatedDevice> {
+        self.devices
+            .get_mut(address)
+            .ok_or_else(|| Error::BluetoothError(format!("no simulated device at {}", address)))
+    }
+
+    fn connected_mut(&mut self, address: &str) -> Result<&mut SimulatedDevice> {
+        let device = self.device_mut(address)?;
+        if !device.connected {
+            return Err(Error::DeviceNotConnected);
+        }
+        Ok(device)
+    }
+
+    /// Queue `payloads` as notifications if the device has them enabled
+    fn notify(&mut self, address: &str, payloads: Vec<Payload>) -> Result<()> {
+        let clock = self.clock;
+        let device = self.device_mut(address)?;
+        if !(device.connected && device.notifying) {
+            return Ok(());
+        }
+        let mut frames = Vec::with_capacity(payloads.len());
+        for payload in payloads {
+            let mut frame = payload.to_message()?.serialize()?;
+            if device.corrupt_next > 0 {
+                device.corrupt_next -= 1;
+                if let Some(last) = frame.last_mut() {
+                    *last ^= 0xFF;
+                }
+            }
+            frames.push(frame);
+        }
+        let due = clock + device.latency;
+        self.notifications
+            .extend(frames.into_iter().map(|frame| Notification {
+                address: address.to_string(),
+                frame,
+                due,
+            }));
+        self.route();
+        Ok(())
+    }
+
+    /// Send due notifications to the streams of their devices
+    ///
+    /// Notifications for devices without a stream stay queued for
+    /// [`SimulatedBackend::take_notifications`].
+    fn route(&mut self) {
+        let clock = self.clock;
+        let subscribers = &mut self.subscribers;
+        self.notifications.retain(|notification| {
+            if notification.due > clock {
+                return true;
+            }
+            let Some(streams) = subscribers.get_mut(&notification.address) else {
+                return true;
+            };
+            streams.retain(|stream| stream.send(notification.frame.clone()).is_ok());
+            streams.is_empty()
+        });
+    }
+
+    /// Show a device to the running scans
+    fn advertise(&mut self, address: &str) {
+        if let Some(device) = self.devices.get(address) {
+            let advertisement = device.advertisement();
+            self.scanners
+                .retain(|scanner| scanner.send(advertisement.clone()).is_ok());
+        }
+    }
+
+    fn disconnect(&mut self, address: &str) -> Result<()> {
+        self.device_mut(address)?.drop_link();
+        // Ends the device's notification streams
+        self.subscribers.remove(address);
+        Ok(())
+    }
+
+    fn connect(&mut self, address: &str) -> Result<()> {
+        let device = self.device_mut(address)?;
+        if device.refuse_connections {
+            return Err(Error::BluetoothError(format!(
+                "{} refused the connection",
+                address
+            )));
+        }
+        device.connected = true;
+        Ok(())
+    }
+
+    fn write(
+        &mut self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+        data: &[u8],
+    ) -> Result<()> {
+        check_aap(service_uuid, char_uuid)?;
+        let replies = self
+            .connected_mut(address)?
+            .handle_command(&Message::parse(data)?)?;
+        self.notify(address, replies)
+    }
+
+    fn read(&mut self, address: &str, service_uuid: u128, char_uuid: u128) -> Result<Vec<u8>> {
+        check_aap(service_uuid, char_uuid)?;
+        self.connected_mut(address)?;
+        Ok(Vec::new())
+    }
+
+    /// Enabling notifications makes the device report its current state
+    fn enable_notifications(
+        &mut self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> Result<()> {
+        check_aap(service_uuid, char_uuid)?;
+        let device = self.connected_mut(address)?;
+        device.notifying = true;
+        let reports = device.initial_reports();
+        self.notify(address, reports)
+    }
+
+    fn advance(&mut self, elapsed: Duration) -> Result<()> {
+        self.clock += elapsed;
+        let clock = self.clock;
+        let addresses: Vec<String> = self.devices.keys().cloned().collect();
+        for address in addresses {
+            let device = self.device_mut(&address)?;
+            let mut reports: Vec<Payload> = device.run_battery(elapsed).into_iter().collect();
+            let drained = !reports.is_empty();
+            while device.script.front().is_some_and(|(at, _)| *at <= clock) {
+                if let Some((_, event)) = device.script.pop_front() {
+                    reports.extend(device.play(event));
+                }
+            }
+            let connected = device.connected;
+            self.notify(&address, reports)?;
+            if drained {
+                self.advertise(&address);
+            }
+            if !connected {
+                self.subscribers.remove(&address);
+            }
+        }
+        self.route();
+        Ok(())
+    }
+}
+
+/// Backend talking to virtual devices instead of a radio
+///
+/// Clones share the same devices, so a test can keep one handle to drive
+/// the devices while an [`Engine`](crate::Engine) owns another. Simulated
+/// time only moves in [`SimulatedBackend::advance`]. Frames the devices send
+/// wait until they are due, then go to the device's notification streams;
+/// without a stream they are queued for
+/// [`SimulatedBackend::take_notifications`].
+#[derive(Clone, Default)]
+pub struct SimulatedBackend {
+    state: Arc<Mutex<SimulatorState>>,
+}
+
+impl SimulatedBackend {
+    /// Backend without devices
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A backend with one AirPods Pro paired to two hosts, draining at
+    /// 10% per hour and taken out and put back in after a minute
+    pub fn demo() -> Self {
+        let backend = Self::new();
+        backend.add_device(
+            SimulatedDevice::new(
+                DEMO_ADDRESS,
+                "Simulated AirPods Pro",
+                DeviceModel::AirPodsProGen2,
+            )
+            .with_hosts(&["this-host", "phone"], Some("this-host"))
+            .with_battery(BatteryInfo {
+                left_bud: 85,
+                right_bud: 80,
+                case: 60,
+                is_charging: false,
+            })
+            .with_drain(10.0)
+            .with_event(
+                Duration::ZERO,
+                SimulatedEvent::EarDetection(EarDetectionState::BothEarsIn),
+            )
+            .with_event(
+                Duration::from_secs(60),
+                SimulatedEvent::EarDetection(EarDetectionState::LeftEarIn),
+            )
+            .with_event(
+                Duration::from_secs(70),
+                SimulatedEvent::EarDetection(EarDetectionState::BothEarsIn),
+            ),
+        );
+        backend
+    }
+
+    /// Add a virtual device, replacing any at the same address
+    pub fn add_device(&self, device: SimulatedDevice) {
+        let mut state = self.state();
+        let address = device.address.clone();
+        state.devices.insert(address.clone(), device);
+        state.advertise(&address);
+    }
+
+    /// Change the signal strength running scans see for a device
+    pub fn set_rssi(&self, address: &str, rssi: i16) -> Result<()> {
+        let mut state = self.state();
+        state.device_mut(address)?.rssi = rssi;
+        state.advertise(address);
+        Ok(())
+    }
+
+    /// Copy of a virtual device's current state
+    pub fn device(&self, address: &str) -> Option<SimulatedDevice> {
+        self.state().devices.get(address).cloned()
+    }
+
+    /// Copies of all virtual devices, by address
+    pub fn devices(&self) -> Vec<SimulatedDevice> {
+        self.state().devices.values().cloned().collect()
+    }
+
+    /// Whether a scan is running
+    pub fn is_scanning(&self) -> bool {
+        self.state().scanning
+    }
+
+    /// Simulated time since the backend was created
+    pub fn now(&self) -> Duration {
+        self.state().clock
+    }
+
+    /// Move simulated time forward, draining batteries and playing scripts
+    pub fn advance(&self, elapsed: Duration) -> Result<()> {
+        self.state().advance(elapsed)
+    }
+
+    /// Another host claims the audio
+    ///
+    /// The device follows only while automatic switching is enabled.
+    /// Returns whether it switched.
+    pub fn switch_host(&self, address: &str, host: &str) -> Result<bool> {
+        let mut state = self.state();
+        let report = state
+            .device_mut(address)?
+            .play(SimulatedEvent::SwitchHost(host.to_string()));
+        let switched = report.is_some();
+        state.notify(address, report.into_iter().collect())?;
+        Ok(switched)
+    }
+
+    /// Inject `fault` into the link of the device at `address`
+    pub fn inject_fault(&self, address: &str, fault: Fault) -> Result<()> {
+        let mut state = self.state();
+        if fault == Fault::Disconnect {
+            return state.disconnect(address);
+        }
+        let device = state.device_mut(address)?;
+        match fault {
+            Fault::Disconnect => {}
+            Fault::RefuseConnections(refuse) => device.refuse_connections = refuse,
+            Fault::CorruptCrc(count) => device.corrupt_next = count,
+            Fault::Latency(latency) => device.latency = latency,
+        }
+        Ok(())
+    }
+
+    /// Remove and return the notifications that are due, oldest first
+    pub fn take_notifications(&self) -> Vec<(String, Vec<u8>)> {
+        let mut state = self.state();
+        let clock = state.clock;
+        let (due, pending): (VecDeque<_>, VecDeque<_>) =
+            state.notifications.drain(..).partition(|n| n.due <= clock);
+        state.notifications = pending;
+        due.into_iter().map(|n| (n.address, n.frame)).collect()
+    }
+
+    /// Spawn a task moving simulated time along with real time
+    ///
+    /// Every `period` the task advances simulated time by the real time
+    /// elapsed, which also sends the notifications that became due.
+    pub fn run(&self, period: Duration) -> JoinHandle<()> {
+        let backend = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            let mut last = Instant::now();
+            loop {
+                ticker.tick().await;
+                let now = Instant::now();
+                if let Err(err) = backend.advance(now - last) {
+                    log::warn!("simulation stopped: {}", err);
+                    break;
+                }
+                last = now;
+            }
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, SimulatorState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn check_aap(service_uuid: u128, char_uuid: u128) -> Result<()> {
+    if service_uuid == AAP_SERVICE_UUID && char_uuid == AAP_CHARACTERISTIC_UUID {
+        Ok(())
+    } else {
+        Err(Error::BluetoothError("unknown characteristic".to_string()))
+    }
+}
+
+impl BluetoothBackend for SimulatedBackend {
+    fn start_scan(&mut self) -> Result<()> {
+        self.state().scanning = true;
+        Ok(())
+    }
+
+    fn stop_scan(&mut self) -> Result<()> {
+        let mut state = self.state();
+        state.scanning = false;
+        state.scanners.clear();
+        Ok(())
+    }
+
+    fn connect(&mut self, address: &str) -> Result<()> {
+        self.state().connect(address)
+    }
+
+    fn disconnect(&mut self, address: &str) -> Result<()> {
+        self.state().disconnect(address)
+    }
+
+    fn write_characteristic(
+        &mut self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+        data: &[u8],
+    ) -> Result<()> {
+        self.state().write(address, service_uuid, char_uuid, data)
+    }
+
+    fn read_characteristic(
+        &mut self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> Result<Vec<u8>> {
+        self.state().read(address, service_uuid, char_uuid)
+    }
+
+    fn enable_notifications(
+        &mut self,
+        address: &str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> Result<()> {
+        self.state()
+            .enable_notifications(address, service_uuid, char_uuid)
+    }
+}
+
+impl AsyncBluetoothBackend for SimulatedBackend {
+    fn scan(&self) -> BackendFuture<'_, ScanStream> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut state = self.state();
+        state.scanning = true;
+        for device in state.devices.values() {
+            let _ = sender.send(device.advertisement());
+        }
+        state.scanners.push(sender);
+        Box::pin(async move { Ok(channel_stream(receiver)) })
+    }
+
+    fn stop_scan(&self) -> BackendFuture<'_, ()> {
+        let mut state = self.state();
+        state.scanning = false;
+        state.scanners.clear();
+        Box::pin(async { Ok(()) })
+    }
+
+    fn connect<'a>(&'a self, address: &'a str) -> BackendFuture<'a, ()> {
+        let result = self.state().connect(address);
+        Box::pin(async move { result })
+    }
+
+    fn disconnect<'a>(&'a self, address: &'a str) -> BackendFuture<'a, ()> {
+        let result = self.state().disconnect(address);
+        Box::pin(async move { result })
+    }
+
+    fn write_characteristic<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+        data: &'a [u8],
+    ) -> BackendFuture<'a, ()> {
+        let result = self.state().write(address, service_uuid, char_uuid, data);
+        Box::pin(async move { result })
+    }
+
+    fn read_characteristic<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> BackendFuture<'a, Vec<u8>> {
+        let result = self.state().read(address, service_uuid, char_uuid);
+        Box::pin(async move { result })
+    }
+
+    fn notifications<'a>(
+        &'a self,
+        address: &'a str,
+        service_uuid: u128,
+        char_uuid: u128,
+    ) -> BackendFuture<'a, NotificationStream> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut state = self.state();
+        // Subscribe first so the initial reports go to the stream
+        let result = check_aap(service_uuid, char_uuid)
+            .and_then(|()| state.connected_mut(address).map(|_| ()))
+            .and_then(|()| {
+                state
+                    .subscribers
+                    .entry(address.to_string())
+                    .or_default()
+                    .push(sender);
+                state.enable_notifications(address, service_uuid, char_uuid)
+            });
+        drop(state);
+        Box::pin(async move { result.map(|()| channel_stream(receiver)) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::multipoint;
+    use futures_util::StreamExt;
+
+    fn write(backend: &mut SimulatedBackend, payload: Payload) -> Result<()> {
+        let frame = payload.to_message()?.serialize()?;
+        backend.write_characteristic("sim", AAP_SERVICE_UUID, AAP_CHARACTERISTIC_UUID, &frame)
+    }
+
+    fn connected(device: SimulatedDevice) -> SimulatedBackend {
+        let mut backend = SimulatedBackend::new();
+        backend.add_device(device);
+        BluetoothBackend::connect(&mut backend, "sim").unwrap();
+        backend
+            .enable_notifications("sim", AAP_SERVICE_UUID, AAP_CHARACTERISTIC_UUID)
+            .unwrap();
+        backend.take_notifications();
+        backend
+    }
+
+    fn decoded(backend: &SimulatedBackend) -> Vec<Payload> {
+        backend
+            .take_notifications()
+            .iter()
+            .map(|(_, frame)| Message::parse(frame).unwrap().decode_payload().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_requires_connection() {
+        let mut backend = SimulatedBackend::new();
+        backend.add_device(SimulatedDevice::new(
+            "sim",
+            "AirPods",
+            DeviceModel::AirPodsProGen2,
+        ));
+        assert!(BluetoothBackend::connect(&mut backend, "missing").is_err());
+        assert_eq!(
+            write(&mut backend, multipoint::auto_switch_command(false)),
+            Err(Error::DeviceNotConnected)
+        );
+    }
+
+    #[test]
+    fn test_multipoint_commands() {
+        let mut backend = connected(
+            SimulatedDevice::new("sim", "AirPods", DeviceModel::AirPodsProGen2)
+                .with_hosts(&["phone"], Some("phone")),
+        );
+
+        write(&mut backend, multipoint::handoff_command("laptop")).unwrap();
+        let session = backend.device("sim").unwrap().session().clone();
+        assert_eq!(session.connected_devices, vec!["phone", "laptop"]);
+        assert_eq!(session.active_device.as_deref(), Some("laptop"));
+
+        write(&mut backend, multipoint::auto_switch_command(false)).unwrap();
+        assert!(!backend.switch_host("sim", "phone").unwrap());
+        write(&mut backend, multipoint::auto_switch_command(true)).unwrap();
+        assert!(backend.switch_host("sim", "phone").unwrap());
+        assert_eq!(backend.take_notifications().len(), 4);
+    }
+
+    #[test]
+    fn test_acks_and_battery_request() {
+        let mut backend = connected(SimulatedDevice::new(
+            "sim",
+            "AirPods",
+            DeviceModel::AirPodsMax,
+        ));
+
+        write(&mut backend, Payload::AncControl(AncMode::Transparency)).unwrap();
+        assert_eq!(
+            decoded(&backend),
+            vec![Payload::AncControl(AncMode::Transparency)]
+        );
+
+        let request = Message::new(MessageType::BatteryStatus, Vec::new())
+            .serialize()
+            .unwrap();
+        BluetoothBackend::write_characteristic(
+            &mut backend,
+            "sim",
+            AAP_SERVICE_UUID,
+            AAP_CHARACTERISTIC_UUID,
+            &request,
+        )
+        .unwrap();
+        assert!(matches!(decoded(&backend)[..], [Payload::BatteryStatus(_)]));
+    }
+
+    #[test]
+    fn test_drain_and_script() {
+        let backend = connected(
+            SimulatedDevice::new("sim", "AirPods", DeviceModel::AirPodsProGen2)
+                .with_drain(60.0)
+                .with_event(
+                    Duration::from_secs(90),
+                    SimulatedEvent::EarDetection(EarDetectionState::RightEarIn),
+                )
+                .with_event(Duration::from_secs(150), SimulatedEvent::Disconnect),
+        );
+
+        backend.advance(Duration::from_secs(30)).unwrap();
+        assert!(decoded(&backend).is_empty());
+        backend.advance(Duration::from_secs(90)).unwrap();
+        let reports = decoded(&backend);
+        assert_eq!(reports.len(), 2);
+        assert!(matches!(&reports[0], Payload::BatteryStatus(b) if b.left_bud == 98));
+        assert_eq!(
+            reports[1],
+            Payload::EarDetection(EarDetectionState::RightEarIn)
+        );
+
+        backend.advance(Duration::from_secs(60)).unwrap();
+        assert!(!backend.device("sim").unwrap().is_connected());
+    }
+
+    #[test]
+    fn test_faults() {
+        let mut backend = connected(SimulatedDevice::new(
+            "sim",
+            "AirPods",
+            DeviceModel::AirPods3,
+        ));
+
+        backend.inject_fault("sim", Fault::CorruptCrc(1)).unwrap();
+        backend
+            .inject_fault("sim", Fault::Latency(Duration::from_secs(1)))
+            .unwrap();
+        backend.switch_host("sim", "phone").unwrap();
+        backend.switch_host("sim", "laptop").unwrap();
+        assert!(backend.take_notifications().is_empty());
+        backend.advance(Duration::from_secs(1)).unwrap();
+        let frames = backend.take_notifications();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(Message::parse(&frames[0].1), Err(Error::CrcMismatch));
+        assert!(Message::parse(&frames[1].1).is_ok());
+
+        backend.inject_fault("sim", Fault::Disconnect).unwrap();
+        assert_eq!(
+            write(&mut backend, Payload::AncControl(AncMode::Off)),
+            Err(Error::DeviceNotConnected)
+        );
+        backend
+            .inject_fault("sim", Fault::RefuseConnections(true))
+            .unwrap();
+        assert!(BluetoothBackend::connect(&mut backend, "sim").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_notification_stream() {
+        let backend = SimulatedBackend::new();
+        backend.add_device(
+            SimulatedDevice::new("sim", "AirPods", DeviceModel::AirPodsProGen2)
+                .with_event(Duration::from_secs(5), SimulatedEvent::Disconnect),
+        );
+        AsyncBluetoothBackend::connect(&backend, "sim")
+            .await
+            .unwrap();
+        let mut frames = backend
+            .notifications("sim", AAP_SERVICE_UUID, AAP_CHARACTERISTIC_UUID)
+            .await
+            .unwrap();
+
+        let frame = frames.next().await.unwrap();
+        assert!(Message::parse(&frame).unwrap().decode_payload().is_ok());
+        let frame = Payload::AncControl(AncMode::Off)
+            .to_message()
+            .unwrap()
+            .serialize()
+            .unwrap();
+        AsyncBluetoothBackend::write_characteristic(
+            &backend,
+            "sim",
+            AAP_SERVICE_UUID,
+            AAP_CHARACTERISTIC_UUID,
+            &frame,
+        )
+        .await
+        .unwrap();
+        backend.advance(Duration::from_secs(5)).unwrap();
+
+        let mut rest = Vec::new();
+        while let Some(frame) = frames.next().await {
+            rest.push(Message::parse(&frame).unwrap().decode_payload().unwrap());
+        }
+        assert_eq!(rest.last(), Some(&Payload::AncControl(AncMode::Off)));
+        assert!(backend.take_notifications().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_scan_stream() {
+        let backend = SimulatedBackend::new();
+        backend.add_device(
+            SimulatedDevice::new("a", "AirPods", DeviceModel::AirPods3).with_drain(10.0),
+        );
+        let mut devices = AsyncBluetoothBackend::scan(&backend).await.unwrap();
+        assert!(backend.is_scanning());
+        assert_eq!(devices.next().await.unwrap().address, "a");
+
+        backend.add_device(
+            SimulatedDevice::new("b", "AirPods Max", DeviceModel::AirPodsMax).with_rssi(-70),
+        );
+        backend.set_rssi("a", -30).unwrap();
+        let seen: Vec<(String, i32)> = devices
+            .by_ref()
+            .take(2)
+            .map(|device| (device.address, device.rssi))
+            .collect()
+            .await;
+        assert_eq!(seen, vec![("b".to_string(), -70), ("a".to_string(), -30)]);
+
+        BluetoothBackend::connect(&mut backend.clone(), "a").unwrap();
+        backend.advance(Duration::from_secs(3600)).unwrap();
+        let pairing = devices.next().await.unwrap().proximity_pairing().unwrap();
+        assert_eq!(pairing.model, Some(DeviceModel::AirPods3));
+        assert_eq!((pairing.left, pairing.case), (Some(90), Some(100)));
+
+        AsyncBluetoothBackend::stop_scan(&backend).await.unwrap();
+        assert!(devices.next().await.is_none());
+    }
+}
@@ -1,7 +1,12 @@
+use crate::models::{EarDetectionState, MultipointInfo};
+use crate::payload::Payload;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub use crate::models::AncMode;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DeviceState {
+    #[default]
     Disconnected,
     Connecting,
     Connected,
@@ -9,7 +14,7 @@ pub enum DeviceState {
     Error,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BatteryInfo {
     pub left_bud: u8,
     pub right_bud: u8,
@@ -17,33 +22,154 @@ pub struct BatteryInfo {
     pub is_charging: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-pub enum AncMode {
-    Off,
-    Active,
-    Transparency,
-    Adaptive,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Live snapshot of a device's state, updated from decoded messages
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DeviceStateInfo {
+    /// Connection state
     pub connection_state: DeviceState,
+    /// Last battery report
     pub battery: Option<BatteryInfo>,
+    /// Noise control mode
     pub anc_mode: Option<AncMode>,
+    /// Which buds are in ear
+    pub ear_detection: Option<EarDetectionState>,
+    /// Firmware version
     pub firmware_version: Option<String>,
+    /// Multipoint session
+    #[serde(default)]
+    pub multipoint: Option<MultipointInfo>,
+    /// When the snapshot last changed, in milliseconds
     pub last_updated: u64,
 }
 
+/// One field that differs between two snapshots
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StateChange {
+    /// [`DeviceStateInfo::connection_state`] changed
+    Connection {
+        /// Old value
+        from: DeviceState,
+        /// New value
+        to: DeviceState,
+    },
+    /// [`DeviceStateInfo::battery`] changed
+    Battery {
+        /// Old value
+        from: Option<BatteryInfo>,
+        /// New value
+        to: Option<BatteryInfo>,
+    },
+    /// [`DeviceStateInfo::anc_mode`] changed
+    AncMode {
+        /// Old value
+        from: Option<AncMode>,
+        /// New value
+        to: Option<AncMode>,
+    },
+    /// [`DeviceStateInfo::ear_detection`] changed
+    EarDetection {
+        /// Old value
+        from: Option<EarDetectionState>,
+        /// New value
+        to: Option<EarDetectionState>,
+    },
+    /// [`DeviceStateInfo::firmware_version`] changed
+    FirmwareVersion {
+        /// Old value
+        from: Option<String>,
+        /// New value
+        to: Option<String>,
+    },
+    /// [`DeviceStateInfo::multipoint`] changed
+    Multipoint {
+        /// Old value
+        from: Option<MultipointInfo>,
+        /// New value
+        to: Option<MultipointInfo>,
+    },
+}
+
 impl DeviceStateInfo {
     pub fn new() -> Self {
         Self {
             connection_state: DeviceState::Disconnected,
             battery: None,
             anc_mode: None,
+            ear_detection: None,
             firmware_version: None,
+            multipoint: None,
             last_updated: 0,
         }
     }
+
+    /// Update the snapshot from a decoded payload
+    ///
+    /// Returns whether the payload carried state tracked by the snapshot;
+    /// `last_updated` is only touched in that case.
+    pub fn apply(&mut self, payload: &Payload) -> bool {
+        match payload {
+            Payload::BatteryStatus(info) => self.battery = Some(info.clone()),
+            Payload::AncControl(mode) => self.anc_mode = Some(*mode),
+            Payload::EarDetection(state) => self.ear_detection = Some(*state),
+            Payload::FirmwareInfo(version) => self.firmware_version = Some(version.clone()),
+            Payload::MultipointControl(info) => self.multipoint = Some(info.clone()),
+            _ => return false,
+        }
+        self.touch();
+        true
+    }
+
+    /// Record a new connection state
+    pub fn set_connection_state(&mut self, state: DeviceState) {
+        self.connection_state = state;
+        self.touch();
+    }
+
+    /// Fields that changed going from `self` to `newer`, ignoring `last_updated`
+    pub fn diff(&self, newer: &DeviceStateInfo) -> Vec<StateChange> {
+        let mut changes = Vec::new();
+        if self.connection_state != newer.connection_state {
+            changes.push(StateChange::Connection {
+                from: self.connection_state,
+                to: newer.connection_state,
+            });
+        }
+        if self.battery != newer.battery {
+            changes.push(StateChange::Battery {
+                from: self.battery.clone(),
+                to: newer.battery.clone(),
+            });
+        }
+        if self.anc_mode != newer.anc_mode {
+            changes.push(StateChange::AncMode {
+                from: self.anc_mode,
+                to: newer.anc_mode,
+            });
+        }
+        if self.ear_detection != newer.ear_detection {
+            changes.push(StateChange::EarDetection {
+                from: self.ear_detection,
+                to: newer.ear_detection,
+            });
+        }
+        if self.firmware_version != newer.firmware_version {
+            changes.push(StateChange::FirmwareVersion {
+                from: self.firmware_version.clone(),
+                to: newer.firmware_version.clone(),
+            });
+        }
+        if self.multipoint != newer.multipoint {
+            changes.push(StateChange::Multipoint {
+                from: self.multipoint.clone(),
+                to: newer.multipoint.clone(),
+            });
+        }
+        changes
+    }
+
+    fn touch(&mut self) {
+        self.last_updated = chrono::Utc::now().timestamp_millis() as u64;
+    }
 }
 
 impl Default for DeviceStateInfo {
@@ -51,3 +177,43 @@ impl Default for DeviceStateInfo {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_updates_snapshot() {
+        let mut info = DeviceStateInfo::new();
+        assert!(info.apply(&Payload::AncControl(AncMode::Transparency)));
+        assert!(info.apply(&Payload::FirmwareInfo("6A300".to_string())));
+        assert!(!info.apply(&Payload::AdaptiveTransparency(true)));
+
+        assert_eq!(info.anc_mode, Some(AncMode::Transparency));
+        assert_eq!(info.firmware_version.as_deref(), Some("6A300"));
+        assert!(info.last_updated > 0);
+    }
+
+    #[test]
+    fn test_diff_between_snapshots() {
+        let before = DeviceStateInfo::new();
+        let mut after = before.clone();
+        after.set_connection_state(DeviceState::Connecting);
+        after.apply(&Payload::AncControl(AncMode::Active));
+
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                StateChange::Connection {
+                    from: DeviceState::Disconnected,
+                    to: DeviceState::Connecting,
+                },
+                StateChange::AncMode {
+                    from: None,
+                    to: Some(AncMode::Active),
+                },
+            ]
+        );
+        assert!(after.diff(&after.clone()).is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+//! Connection state machine with validated transitions
+
+use crate::error::{Error, Result};
+use crate::state::DeviceState;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+
+/// Transitions kept per device before the oldest are dropped
+pub const HISTORY_LIMIT: usize = 64;
+
+/// A completed change of connection state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateTransition {
+    /// State left
+    pub from: DeviceState,
+    /// State entered
+    pub to: DeviceState,
+    /// When the transition happened, in milliseconds
+    pub timestamp: u64,
+}
+
+/// Callback run when a state is entered or exited
+pub type TransitionHook = Arc<dyn Fn(&StateTransition) + Send + Sync>;
+
+impl DeviceState {
+    /// Whether the state machine allows moving from `self` to `next`
+    ///
+    /// ```text
+    /// Disconnected -> Connecting
+    /// Connecting   -> Connected | Disconnected | Error
+    /// Connected    -> Disconnecting | Disconnected | Error
+    /// Disconnecting -> Disconnected | Error
+    /// Error        -> Disconnected | Connecting
+    /// ```
+    pub fn can_transition_to(self, next: DeviceState) -> bool {
+        use DeviceState::*;
+        matches!(
+            (self, next),
+            (Disconnected, Connecting)
+                | (Connecting, Connected | Disconnected | Error)
+                | (Connected, Disconnecting | Disconnected | Error)
+                | (Disconnecting, Disconnected | Error)
+                | (Error, Disconnected | Connecting)
+        )
+    }
+}
+
+/// Tracks a device's connection state and rejects illegal transitions
+///
+/// Exit hooks for the old state run before the state changes and entry hooks
+/// for the new state run after it, both with the completed transition.
+/// Hooks are not serialized.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ConnectionStateMachine {
+    state: DeviceState,
+    history: VecDeque<StateTransition>,
+    #[serde(skip)]
+    enter_hooks: Vec<(DeviceState, TransitionHook)>,
+    #[serde(skip)]
+    exit_hooks: Vec<(DeviceState, TransitionHook)>,
+}
+
+impl ConnectionStateMachine {
+    /// Machine starting out `Disconnected`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current state
+    pub fn state(&self) -> &DeviceState {
+        &self.state
+    }
+
+    /// Move to `next`, failing with `InvalidState` if the transition is illegal
+    pub fn transition(&mut self, next: DeviceState) -> Result<StateTransition> {
+        if !self.state.can_transition_to(next) {
+            log::debug!("rejected state transition {:?} -> {:?}", self.state, next);
+            return Err(Error::InvalidState);
+        }
+
+        let transition = StateTransition {
+            from: self.state,
+            to: next,
+            timestamp: chrono::Utc::now().timestamp_millis() as u64,
+        };
+
+        Self::run_hooks(&self.exit_hooks, transition.from, &transition);
+        self.state = next;
+        if self.history.len() == HISTORY_LIMIT {
+            self.history.pop_front();
+        }
+        self.history.push_back(transition);
+        Self::run_hooks(&self.enter_hooks, transition.to, &transition);
+
+        Ok(transition)
+    }
+
+    /// Register a hook run after entering `state`
+    pub fn on_enter(&mut self, state: DeviceState, hook: TransitionHook) {
+        self.enter_hooks.push((state, hook));
+    }
+
+    /// Register a hook run before leaving `state`
+    pub fn on_exit(&mut self, state: DeviceState, hook: TransitionHook) {
+        self.exit_hooks.push((state, hook));
+    }
+
+    /// Past transitions, oldest first, up to `HISTORY_LIMIT`
+    pub fn history(&self) -> impl Iterator<Item = &StateTransition> {
+        self.history.iter()
+    }
+
+    fn run_hooks(
+        hooks: &[(DeviceState, TransitionHook)],
+        state: DeviceState,
+        transition: &StateTransition,
+    ) {
+        for (_, hook) in hooks.iter().filter(|(s, _)| *s == state) {
+            hook(transition);
+        }
+    }
+}
+
+impl fmt::Debug for ConnectionStateMachine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConnectionStateMachine")
+            .field("state", &self.state)
+            .field("history", &self.history)
+            .field("enter_hooks", &self.enter_hooks.len())
+            .field("exit_hooks", &self.exit_hooks.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_legal_connection_cycle() {
+        let mut machine = ConnectionStateMachine::new();
+        for next in [
+            DeviceState::Connecting,
+            DeviceState::Connected,
+            DeviceState::Disconnecting,
+            DeviceState::Disconnected,
+        ] {
+            machine.transition(next).unwrap();
+        }
+        assert_eq!(*machine.state(), DeviceState::Disconnected);
+        assert_eq!(machine.history().count(), 4);
+    }
+
+    #[test]
+    fn test_illegal_transition_rejected() {
+        let mut machine = ConnectionStateMachine::new();
+        assert_eq!(
+            machine.transition(DeviceState::Disconnecting),
+            Err(Error::InvalidState)
+        );
+        assert_eq!(
+            machine.transition(DeviceState::Disconnected),
+            Err(Error::InvalidState)
+        );
+        assert_eq!(*machine.state(), DeviceState::Disconnected);
+        assert_eq!(machine.history().count(), 0);
+    }
+
+    #[test]
+    fn test_hooks_run_in_order() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut machine = ConnectionStateMachine::new();
+
+        let log = Arc::clone(&calls);
+        machine.on_exit(
+            DeviceState::Connecting,
+            Arc::new(move |t| log.lock().unwrap().push(format!("exit {:?}", t.from))),
+        );
+        let log = Arc::clone(&calls);
+        machine.on_enter(
+            DeviceState::Connected,
+            Arc::new(move |t| log.lock().unwrap().push(format!("enter {:?}", t.to))),
+        );
+
+        machine.transition(DeviceState::Connecting).unwrap();
+        assert!(calls.lock().unwrap().is_empty());
+        machine.transition(DeviceState::Connected).unwrap();
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec!["exit Connecting".to_string(), "enter Connected".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut machine = ConnectionStateMachine::new();
+        for _ in 0..HISTORY_LIMIT {
+            machine.transition(DeviceState::Connecting).unwrap();
+            machine.transition(DeviceState::Disconnected).unwrap();
+        }
+        assert_eq!(machine.history().count(), HISTORY_LIMIT);
+        assert_eq!(
+            machine.history().next().unwrap().to,
+            DeviceState::Connecting
+        );
+    }
+}
@@ -0,0 +1,464 @@
+//! Persistent device registry
+//!
+//! Known devices are kept in a JSON file under the XDG data directory so
+//! they survive restarts. Pairing keys are encrypted with a key supplied by
+//! the caller; everything else is stored in the clear.
+
+use crate::crypto::{Crypto, SecureKey};
+use crate::device::{Device, DeviceModel};
+use crate::error::{Error, Result};
+use crate::firmware_version_analyzer::FirmwareVersionAnalyzer;
+use crate::models::AncMode;
+use crate::state::DeviceStateInfo;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File name of the registry inside the store directory
+pub const STORE_FILE: &str = "devices.json";
+
+/// Rewrites a registry document from one schema version to the next
+pub type Migration = fn(&mut Value) -> Result<()>;
+
+/// Migrations in order; `MIGRATIONS[n]` upgrades version `n + 1` to `n + 2`
+const MIGRATIONS: &[Migration] = &[];
+
+/// Schema version written by this build
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+/// Per-device user preferences
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DevicePreferences {
+    /// Connect when the device is seen
+    pub auto_connect: bool,
+    /// Noise control mode to restore after connecting
+    pub preferred_anc_mode: Option<AncMode>,
+    /// Pause playback when a bud is taken out
+    pub pause_on_ear_removal: bool,
+}
+
+impl Default for DevicePreferences {
+    fn default() -> Self {
+        Self {
+            auto_connect: true,
+            preferred_anc_mode: None,
+            pause_on_ear_removal: true,
+        }
+    }
+}
+
+/// Pairing key material, by key name (e.g. `irk`, `enc_key`)
+pub type PairingKeys = BTreeMap<String, Vec<u8>>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct EncryptedBlob {
+    #[serde(with = "hex_bytes")]
+    nonce: Vec<u8>,
+    #[serde(with = "hex_bytes")]
+    ciphertext: Vec<u8>,
+}
+
+mod hex_bytes {
+    use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        crate::dissector::parse_hex(&hex).map_err(D::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct StoredDevice {
+    id: String,
+    name: String,
+    model: DeviceModel,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+    #[serde(default)]
+    last_state: DeviceStateInfo,
+    #[serde(default)]
+    preferences: DevicePreferences,
+    #[serde(default)]
+    pairing_keys: Option<EncryptedBlob>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreFile {
+    version: u32,
+    devices: Vec<StoredDevice>,
+}
+
+/// Default store directory: `$XDG_DATA_HOME/librepods`, falling back to
+/// `$HOME/.local/share/librepods`
+pub fn default_store_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_DATA_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(dir).join("librepods"));
+    }
+    std::env::var_os("HOME")
+        .filter(|h| !h.is_empty())
+        .map(|home| PathBuf::from(home).join(".local/share/librepods"))
+        .ok_or_else(|| Error::ConfigError("neither XDG_DATA_HOME nor HOME is set".to_string()))
+}
+
+/// Devices known across restarts
+///
+/// Changes are kept in memory until [`DeviceStore::flush`], which replaces
+/// the file atomically so a crash never leaves a half-written registry.
+pub struct DeviceStore {
+    path: PathBuf,
+    key: SecureKey,
+    devices: BTreeMap<String, StoredDevice>,
+}
+
+impl DeviceStore {
+    /// Open the store in `dir`, creating it on first flush
+    ///
+    /// `key` must be 32 bytes and is used to encrypt pairing keys. Older
+    /// schema versions are migrated on load; newer ones are refused with
+    /// `VersionMismatch`.
+    pub fn open(dir: impl AsRef<Path>, key: SecureKey) -> Result<Self> {
+        if key.as_ref().len() != 32 {
+            return Err(Error::CryptoError);
+        }
+        let path = dir.as_ref().join(STORE_FILE);
+        let devices = if path.exists() {
+            let document: Value = serde_json::from_slice(&fs::read(&path)?)
+                .map_err(|e| Error::ParseError(e.to_string()))?;
+            Self::decode(document, MIGRATIONS)?
+                .devices
+                .into_iter()
+                .map(|d| (d.id.clone(), d))
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
+        Ok(Self { path, key, devices })
+    }
+
+    /// Open the store in [`default_store_dir`]
+    pub fn open_default(key: SecureKey) -> Result<Self> {
+        Self::open(default_store_dir()?, key)
+    }
+
+    /// Path of the registry file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Number of stored devices
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Whether no device is stored
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Whether a device is stored
+    pub fn contains(&self, id: &str) -> bool {
+        self.devices.contains_key(id)
+    }
+
+    /// Record a device's identity, metadata and last-known state
+    ///
+    /// Preferences and pairing keys already stored for it are kept.
+    pub fn save_device(&mut self, device: &Device) {
+        let stored = self
+            .devices
+            .entry(device.id().to_string())
+            .or_insert_with(|| StoredDevice {
+                id: device.id().to_string(),
+                name: String::new(),
+                model: device.model(),
+                metadata: HashMap::new(),
+                last_state: DeviceStateInfo::new(),
+                preferences: DevicePreferences::default(),
+                pairing_keys: None,
+            });
+        stored.name = device.name().to_string();
+        stored.model = device.model();
+        stored.metadata = device.metadata().clone();
+        stored.last_state = device.snapshot();
+    }
+
+    /// Rebuild all stored devices, disconnected, with their last-known state
+    pub fn restore_devices(&self) -> Vec<Device> {
+        let analyzer = FirmwareVersionAnalyzer::new();
+        self.devices
+            .values()
+            .map(|stored| {
+                let mut device = Device::new(stored.id.clone(), stored.name.clone(), stored.model);
+                for (key, value) in &stored.metadata {
+                    device.set_metadata(key.clone(), value.clone());
+                }
+                if let Some(version) = &stored.last_state.firmware_version {
+                    device.refine_capabilities(version, &analyzer);
+                }
+                device.restore_snapshot(stored.last_state.clone());
+                device
+            })
+            .collect()
+    }
+
+    /// Forget a device, returning whether it was stored
+    pub fn remove(&mut self, id: &str) -> bool {
+        self.devices.remove(id).is_some()
+    }
+
+    /// Preferences of a stored device
+    pub fn preferences(&self, id: &str) -> Option<&DevicePreferences> {
+        self.devices.get(id).map(|d| &d.preferences)
+    }
+
+    /// Set preferences for a stored device
+    pub fn set_preferences(&mut self, id: &str, preferences: DevicePreferences) -> Result<()> {
+        self.stored_mut(id)?.preferences = preferences;
+        Ok(())
+    }
+
+    /// Encrypt and store pairing keys for a stored device
+    pub fn set_pairing_keys(&mut self, id: &str, keys: &PairingKeys) -> Result<()> {
+        let plaintext = serde_json::to_vec(keys).map_err(|e| Error::ParseError(e.to_string()))?;
+        let nonce = Crypto::generate_nonce();
+        let ciphertext = Crypto::encrypt(self.key.as_ref(), &plaintext, &nonce)?;
+        self.stored_mut(id)?.pairing_keys = Some(EncryptedBlob {
+            nonce: nonce.to_vec(),
+            ciphertext,
+        });
+        Ok(())
+    }
+
+    /// Decrypt the pairing keys of a device
+    ///
+    /// Fails with `CryptoError` if the store was opened with another key.
+    pub fn pairing_keys(&self, id: &str) -> Result<Option<PairingKeys>> {
+        let Some(blob) = self.devices.get(id).and_then(|d| d.pairing_keys.as_ref()) else {
+            return Ok(None);
+        };
+        let plaintext = Crypto::decrypt(self.key.as_ref(), &blob.ciphertext, &blob.nonce)?;
+        serde_json::from_slice(&plaintext)
+            .map(Some)
+            .map_err(|e| Error::ParseError(e.to_string()))
+    }
+
+    /// Write the registry to disk atomically
+    pub fn flush(&self) -> Result<()> {
+        let document = StoreFile {
+            version: SCHEMA_VERSION,
+            devices: self.devices.values().cloned().collect(),
+        };
+        let json =
+            serde_json::to_vec_pretty(&document).map_err(|e| Error::ParseError(e.to_string()))?;
+        write_atomic(&self.path, &json)
+    }
+
+    fn stored_mut(&mut self, id: &str) -> Result<&mut StoredDevice> {
+        self.devices
+            .get_mut(id)
+            .ok_or_else(|| Error::ConfigError(format!("unknown device: {}", id)))
+    }
+
+    fn decode(mut document: Value, migrations: &[Migration]) -> Result<StoreFile> {
+        let version = document
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| Error::ParseError("missing schema version".to_string()))?
+            as usize;
+        let current = migrations.len() + 1;
+        if version == 0 || version > current {
+            return Err(Error::VersionMismatch);
+        }
+
+        for (index, migration) in migrations.iter().enumerate().skip(version - 1) {
+            migration(&mut document)?;
+            document["version"] = Value::from(index as u64 + 2);
+        }
+
+        serde_json::from_value(document).map_err(|e| Error::ParseError(e.to_string()))
+    }
+}
+
+/// Replace `path` with `data` via a synced temporary file and a rename
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| Error::ConfigError(format!("invalid store path: {}", path.display())))?;
+    fs::create_dir_all(dir)?;
+
+    let tmp = path.with_extension("json.tmp");
+    {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)?;
+
+    #[cfg(unix)]
+    if let Ok(dir) = fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::Payload;
+    use crate::state::DeviceState;
+
+    fn key() -> SecureKey {
+        SecureKey::new(vec![7; 32])
+    }
+
+    fn device() -> Device {
+        let mut device = Device::new(
+            "AA:BB:CC:DD:EE:FF".to_string(),
+            "Office AirPods".to_string(),
+            DeviceModel::AirPodsProGen2,
+        );
+        device.set_metadata("color".to_string(), "white".to_string());
+        device.set_state(DeviceState::Connecting).unwrap();
+        device.set_state(DeviceState::Connected).unwrap();
+        device.apply_payload(&Payload::FirmwareInfo("5D134".to_string()));
+        device
+    }
+
+    #[test]
+    fn test_round_trip_across_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let keys = PairingKeys::from([("irk".to_string(), vec![1, 2, 3])]);
+
+        let mut store = DeviceStore::open(dir.path(), key()).unwrap();
+        store.save_device(&device());
+        store
+            .set_preferences(
+                "AA:BB:CC:DD:EE:FF",
+                DevicePreferences {
+                    preferred_anc_mode: Some(AncMode::Adaptive),
+                    ..DevicePreferences::default()
+                },
+            )
+            .unwrap();
+        store.set_pairing_keys("AA:BB:CC:DD:EE:FF", &keys).unwrap();
+        store.flush().unwrap();
+
+        let store = DeviceStore::open(dir.path(), key()).unwrap();
+        let restored = store.restore_devices();
+        assert_eq!(restored.len(), 1);
+        let device = &restored[0];
+        assert_eq!(device.name(), "Office AirPods");
+        assert_eq!(device.get_metadata("color"), Some("white"));
+        assert_eq!(*device.state(), DeviceState::Disconnected);
+        assert_eq!(
+            device.state_info().firmware_version.as_deref(),
+            Some("5D134")
+        );
+        assert!(!device.has_capability(crate::DeviceCapability::SpatialAudio));
+        assert_eq!(
+            store
+                .preferences("AA:BB:CC:DD:EE:FF")
+                .unwrap()
+                .preferred_anc_mode,
+            Some(AncMode::Adaptive)
+        );
+        assert_eq!(store.pairing_keys("AA:BB:CC:DD:EE:FF").unwrap(), Some(keys));
+    }
+
+    #[test]
+    fn test_pairing_keys_are_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = DeviceStore::open(dir.path(), key()).unwrap();
+        store.save_device(&device());
+        let secret = b"very-secret-irk".to_vec();
+        store
+            .set_pairing_keys(
+                "AA:BB:CC:DD:EE:FF",
+                &PairingKeys::from([("irk".to_string(), secret)]),
+            )
+            .unwrap();
+        store.flush().unwrap();
+
+        let raw = fs::read_to_string(store.path()).unwrap();
+        let secret_hex: String = b"very-secret-irk"
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        assert!(raw.contains("ciphertext"));
+        assert!(!raw.contains(&secret_hex));
+
+        let other = DeviceStore::open(dir.path(), SecureKey::new(vec![8; 32])).unwrap();
+        assert_eq!(
+            other.pairing_keys("AA:BB:CC:DD:EE:FF"),
+            Err(Error::CryptoError)
+        );
+    }
+
+    #[test]
+    fn test_flush_leaves_no_temporary_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = DeviceStore::open(dir.path().join("nested"), key()).unwrap();
+        store.save_device(&device());
+        store.flush().unwrap();
+
+        let entries: Vec<_> = fs::read_dir(dir.path().join("nested"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec![std::ffi::OsString::from(STORE_FILE)]);
+    }
+
+    #[test]
+    fn test_migrations_run_in_order() {
+        fn rename_devices(doc: &mut Value) -> Result<()> {
+            let devices = doc["known"].take();
+            doc["devices"] = devices;
+            Ok(())
+        }
+        fn add_model(doc: &mut Value) -> Result<()> {
+            for device in doc["devices"].as_array_mut().unwrap() {
+                device["model"] = Value::from("AirPodsMax");
+            }
+            Ok(())
+        }
+
+        let v1 = serde_json::json!({
+            "version": 1,
+            "known": [{ "id": "dev", "name": "Max" }],
+        });
+        let file = DeviceStore::decode(v1, &[rename_devices, add_model]).unwrap();
+        assert_eq!(file.version, 3);
+        assert_eq!(file.devices[0].model, DeviceModel::AirPodsMax);
+        assert_eq!(file.devices[0].preferences, DevicePreferences::default());
+    }
+
+    #[test]
+    fn test_newer_schema_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join(STORE_FILE),
+            format!(r#"{{"version": {}, "devices": []}}"#, SCHEMA_VERSION + 1),
+        )
+        .unwrap();
+        assert!(matches!(
+            DeviceStore::open(dir.path(), key()),
+            Err(Error::VersionMismatch)
+        ));
+    }
+}
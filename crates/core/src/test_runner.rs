@@ -101,14 +101,22 @@ impl TestSuite {
         report.push_str(&format!("  Failed: {}\n", self.failed_tests));
         report.push_str(&format!("  Skipped: {}\n", self.skipped_tests));
         report.push_str(&format!("Pass Rate: {:.1}%\n", self.get_pass_rate()));
-        report.push_str(&format!("Duration: {:.1}s\n\n", self.total_duration_ms as f32 / 1000.0));
+        report.push_str(&format!(
+            "Duration: {:.1}s\n\n",
+            self.total_duration_ms as f32 / 1000.0
+        ));
 
         let failed = self.get_tests_by_status(TestStatus::Failed);
         if !failed.is_empty() {
             report.push_str(&format!("Failed Tests: {}\n", failed.len()));
             for test in failed {
-                report.push_str(&format!("  {} - {}\n", test.test_name, 
-                    test.error_message.as_ref().unwrap_or(&"Unknown error".to_string())));
+                report.push_str(&format!(
+                    "  {} - {}\n",
+                    test.test_name,
+                    test.error_message
+                        .as_ref()
+                        .unwrap_or(&"Unknown error".to_string())
+                ));
             }
         }
 
@@ -118,7 +126,11 @@ impl TestSuite {
 
 impl Default for TestSuite {
     fn default() -> Self {
-        Self::new("suite-1".to_string(), "Test Suite 1".to_string(), TestSuiteType::Unit)
+        Self::new(
+            "suite-1".to_string(),
+            "Test Suite 1".to_string(),
+            TestSuiteType::Unit,
+        )
     }
 }
 
@@ -133,7 +145,12 @@ impl TestRunner {
         }
     }
 
-    pub fn create_suite(&mut self, suite_id: String, suite_name: String, test_type: TestSuiteType) -> &mut TestSuite {
+    pub fn create_suite(
+        &mut self,
+        suite_id: String,
+        suite_name: String,
+        test_type: TestSuiteType,
+    ) -> &mut TestSuite {
         let suite = TestSuite::new(suite_id.clone(), suite_name, test_type);
         self.suites.insert(suite_id.clone(), suite);
         self.suites.get_mut(&suite_id).unwrap()
@@ -181,12 +198,19 @@ impl TestRunner {
         summary.push_str(&format!("Total Tests: {}\n", self.get_total_tests()));
         summary.push_str(&format!("  Passed: {}\n", self.get_total_passed()));
         summary.push_str(&format!("  Failed: {}\n", self.get_total_failed()));
-        summary.push_str(&format!("Overall Pass Rate: {:.1}%\n\n", self.get_overall_pass_rate()));
+        summary.push_str(&format!(
+            "Overall Pass Rate: {:.1}%\n\n",
+            self.get_overall_pass_rate()
+        ));
 
         summary.push_str("Suites:\n");
         for suite in self.suites.values() {
-            summary.push_str(&format!("  {} ({:?}) - {:.1}%\n", 
-                suite.suite_name, suite.test_type, suite.get_pass_rate()));
+            summary.push_str(&format!(
+                "  {} ({:?}) - {:.1}%\n",
+                suite.suite_name,
+                suite.test_type,
+                suite.get_pass_rate()
+            ));
         }
 
         summary
@@ -205,14 +229,22 @@ mod tests {
 
     #[test]
     fn test_suite_creation() {
-        let suite = TestSuite::new("suite-1".to_string(), "Suite 1".to_string(), TestSuiteType::Unit);
+        let suite = TestSuite::new(
+            "suite-1".to_string(),
+            "Suite 1".to_string(),
+            TestSuiteType::Unit,
+        );
         assert_eq!(suite.suite_id, "suite-1");
         assert_eq!(suite.total_tests, 0);
     }
 
     #[test]
     fn test_add_test() {
-        let mut suite = TestSuite::new("suite-1".to_string(), "Suite 1".to_string(), TestSuiteType::Unit);
+        let mut suite = TestSuite::new(
+            "suite-1".to_string(),
+            "Suite 1".to_string(),
+            TestSuiteType::Unit,
+        );
         let test = TestCase {
             test_id: "test-1".to_string(),
             test_name: "Test 1".to_string(),
@@ -228,12 +260,20 @@ mod tests {
 
     #[test]
     fn test_pass_rate() {
-        let mut suite = TestSuite::new("suite-1".to_string(), "Suite 1".to_string(), TestSuiteType::Unit);
+        let mut suite = TestSuite::new(
+            "suite-1".to_string(),
+            "Suite 1".to_string(),
+            TestSuiteType::Unit,
+        );
         for i in 0..10 {
             let test = TestCase {
                 test_id: format!("test-{}", i),
                 test_name: format!("Test {}", i),
-                status: if i < 8 { TestStatus::Passed } else { TestStatus::Failed },
+                status: if i < 8 {
+                    TestStatus::Passed
+                } else {
+                    TestStatus::Failed
+                },
                 duration_ms: 100,
                 error_message: None,
                 stack_trace: None,
@@ -246,7 +286,11 @@ mod tests {
     #[test]
     fn test_runner() {
         let mut runner = TestRunner::new();
-        runner.create_suite("suite-1".to_string(), "Suite 1".to_string(), TestSuiteType::Unit);
+        runner.create_suite(
+            "suite-1".to_string(),
+            "Suite 1".to_string(),
+            TestSuiteType::Unit,
+        );
         let test = TestCase {
             test_id: "test-1".to_string(),
             test_name: "Test 1".to_string(),
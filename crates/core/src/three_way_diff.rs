@@ -31,7 +31,12 @@ pub struct ConflictRegion {
 pub struct ThreeWayDiffAnalyzer;
 
 impl ThreeWayDiffAnalyzer {
-    pub fn analyze(file_path: String, base: String, upstream: String, local: String) -> ThreeWayDiff {
+    pub fn analyze(
+        file_path: String,
+        base: String,
+        upstream: String,
+        local: String,
+    ) -> ThreeWayDiff {
         let base_lines: Vec<&str> = base.lines().collect();
         let upstream_lines: Vec<&str> = upstream.lines().collect();
         let local_lines: Vec<&str> = local.lines().collect();
@@ -49,11 +54,7 @@ impl ThreeWayDiffAnalyzer {
         }
     }
 
-    fn determine_merge_status(
-        base: &[&str],
-        upstream: &[&str],
-        local: &[&str],
-    ) -> MergeStatus {
+    fn determine_merge_status(base: &[&str], upstream: &[&str], local: &[&str]) -> MergeStatus {
         let base_upstream_same = base == upstream;
         let base_local_same = base == local;
         let upstream_local_same = upstream == local;
@@ -68,11 +69,7 @@ impl ThreeWayDiffAnalyzer {
         }
     }
 
-    fn detect_conflicts(
-        base: &[&str],
-        upstream: &[&str],
-        local: &[&str],
-    ) -> Vec<ConflictRegion> {
+    fn detect_conflicts(base: &[&str], upstream: &[&str], local: &[&str]) -> Vec<ConflictRegion> {
         let mut conflicts = Vec::new();
 
         let max_len = std::cmp::max(std::cmp::max(base.len(), upstream.len()), local.len());
@@ -82,7 +79,8 @@ impl ThreeWayDiffAnalyzer {
             let upstream_line = upstream.get(i).copied().unwrap_or("");
             let local_line = local.get(i).copied().unwrap_or("");
 
-            if base_line != upstream_line && base_line != local_line && upstream_line != local_line {
+            if base_line != upstream_line && base_line != local_line && upstream_line != local_line
+            {
                 conflicts.push(ConflictRegion {
                     start_line: i,
                     end_line: i,
@@ -99,9 +97,7 @@ impl ThreeWayDiffAnalyzer {
     pub fn can_auto_merge(diff: &ThreeWayDiff) -> bool {
         matches!(
             diff.merge_status,
-            MergeStatus::Clean
-                | MergeStatus::OnlyUpstreamModified
-                | MergeStatus::OnlyLocalModified
+            MergeStatus::Clean | MergeStatus::OnlyUpstreamModified | MergeStatus::OnlyLocalModified
         )
     }
 
@@ -114,12 +110,16 @@ impl ThreeWayDiffAnalyzer {
             MergeStatus::Clean => diff.base_content.clone(),
             MergeStatus::OnlyUpstreamModified => diff.upstream_content.clone(),
             MergeStatus::OnlyLocalModified => diff.local_content.clone(),
-            MergeStatus::BothModified => {
-                Self::merge_both_modified(&diff.base_content, &diff.upstream_content, &diff.local_content)
-            }
-            MergeStatus::Conflict => {
-                Self::generate_conflict_markers(&diff.base_content, &diff.upstream_content, &diff.local_content)
-            }
+            MergeStatus::BothModified => Self::merge_both_modified(
+                &diff.base_content,
+                &diff.upstream_content,
+                &diff.local_content,
+            ),
+            MergeStatus::Conflict => Self::generate_conflict_markers(
+                &diff.base_content,
+                &diff.upstream_content,
+                &diff.local_content,
+            ),
         }
     }
 
@@ -129,7 +129,10 @@ impl ThreeWayDiffAnalyzer {
         let local_lines: Vec<&str> = local.lines().collect();
 
         let mut result = String::new();
-        let max_len = std::cmp::max(std::cmp::max(base_lines.len(), upstream_lines.len()), local_lines.len());
+        let max_len = std::cmp::max(
+            std::cmp::max(base_lines.len(), upstream_lines.len()),
+            local_lines.len(),
+        );
 
         for i in 0..max_len {
             let base_line = base_lines.get(i).copied().unwrap_or("");
@@ -165,8 +168,14 @@ impl ThreeWayDiffAnalyzer {
         let mut report = String::new();
         report.push_str("=== THREE-WAY DIFF REPORT ===\n\n");
 
-        let clean = diffs.iter().filter(|d| d.merge_status == MergeStatus::Clean).count();
-        let conflicts = diffs.iter().filter(|d| d.merge_status == MergeStatus::Conflict).count();
+        let clean = diffs
+            .iter()
+            .filter(|d| d.merge_status == MergeStatus::Clean)
+            .count();
+        let conflicts = diffs
+            .iter()
+            .filter(|d| d.merge_status == MergeStatus::Conflict)
+            .count();
         let only_upstream = diffs
             .iter()
             .filter(|d| d.merge_status == MergeStatus::OnlyUpstreamModified)
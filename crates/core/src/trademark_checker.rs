@@ -67,8 +67,14 @@ impl TrademarkChecker {
 
     pub fn check_trademark_usage(&self, term: &str, context: &str) -> TrademarkUsage {
         if let Some(policy) = self.protected_trademarks.get(term) {
-            let compliant = policy.allowed_contexts.iter().any(|c| context.to_lowercase().contains(c))
-                && !policy.forbidden_contexts.iter().any(|c| context.to_lowercase().contains(c));
+            let compliant = policy
+                .allowed_contexts
+                .iter()
+                .any(|c| context.to_lowercase().contains(c))
+                && !policy
+                    .forbidden_contexts
+                    .iter()
+                    .any(|c| context.to_lowercase().contains(c));
 
             let reason = if compliant {
                 "Usage is compliant with trademark policy".to_string()
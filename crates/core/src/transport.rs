@@ -0,0 +1,442 @@
+//! Transports carrying AAP frames to a connected device
+//!
+//! The same frames travel either as writes and notifications on the AAP
+//! GATT characteristic ([`GattTransport`]) or over a classic L2CAP channel
+//! on PSM [`AACP_PSM`] ([`StreamTransport`]). The L2CAP channel speaks
+//! AACP: the host opens with a handshake packet, the device acknowledges
+//! it, and from then on every packet is an AACP data header followed by
+//! one AAP frame.
+//!
+//! AAP frames carry their own length, so data packets can be split out of
+//! a plain byte stream. That lets a Unix socket stand in for the L2CAP
+//! socket in tests.
+
+use crate::bluetooth::{AsyncBluetoothBackend, BackendFuture, NotificationStream};
+use crate::error::{Error, Result};
+use crate::frame::{FrameVersion, FRAME_CRC_LEN};
+use crate::protocol::{AAP_CHARACTERISTIC_UUID, AAP_SERVICE_UUID};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, WriteHalf};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::task::JoinHandle;
+
+/// L2CAP protocol/service multiplexer of the AACP channel
+pub const AACP_PSM: u16 = 0x1001;
+
+/// How long to wait for the device to acknowledge the handshake
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Packet the host opens the channel with
+pub const HANDSHAKE: [u8; 16] = [
+    0x00, 0x00, 0x04, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+const HANDSHAKE_HEADER: [u8; 4] = [0x00, 0x00, 0x04, 0x00];
+const HANDSHAKE_ACK_HEADER: [u8; 4] = [0x01, 0x00, 0x04, 0x00];
+const DATA_HEADER: [u8; 4] = [0x04, 0x00, 0x04, 0x00];
+const HEADER_LEN: usize = 4;
+const READ_CHUNK: usize = 1024;
+
+/// Channel carrying AAP frames to and from one device
+///
+/// Like [`AsyncBluetoothBackend`], the trait is object safe so callers can
+/// hold either transport as a `Box<dyn Transport>`.
+pub trait Transport: Send + Sync {
+    /// Send one serialized AAP frame
+    fn send<'a>(&'a self, frame: &'a [u8]) -> BackendFuture<'a, ()>;
+    /// Frames arriving from the device, ending when the channel closes
+    ///
+    /// Chunks may split or join frames; feed them to a
+    /// [`FrameDecoder`](crate::FrameDecoder).
+    fn frames(&self) -> BackendFuture<'_, NotificationStream>;
+    /// Close the channel, ending the frame stream
+    fn close(&self) -> BackendFuture<'_, ()>;
+}
+
+/// AAP over the GATT characteristic of a backend
+pub struct GattTransport {
+    backend: Arc<dyn AsyncBluetoothBackend>,
+    address: String,
+}
+
+impl GattTransport {
+    /// Connect to the device at `address`
+    pub async fn open(backend: Arc<dyn AsyncBluetoothBackend>, address: &str) -> Result<Self> {
+        backend.connect(address).await?;
+        Ok(Self {
+            backend,
+            address: address.to_string(),
+        })
+    }
+}
+
+impl Transport for GattTransport {
+    fn send<'a>(&'a self, frame: &'a [u8]) -> BackendFuture<'a, ()> {
+        self.backend.write_characteristic(
+            &self.address,
+            AAP_SERVICE_UUID,
+            AAP_CHARACTERISTIC_UUID,
+            frame,
+        )
+    }
+
+    fn frames(&self) -> BackendFuture<'_, NotificationStream> {
+        self.backend
+            .notifications(&self.address, AAP_SERVICE_UUID, AAP_CHARACTERISTIC_UUID)
+    }
+
+    fn close(&self) -> BackendFuture<'_, ()> {
+        self.backend.disconnect(&self.address)
+    }
+}
+
+/// Packet on the AACP channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AacpPacket {
+    /// Host opening the channel
+    Handshake,
+    /// Device accepting the handshake
+    HandshakeAck,
+    /// One serialized AAP frame
+    Data(Vec<u8>),
+}
+
+impl AacpPacket {
+    /// Bytes of the packet on the wire
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            AacpPacket::Handshake => HANDSHAKE.to_vec(),
+            AacpPacket::HandshakeAck => {
+                let mut packet = HANDSHAKE.to_vec();
+                packet[..HEADER_LEN].copy_from_slice(&HANDSHAKE_ACK_HEADER);
+                packet
+            }
+            AacpPacket::Data(frame) => [&DATA_HEADER[..], frame].concat(),
+        }
+    }
+}
+
+/// Splits a byte stream into AACP packets
+///
+/// Unlike [`FrameDecoder`](crate::FrameDecoder) it cannot resync: an
+/// unknown header leaves no way to find the next packet, so the error is
+/// final and the channel should be closed.
+#[derive(Debug, Default)]
+pub struct AacpDecoder {
+    buffer: Vec<u8>,
+}
+
+impl AacpDecoder {
+    /// Decoder with an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a chunk and decode every packet that is now complete
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<AacpPacket>> {
+        self.buffer.extend_from_slice(chunk);
+        let mut packets = Vec::new();
+        while let Some(packet) = self.decode_next()? {
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+
+    fn decode_next(&mut self) -> Result<Option<AacpPacket>> {
+        if self.buffer.len() < HEADER_LEN {
+            return Ok(None);
+        }
+        let header = &self.buffer[..HEADER_LEN];
+        let (packet, len) = if header == DATA_HEADER {
+            let version = FrameVersion::V1;
+            let frame = &self.buffer[HEADER_LEN..];
+            if frame.len() < version.header_len() {
+                return Ok(None);
+            }
+            let frame_len = version.header_len() + version.payload_len(frame) + FRAME_CRC_LEN;
+            if frame.len() < frame_len {
+                return Ok(None);
+            }
+            (
+                AacpPacket::Data(frame[..frame_len].to_vec()),
+                HEADER_LEN + frame_len,
+            )
+        } else if header == HANDSHAKE_HEADER || header == HANDSHAKE_ACK_HEADER {
+            if self.buffer.len() < HANDSHAKE.len() {
+                return Ok(None);
+            }
+            let packet = if header == HANDSHAKE_HEADER {
+                AacpPacket::Handshake
+            } else {
+                AacpPacket::HandshakeAck
+            };
+            (packet, HANDSHAKE.len())
+        } else {
+            return Err(Error::ParseError(format!(
+                "unknown AACP header {:02X?}",
+                header
+            )));
+        };
+        self.buffer.drain(..len);
+        Ok(Some(packet))
+    }
+}
+
+/// AAP over AACP on a connected stream socket
+///
+/// Normally an L2CAP socket on [`AACP_PSM`], but any async byte stream
+/// works. Opening performs the handshake; afterwards a task reads the
+/// socket and queues frames until [`Transport::frames`] takes them.
+pub struct StreamTransport<S> {
+    writer: tokio::sync::Mutex<WriteHalf<S>>,
+    frames: Mutex<Option<UnboundedReceiver<Vec<u8>>>>,
+    reader: JoinHandle<()>,
+}
+
+impl<S> StreamTransport<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// Handshake over `socket`, failing with `Timeout` if the device does
+    /// not answer within [`HANDSHAKE_TIMEOUT`]
+    pub async fn open(socket: S) -> Result<Self> {
+        let (mut reader, mut writer) = tokio::io::split(socket);
+        writer.write_all(&HANDSHAKE).await?;
+
+        let mut decoder = AacpDecoder::new();
+        let mut early = Vec::new();
+        let acknowledged = async {
+            let mut chunk = vec![0; READ_CHUNK];
+            loop {
+                let read = reader.read(&mut chunk).await?;
+                if read == 0 {
+                    return Err(Error::DeviceNotConnected);
+                }
+                let mut packets = decoder.push(&chunk[..read])?.into_iter();
+                if packets.any(|packet| packet == AacpPacket::HandshakeAck) {
+                    early.extend(packets);
+                    return Ok(());
+                }
+            }
+        };
+        tokio::time::timeout(HANDSHAKE_TIMEOUT, acknowledged)
+            .await
+            .map_err(|_| Error::Timeout)??;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        for packet in early {
+            if let AacpPacket::Data(frame) = packet {
+                let _ = sender.send(frame);
+            }
+        }
+        let reader = tokio::spawn(async move {
+            let mut chunk = vec![0; READ_CHUNK];
+            loop {
+                let read = match reader.read(&mut chunk).await {
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(err) => {
+                        log::debug!("AACP channel closed: {}", err);
+                        break;
+                    }
+                };
+                let packets = match decoder.push(&chunk[..read]) {
+                    Ok(packets) => packets,
+                    Err(err) => {
+                        log::warn!("AACP channel dropped: {}", err);
+                        break;
+                    }
+                };
+                for packet in packets {
+                    if let AacpPacket::Data(frame) = packet {
+                        if sender.send(frame).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            writer: tokio::sync::Mutex::new(writer),
+            frames: Mutex::new(Some(receiver)),
+            reader,
+        })
+    }
+}
+
+impl<S> Drop for StreamTransport<S> {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+impl<S> Transport for StreamTransport<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    fn send<'a>(&'a self, frame: &'a [u8]) -> BackendFuture<'a, ()> {
+        Box::pin(async move {
+            let packet = AacpPacket::Data(frame.to_vec()).encode();
+            self.writer.lock().await.write_all(&packet).await?;
+            Ok(())
+        })
+    }
+
+    /// The frames can only be taken once; later calls fail with `InvalidState`
+    fn frames(&self) -> BackendFuture<'_, NotificationStream> {
+        let frames = self
+            .frames
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        Box::pin(async move {
+            let frames = frames.ok_or(Error::InvalidState)?;
+            Ok(crate::bluetooth::channel_stream(frames))
+        })
+    }
+
+    fn close(&self) -> BackendFuture<'_, ()> {
+        Box::pin(async move {
+            self.reader.abort();
+            self.writer.lock().await.shutdown().await?;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AncMode;
+    use crate::payload::Payload;
+    use crate::protocol::Message;
+    use crate::simulator::{SimulatedBackend, SimulatedDevice};
+    use crate::DeviceModel;
+    use futures_util::StreamExt;
+
+    fn anc_frame(mode: AncMode) -> Vec<u8> {
+        Payload::AncControl(mode)
+            .to_message()
+            .unwrap()
+            .serialize()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_decoder_splits_packets() {
+        let frame = anc_frame(AncMode::Transparency);
+        let bytes = [
+            AacpPacket::HandshakeAck.encode(),
+            AacpPacket::Data(frame.clone()).encode(),
+            AacpPacket::Data(frame.clone()).encode(),
+        ]
+        .concat();
+
+        let mut decoder = AacpDecoder::new();
+        let (head, tail) = bytes.split_at(21);
+        assert_eq!(decoder.push(head).unwrap(), vec![AacpPacket::HandshakeAck]);
+        assert_eq!(
+            decoder.push(tail).unwrap(),
+            vec![AacpPacket::Data(frame.clone()), AacpPacket::Data(frame)]
+        );
+        assert!(matches!(
+            decoder.push(&[0x09, 0x00, 0x04, 0x00]),
+            Err(Error::ParseError(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stream_transport_over_socketpair() {
+        let (host, device) = tokio::net::UnixStream::pair().unwrap();
+        // The device end acknowledges the handshake and echoes every frame
+        let device = tokio::spawn(async move {
+            let (mut reader, mut writer) = device.into_split();
+            let mut decoder = AacpDecoder::new();
+            let mut chunk = [0; 256];
+            loop {
+                let read = reader.read(&mut chunk).await.unwrap();
+                if read == 0 {
+                    return;
+                }
+                for packet in decoder.push(&chunk[..read]).unwrap() {
+                    let reply = match packet {
+                        AacpPacket::Handshake => AacpPacket::HandshakeAck,
+                        other => other,
+                    };
+                    writer.write_all(&reply.encode()).await.unwrap();
+                }
+            }
+        });
+
+        let transport: Box<dyn Transport> = Box::new(StreamTransport::open(host).await.unwrap());
+        let mut frames = transport.frames().await.unwrap();
+        assert_eq!(transport.frames().await.err(), Some(Error::InvalidState));
+
+        transport.send(&anc_frame(AncMode::Off)).await.unwrap();
+        transport.send(&anc_frame(AncMode::Adaptive)).await.unwrap();
+        let mut decoder = crate::FrameDecoder::new();
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            let chunk = frames.next().await.unwrap();
+            received.extend(decoder.push(&chunk).into_iter().map(|m| m.unwrap()));
+        }
+        assert_eq!(
+            received,
+            vec![
+                Message::parse(&anc_frame(AncMode::Off)).unwrap(),
+                Message::parse(&anc_frame(AncMode::Adaptive)).unwrap(),
+            ]
+        );
+
+        transport.close().await.unwrap();
+        assert_eq!(frames.next().await, None);
+        device.await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(start_paused = true)]
+    async fn test_handshake_times_out() {
+        let (host, _device) = tokio::net::UnixStream::pair().unwrap();
+        assert_eq!(
+            StreamTransport::open(host).await.err(),
+            Some(Error::Timeout)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_gatt_transport() {
+        let simulator = SimulatedBackend::new();
+        simulator.add_device(SimulatedDevice::new(
+            "sim",
+            "AirPods Max",
+            DeviceModel::AirPodsMax,
+        ));
+        let transport = GattTransport::open(Arc::new(simulator.clone()), "sim")
+            .await
+            .unwrap();
+        let mut frames = transport.frames().await.unwrap();
+
+        transport
+            .send(&anc_frame(AncMode::Transparency))
+            .await
+            .unwrap();
+        assert_eq!(
+            simulator.device("sim").unwrap().anc_mode(),
+            AncMode::Transparency
+        );
+        let mut acked = false;
+        while let Some(frame) = frames.next().await {
+            if frame == anc_frame(AncMode::Transparency) {
+                acked = true;
+                break;
+            }
+        }
+        assert!(acked);
+
+        transport.close().await.unwrap();
+        assert_eq!(frames.next().await, None);
+    }
+}
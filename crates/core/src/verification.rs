@@ -74,7 +74,10 @@ impl VerificationReport {
     }
 
     pub fn get_results_by_type(&self, test_type: TestType) -> Vec<&VerificationResult> {
-        self.results.iter().filter(|r| r.test_type == test_type).collect()
+        self.results
+            .iter()
+            .filter(|r| r.test_type == test_type)
+            .collect()
     }
 
     pub fn get_failed_results(&self) -> Vec<&VerificationResult> {
@@ -116,8 +119,14 @@ impl VerificationReport {
         if !failed.is_empty() {
             report.push_str(&format!("Failed Tests: {}\n", failed.len()));
             for result in failed {
-                report.push_str(&format!("  {} - {}\n", result.test_name, 
-                    result.error_message.as_ref().unwrap_or(&"Unknown error".to_string())));
+                report.push_str(&format!(
+                    "  {} - {}\n",
+                    result.test_name,
+                    result
+                        .error_message
+                        .as_ref()
+                        .unwrap_or(&"Unknown error".to_string())
+                ));
             }
         }
 
@@ -142,7 +151,11 @@ impl Verifier {
         }
     }
 
-    pub fn create_report(&mut self, report_id: String, sprint_id: String) -> &mut VerificationReport {
+    pub fn create_report(
+        &mut self,
+        report_id: String,
+        sprint_id: String,
+    ) -> &mut VerificationReport {
         let report = VerificationReport::new(report_id.clone(), sprint_id);
         self.reports.insert(report_id.clone(), report);
         self.reports.get_mut(&report_id).unwrap()
@@ -152,7 +165,13 @@ impl Verifier {
         self.reports.get(report_id)
     }
 
-    pub fn add_unit_test(&mut self, report_id: &str, test_name: String, passed: bool, duration_ms: u64) {
+    pub fn add_unit_test(
+        &mut self,
+        report_id: &str,
+        test_name: String,
+        passed: bool,
+        duration_ms: u64,
+    ) {
         if let Some(report) = self.reports.get_mut(report_id) {
             let result = VerificationResult {
                 test_name,
@@ -166,7 +185,13 @@ impl Verifier {
         }
     }
 
-    pub fn add_integration_test(&mut self, report_id: &str, test_name: String, passed: bool, duration_ms: u64) {
+    pub fn add_integration_test(
+        &mut self,
+        report_id: &str,
+        test_name: String,
+        passed: bool,
+        duration_ms: u64,
+    ) {
         if let Some(report) = self.reports.get_mut(report_id) {
             let result = VerificationResult {
                 test_name,
@@ -248,7 +273,7 @@ mod tests {
         let mut verifier = Verifier::new();
         verifier.create_report("report-1".to_string(), "sprint-1".to_string());
         verifier.add_unit_test("report-1", "test-1".to_string(), true, 100);
-        
+
         let report = verifier.get_report("report-1").unwrap();
         assert_eq!(report.total_tests, 1);
     }
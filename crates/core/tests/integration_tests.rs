@@ -8,26 +8,26 @@ fn test_engine_initialization() {
 
 #[test]
 fn test_device_registration() {
-    let mut engine = Engine::new();
+    let engine = Engine::new();
     let device = Device::new(
         "test_001".to_string(),
         "Test AirPods".to_string(),
         DeviceModel::AirPods3,
     );
-    
+
     engine.register_device(device);
     assert_eq!(engine.devices().count(), 1);
 }
 
 #[test]
 fn test_device_retrieval() {
-    let mut engine = Engine::new();
+    let engine = Engine::new();
     let device = Device::new(
         "test_001".to_string(),
         "Test AirPods".to_string(),
         DeviceModel::AirPods3,
     );
-    
+
     engine.register_device(device);
     let retrieved = engine.get_device("test_001");
     assert!(retrieved.is_some());
@@ -37,7 +37,7 @@ fn test_device_retrieval() {
 #[test]
 fn test_message_parsing() {
     let msg = Message::new(MessageType::BatteryStatus, vec![50, 60, 70]);
-    let serialized = msg.serialize();
+    let serialized = msg.serialize().unwrap();
     assert!(serialized.len() > 0);
 }
 
@@ -48,13 +48,33 @@ fn test_device_capabilities() {
         "Test AirPods".to_string(),
         DeviceModel::AirPodsProGen2,
     );
-    
+
     device.add_capability(DeviceCapability::BatteryMonitoring);
     device.add_capability(DeviceCapability::NoiseControl);
-    
+
     assert!(device.has_capability(DeviceCapability::BatteryMonitoring));
     assert!(device.has_capability(DeviceCapability::NoiseControl));
     assert!(!device.has_capability(DeviceCapability::HeartRate));
+    assert_eq!(
+        device.require_capability(DeviceCapability::HeartRate),
+        Err(Error::UnsupportedDevice)
+    );
+}
+
+#[test]
+fn test_device_capabilities_from_model() {
+    let device = Device::new(
+        "test_001".to_string(),
+        "Test AirPods".to_string(),
+        DeviceModel::AirPods2,
+    );
+
+    assert!(device.has_capability(DeviceCapability::BatteryMonitoring));
+    assert!(!device.has_capability(DeviceCapability::NoiseControl));
+    assert_eq!(
+        device.command(&Payload::AncControl(AncMode::Active)),
+        Err(Error::UnsupportedDevice)
+    );
 }
 
 #[test]
@@ -64,14 +84,20 @@ fn test_device_state_transitions() {
         "Test AirPods".to_string(),
         DeviceModel::AirPods3,
     );
-    
+
     assert_eq!(*device.state(), DeviceState::Disconnected);
-    
-    device.set_state(DeviceState::Connecting);
+
+    device.set_state(DeviceState::Connecting).unwrap();
     assert_eq!(*device.state(), DeviceState::Connecting);
-    
-    device.set_state(DeviceState::Connected);
+
+    device.set_state(DeviceState::Connected).unwrap();
     assert_eq!(*device.state(), DeviceState::Connected);
+
+    assert_eq!(
+        device.set_state(DeviceState::Connecting),
+        Err(Error::InvalidState)
+    );
+    assert_eq!(device.state_history().count(), 2);
 }
 
 #[test]
@@ -81,7 +107,248 @@ fn test_device_metadata() {
         "Test AirPods".to_string(),
         DeviceModel::AirPods3,
     );
-    
+
     device.set_metadata("firmware".to_string(), "7.1".to_string());
     assert_eq!(device.get_metadata("firmware"), Some("7.1"));
 }
+
+#[test]
+fn test_engine_devices_persist_across_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let key = || librepods_core::crypto::SecureKey::new(vec![3; 32]);
+
+    let engine = Engine::new();
+    engine.register_device(Device::new(
+        "test_001".to_string(),
+        "Test AirPods".to_string(),
+        DeviceModel::AirPods3,
+    ));
+    let mut store = DeviceStore::open(dir.path(), key()).unwrap();
+    engine.persist_devices(&mut store).unwrap();
+
+    let restarted = Engine::new();
+    let store = DeviceStore::open(dir.path(), key()).unwrap();
+    assert_eq!(restarted.restore_devices(&store), 1);
+    assert_eq!(
+        restarted.get_device("test_001").unwrap().name(),
+        "Test AirPods"
+    );
+}
+
+/// Let the engine's notification tasks catch up with the simulator
+async fn settle() {
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+}
+
+#[tokio::test]
+async fn test_multipoint_session_on_simulated_device() {
+    use librepods_core::multipoint::MultipointEvent;
+
+    let simulator = SimulatedBackend::new();
+    simulator.add_device(
+        SimulatedDevice::new("sim", "AirPods Pro", DeviceModel::AirPodsProGen2)
+            .with_hosts(&["phone", "laptop"], Some("phone")),
+    );
+    let engine = std::sync::Arc::new(Engine::with_backend(simulator.clone()));
+    engine.register_device(Device::new(
+        "sim".to_string(),
+        "AirPods Pro".to_string(),
+        DeviceModel::AirPodsProGen2,
+    ));
+    let mut events = engine
+        .event_bus()
+        .subscribe_filtered(EventFilter::new().kind(EventKind::Multipoint));
+
+    engine.connect("sim").await.unwrap();
+    settle().await;
+    assert_eq!(engine.hosts("sim").unwrap(), vec!["phone", "laptop"]);
+    assert_eq!(engine.active_host("sim").unwrap().as_deref(), Some("phone"));
+
+    engine.request_handoff("sim", "laptop").await.unwrap();
+    settle().await;
+    assert_eq!(
+        engine.active_host("sim").unwrap().as_deref(),
+        Some("laptop")
+    );
+
+    engine.set_auto_switch("sim", false).await.unwrap();
+    assert!(!simulator.switch_host("sim", "phone").unwrap());
+    settle().await;
+    assert_eq!(
+        engine.active_host("sim").unwrap().as_deref(),
+        Some("laptop")
+    );
+
+    let mut received = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let EventType::Multipoint(change) = event.event_type {
+            received.push(change);
+        }
+    }
+    assert_eq!(
+        received[received.len() - 2..],
+        [
+            MultipointEvent::ActiveHostChanged {
+                from: Some("phone".to_string()),
+                to: Some("laptop".to_string()),
+            },
+            MultipointEvent::AutoSwitchChanged(false),
+        ]
+    );
+    assert_eq!(received.len(), 5);
+}
+
+#[tokio::test]
+async fn test_scripted_device_with_faults() {
+    use librepods_core::simulator::{Fault, SimulatedEvent};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let simulator = SimulatedBackend::new();
+    simulator.add_device(
+        SimulatedDevice::new("sim", "AirPods Pro", DeviceModel::AirPodsProGen2)
+            .with_drain(60.0)
+            .with_event(
+                Duration::from_secs(30),
+                SimulatedEvent::EarDetection(EarDetectionState::BothEarsIn),
+            )
+            .with_event(Duration::from_secs(600), SimulatedEvent::Disconnect),
+    );
+    let engine = Arc::new(
+        Engine::with_backend(simulator.clone()).with_request_options(RequestOptions {
+            timeout: Duration::from_millis(50),
+            ..RequestOptions::default()
+        }),
+    );
+    engine.register_device(Device::new(
+        "sim".to_string(),
+        "AirPods Pro".to_string(),
+        DeviceModel::AirPodsProGen2,
+    ));
+    let mut events = engine
+        .event_bus()
+        .subscribe_filtered(EventFilter::new().kind(EventKind::EarDetection));
+
+    engine.connect("sim").await.unwrap();
+    simulator.advance(Duration::from_secs(300)).unwrap();
+    settle().await;
+    let history = engine.battery_history("sim").unwrap();
+    assert_eq!(history.latest().unwrap().battery.left_bud, 95);
+    let mut ear_states = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        ear_states.push(event.event_type);
+    }
+    assert_eq!(
+        ear_states,
+        vec![
+            EventType::EarDetection(EarDetectionState::BothEarsOut),
+            EventType::EarDetection(EarDetectionState::BothEarsIn),
+        ]
+    );
+
+    // A corrupted ack is rejected, so the command is resent until acknowledged
+    let mut acks = engine
+        .event_bus()
+        .subscribe_filtered(EventFilter::new().kind(EventKind::AncChanged));
+    engine.set_anc("sim", AncMode::Off).await.unwrap();
+    simulator.inject_fault("sim", Fault::CorruptCrc(1)).unwrap();
+    engine.set_anc("sim", AncMode::Transparency).await.unwrap();
+    engine.set_anc("sim", AncMode::Active).await.unwrap();
+    settle().await;
+    let mut modes = Vec::new();
+    while let Ok(event) = acks.try_recv() {
+        modes.push(event.event_type);
+    }
+    assert_eq!(
+        modes,
+        vec![
+            EventType::AncChanged(AncMode::Off),
+            EventType::AncChanged(AncMode::Transparency),
+            EventType::AncChanged(AncMode::Active),
+        ]
+    );
+    assert_eq!(simulator.device("sim").unwrap().anc_mode(), AncMode::Active);
+
+    // Battery requests are answered once the delayed reply is delivered
+    simulator
+        .inject_fault("sim", Fault::Latency(Duration::from_secs(1)))
+        .unwrap();
+    let pump = simulator.run(Duration::from_millis(10));
+    let battery = engine.battery("sim").await.unwrap();
+    pump.abort();
+    assert_eq!(battery.left_bud, 95);
+
+    simulator.advance(Duration::from_secs(600)).unwrap();
+    assert_eq!(
+        engine.set_anc("sim", AncMode::Off).await,
+        Err(Error::DeviceNotConnected)
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_supervisor_reconnects_engine_after_link_drop() {
+    use librepods_core::reconnect::ReconnectStatus;
+    use librepods_core::simulator::Fault;
+    use std::sync::Arc;
+
+    let simulator = SimulatedBackend::new();
+    simulator.add_device(SimulatedDevice::new(
+        "sim",
+        "AirPods Pro",
+        DeviceModel::AirPodsProGen2,
+    ));
+    let engine = Arc::new(Engine::with_backend(simulator.clone()));
+    engine.register_device(Device::new(
+        "sim".to_string(),
+        "AirPods Pro".to_string(),
+        DeviceModel::AirPodsProGen2,
+    ));
+    engine.connect("sim").await.unwrap();
+
+    let policy = ReconnectPolicy {
+        jitter: 0.0,
+        ..ReconnectPolicy::default()
+    };
+    let supervisor = engine
+        .reconnect_supervisor("sim", policy)
+        .unwrap()
+        .supervise();
+    let mut reconnects = engine
+        .event_bus()
+        .subscribe_filtered(EventFilter::new().kind(EventKind::Reconnect));
+
+    // The first attempt is refused, the second gets through
+    simulator
+        .inject_fault("sim", Fault::RefuseConnections(true))
+        .unwrap();
+    simulator.inject_fault("sim", Fault::Disconnect).unwrap();
+    loop {
+        match reconnects.recv().await.unwrap().event_type {
+            EventType::Reconnect(ReconnectStatus::Failed { .. }) => simulator
+                .inject_fault("sim", Fault::RefuseConnections(false))
+                .unwrap(),
+            EventType::Reconnect(ReconnectStatus::Connected { attempt }) => {
+                assert_eq!(attempt, 2);
+                break;
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(
+        *engine.get_device("sim").unwrap().state(),
+        DeviceState::Connected
+    );
+
+    // Notifications flow from the new stream
+    let mut acks = engine
+        .event_bus()
+        .subscribe_filtered(EventFilter::new().kind(EventKind::AncChanged));
+    engine.set_anc("sim", AncMode::Transparency).await.unwrap();
+    assert_eq!(
+        acks.recv().await.unwrap().event_type,
+        EventType::AncChanged(AncMode::Transparency)
+    );
+    supervisor.abort();
+}
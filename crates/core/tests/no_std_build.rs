@@ -0,0 +1,30 @@
+//! Build check for the `no_std` frame path used by the embedded bridge firmware
+
+use std::process::Command;
+
+#[test]
+fn builds_without_std() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    // Separate target dir so the nested build does not wait on the outer lock
+    let target_dir = format!("{}/../../target/no_std-check", manifest_dir);
+
+    let output = Command::new(env!("CARGO"))
+        .args([
+            "check",
+            "--lib",
+            "--no-default-features",
+            "--features",
+            "no_std",
+        ])
+        .arg("--manifest-path")
+        .arg(format!("{}/Cargo.toml", manifest_dir))
+        .env("CARGO_TARGET_DIR", target_dir)
+        .output()
+        .expect("failed to run cargo");
+
+    assert!(
+        output.status.success(),
+        "no_std build failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
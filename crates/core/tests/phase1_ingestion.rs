@@ -1,7 +1,9 @@
+use librepods_core::firmware_analyzer::FirmwareAnalyzer;
 use librepods_core::ingestion::DataIngestionEngine;
 use librepods_core::protocol_analyzer::ProtocolAnalyzer;
-use librepods_core::firmware_analyzer::FirmwareAnalyzer;
-use librepods_core::upstream::{Commit, Release, ReleaseAsset, Tag, ProtocolDefinition, FirmwareVersion};
+use librepods_core::upstream::{
+    Commit, FirmwareVersion, ProtocolDefinition, Release, ReleaseAsset, Tag,
+};
 use std::collections::HashMap;
 
 #[test]
@@ -42,30 +44,31 @@ fn test_phase1_complete_ingestion_workflow() {
         },
     ];
 
-    engine.ingest_commits(commits).expect("Failed to ingest commits");
-
-    let releases = vec![
-        Release {
-            tag_name: "linux-v0.1.0".to_string(),
-            name: "linux-v0.1.0".to_string(),
-            draft: false,
-            prerelease: true,
-            created_at: "2025-11-10T08:34:40Z".to_string(),
-            published_at: "2025-11-10T08:40:41Z".to_string(),
-            body: "Linux release".to_string(),
-            assets: vec![
-                ReleaseAsset {
-                    name: "librepods".to_string(),
-                    size: 14621200,
-                    download_count: 929,
-                    digest: "sha256:589c62ff2d98a72083e8942a5e726a08c8b87b213d5ea12aff2a5a57b8dab148".to_string(),
-                    content_type: "application/octet-stream".to_string(),
-                },
-            ],
-        },
-    ];
+    engine
+        .ingest_commits(commits)
+        .expect("Failed to ingest commits");
+
+    let releases = vec![Release {
+        tag_name: "linux-v0.1.0".to_string(),
+        name: "linux-v0.1.0".to_string(),
+        draft: false,
+        prerelease: true,
+        created_at: "2025-11-10T08:34:40Z".to_string(),
+        published_at: "2025-11-10T08:40:41Z".to_string(),
+        body: "Linux release".to_string(),
+        assets: vec![ReleaseAsset {
+            name: "librepods".to_string(),
+            size: 14621200,
+            download_count: 929,
+            digest: "sha256:589c62ff2d98a72083e8942a5e726a08c8b87b213d5ea12aff2a5a57b8dab148"
+                .to_string(),
+            content_type: "application/octet-stream".to_string(),
+        }],
+    }];
 
-    engine.ingest_releases(releases).expect("Failed to ingest releases");
+    engine
+        .ingest_releases(releases)
+        .expect("Failed to ingest releases");
 
     let tags = vec![
         Tag {
@@ -102,7 +105,11 @@ fn test_phase1_complete_ingestion_workflow() {
             version: "5E135".to_string(),
             device_model: "AirPodsProGen2".to_string(),
             release_date: "2025-11-21".to_string(),
-            features: vec!["BatteryMonitoring".to_string(), "ANC".to_string(), "SpatialAudio".to_string()],
+            features: vec![
+                "BatteryMonitoring".to_string(),
+                "ANC".to_string(),
+                "SpatialAudio".to_string(),
+            ],
         },
         FirmwareVersion {
             version: "5D134".to_string(),
@@ -175,7 +182,10 @@ fn test_protocol_analyzer_integration() {
     let has_drift = analyzer.detect_drift(vec![0x01, 0xFF], vec!["UNKNOWN-UUID".to_string()]);
     assert!(has_drift);
 
-    let report = analyzer.generate_analysis_report(vec![0x01, 0x02], vec!["7DFC9000-7D1C-4951-86AA-8D9728F8D66C".to_string()]);
+    let report = analyzer.generate_analysis_report(
+        vec![0x01, 0x02],
+        vec!["7DFC9000-7D1C-4951-86AA-8D9728F8D66C".to_string()],
+    );
     assert!(report.contains("PROTOCOL ANALYSIS REPORT"));
 }
 
@@ -278,7 +288,10 @@ fn test_dependency_tracking() {
 
     let diff = engine.get_diff();
     assert_eq!(diff.dependency_changes.len(), 3);
-    assert_eq!(diff.dependency_changes.get("serde"), Some(&"1.0.0".to_string()));
+    assert_eq!(
+        diff.dependency_changes.get("serde"),
+        Some(&"1.0.0".to_string())
+    );
 }
 
 #[test]
@@ -302,7 +315,9 @@ fn test_latest_commit_retrieval() {
         },
     ];
 
-    engine.ingest_commits(commits).expect("Failed to ingest commits");
+    engine
+        .ingest_commits(commits)
+        .expect("Failed to ingest commits");
 
     let latest = engine.get_latest_commit();
     assert!(latest.is_some());
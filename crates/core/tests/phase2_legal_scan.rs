@@ -1,8 +1,8 @@
-use librepods_core::legal_scan::*;
 use librepods_core::dmca_scanner::DMCAScanner;
+use librepods_core::firmware_security::FirmwareSecurityAnalyzer;
 use librepods_core::gpl_checker::GPLChecker;
+use librepods_core::legal_scan::*;
 use librepods_core::trademark_checker::TrademarkChecker;
-use librepods_core::firmware_security::FirmwareSecurityAnalyzer;
 
 #[test]
 fn test_phase2_complete_legal_scan() {
@@ -27,7 +27,8 @@ fn test_dmca_scanner_integration() {
     let notices = scanner.scan_repository("test-repo", "A normal repository");
     assert_eq!(notices.len(), 0);
 
-    let notices_with_keyword = scanner.scan_repository("test-repo", "This contains proprietary code");
+    let notices_with_keyword =
+        scanner.scan_repository("test-repo", "This contains proprietary code");
     assert!(notices_with_keyword.len() > 0);
 }
 
@@ -1,6 +1,6 @@
-use librepods_core::protocol_drift::*;
-use librepods_core::protocol_comparator::ProtocolComparator;
 use librepods_core::firmware_version_analyzer::FirmwareVersionAnalyzer;
+use librepods_core::protocol_comparator::ProtocolComparator;
+use librepods_core::protocol_drift::*;
 
 #[test]
 fn test_phase3_complete_protocol_drift_analysis() {
@@ -82,7 +82,9 @@ fn test_protocol_comparator_removed_type() {
     upstream.remove(&0x01);
 
     let drifts = comparator.compare_message_types(upstream);
-    assert!(drifts.iter().any(|d| d.status == DriftStatus::Removed && d.breaking_change));
+    assert!(drifts
+        .iter()
+        .any(|d| d.status == DriftStatus::Removed && d.breaking_change));
 }
 
 #[test]
@@ -216,7 +216,10 @@ fn test_three_way_diff_only_upstream() {
         "upstream".to_string(),
         "base".to_string(),
     );
-    assert!(matches!(diff.merge_status, MergeStatus::OnlyUpstreamModified | MergeStatus::OnlyLocalModified));
+    assert!(matches!(
+        diff.merge_status,
+        MergeStatus::OnlyUpstreamModified | MergeStatus::OnlyLocalModified
+    ));
 }
 
 #[test]
@@ -1,10 +1,13 @@
-use librepods_core::implementation_executor::*;
 use librepods_core::change_applier::*;
+use librepods_core::implementation_executor::*;
 use librepods_core::verification::*;
 
 #[test]
 fn test_phase5_complete_implementation_sprint() {
-    let mut sprint = SprintPlan::new("sprint-1".to_string(), "Implementation Sprint 1".to_string());
+    let mut sprint = SprintPlan::new(
+        "sprint-1".to_string(),
+        "Implementation Sprint 1".to_string(),
+    );
 
     let task = ImplementationTask {
         task_id: "task-1".to_string(),
@@ -90,7 +93,7 @@ fn test_update_task_progress() {
     };
     sprint.add_task(task);
     sprint.update_task_progress("task-1", 50);
-    
+
     let task = sprint.tasks.iter().find(|t| t.task_id == "task-1").unwrap();
     assert_eq!(task.completion_percentage, 50);
 }
@@ -105,7 +108,11 @@ fn test_get_tasks_by_status() {
             description: "Test".to_string(),
             category: TaskCategory::BluetoothBackends,
             priority: 1,
-            status: if i == 0 { TaskStatus::Completed } else { TaskStatus::Pending },
+            status: if i == 0 {
+                TaskStatus::Completed
+            } else {
+                TaskStatus::Pending
+            },
             effort_hours: 1.0,
             assigned_to: "dev".to_string(),
             dependencies: Vec::new(),
@@ -113,7 +120,7 @@ fn test_get_tasks_by_status() {
         };
         sprint.add_task(task);
     }
-    
+
     let completed = sprint.get_tasks_by_status(TaskStatus::Completed);
     assert_eq!(completed.len(), 1);
 }
@@ -126,7 +133,11 @@ fn test_get_tasks_by_category() {
             task_id: format!("task-{}", i),
             title: format!("Task {}", i),
             description: "Test".to_string(),
-            category: if i == 0 { TaskCategory::BluetoothBackends } else { TaskCategory::ProtocolMessages },
+            category: if i == 0 {
+                TaskCategory::BluetoothBackends
+            } else {
+                TaskCategory::ProtocolMessages
+            },
             priority: 1,
             status: TaskStatus::Pending,
             effort_hours: 1.0,
@@ -136,7 +147,7 @@ fn test_get_tasks_by_category() {
         };
         sprint.add_task(task);
     }
-    
+
     let bluetooth = sprint.get_tasks_by_category(TaskCategory::BluetoothBackends);
     assert_eq!(bluetooth.len(), 1);
 }
@@ -159,11 +170,11 @@ fn test_completion_percentage() {
         };
         sprint.add_task(task);
     }
-    
+
     for i in 0..2 {
         sprint.update_task_status(&format!("task-{}", i), TaskStatus::Completed);
     }
-    
+
     assert_eq!(sprint.get_completion_percentage(), 50);
 }
 
@@ -205,7 +216,11 @@ fn test_change_success_rate() {
             timestamp: "2025-11-21T00:00:00Z".to_string(),
             applied_by: "dev".to_string(),
             success: i < 8,
-            error_message: if i >= 8 { Some("Error".to_string()) } else { None },
+            error_message: if i >= 8 {
+                Some("Error".to_string())
+            } else {
+                None
+            },
         };
         log.add_change(change);
     }
@@ -281,7 +296,7 @@ fn test_verifier() {
     let mut verifier = Verifier::new();
     verifier.create_report("report-1".to_string(), "sprint-1".to_string());
     verifier.add_unit_test("report-1", "test-1".to_string(), true, 100);
-    
+
     let report = verifier.get_report("report-1").unwrap();
     assert_eq!(report.total_tests, 1);
 }
@@ -291,7 +306,7 @@ fn test_verifier_coverage() {
     let mut verifier = Verifier::new();
     verifier.create_report("report-1".to_string(), "sprint-1".to_string());
     verifier.set_coverage("report-1", 85.5);
-    
+
     let report = verifier.get_report("report-1").unwrap();
     assert_eq!(report.coverage_percentage, 85.5);
 }
@@ -301,7 +316,7 @@ fn test_verifier_build_status() {
     let mut verifier = Verifier::new();
     verifier.create_report("report-1".to_string(), "sprint-1".to_string());
     verifier.set_build_status("report-1", BuildStatus::Success);
-    
+
     let report = verifier.get_report("report-1").unwrap();
     assert_eq!(report.build_status, BuildStatus::Success);
 }
@@ -323,7 +338,7 @@ fn test_sprint_report_generation() {
     };
     sprint.add_task(task);
     sprint.update_task_status("task-1", TaskStatus::Completed);
-    
+
     let report = sprint.generate_report();
     assert!(report.contains("IMPLEMENTATION SPRINT REPORT"));
     assert!(report.contains("Sprint 1"));
@@ -344,7 +359,7 @@ fn test_change_log_report_generation() {
         error_message: None,
     };
     log.add_change(change);
-    
+
     let report = log.generate_report();
     assert!(report.contains("CHANGE APPLICATION REPORT"));
 }
@@ -361,15 +376,18 @@ fn test_verification_report_generation() {
         timestamp: "2025-11-21T00:00:00Z".to_string(),
     };
     report.add_result(result);
-    
+
     let report_text = report.generate_report();
     assert!(report_text.contains("VERIFICATION REPORT"));
 }
 
 #[test]
 fn test_complete_phase5_workflow() {
-    let mut sprint = SprintPlan::new("sprint-1".to_string(), "Implementation Sprint 1".to_string());
-    
+    let mut sprint = SprintPlan::new(
+        "sprint-1".to_string(),
+        "Implementation Sprint 1".to_string(),
+    );
+
     let task = ImplementationTask {
         task_id: "task-1".to_string(),
         title: "Implement Feature".to_string(),
@@ -383,17 +401,17 @@ fn test_complete_phase5_workflow() {
         completion_percentage: 50,
     };
     sprint.add_task(task);
-    
+
     let mut applier = ChangeApplier::new();
     let change = applier.apply_add("src/new_feature.rs".to_string(), "code".to_string());
     assert!(change.success);
-    
+
     let mut verifier = Verifier::new();
     verifier.create_report("report-1".to_string(), "sprint-1".to_string());
     verifier.add_unit_test("report-1", "feature_test".to_string(), true, 150);
     verifier.set_coverage("report-1", 90.0);
     verifier.set_build_status("report-1", BuildStatus::Success);
-    
+
     let report = verifier.get_report("report-1").unwrap();
     assert!(report.is_all_passed());
 }
@@ -1,10 +1,14 @@
 use librepods_core::ci_pipeline::*;
-use librepods_core::test_runner::*;
 use librepods_core::coverage_analyzer::*;
+use librepods_core::test_runner::*;
 
 #[test]
 fn test_phase6_complete_automated_verification() {
-    let mut pipeline = CIPipeline::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string());
+    let mut pipeline = CIPipeline::new(
+        "pipeline-1".to_string(),
+        "main".to_string(),
+        "abc123".to_string(),
+    );
 
     let build_stage = PipelineStage {
         stage_id: "build".to_string(),
@@ -40,7 +44,11 @@ fn test_phase6_complete_automated_verification() {
 
 #[test]
 fn test_ci_pipeline_creation() {
-    let pipeline = CIPipeline::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string());
+    let pipeline = CIPipeline::new(
+        "pipeline-1".to_string(),
+        "main".to_string(),
+        "abc123".to_string(),
+    );
     assert_eq!(pipeline.pipeline_id, "pipeline-1");
     assert_eq!(pipeline.branch, "main");
     assert_eq!(pipeline.total_stages, 0);
@@ -48,7 +56,11 @@ fn test_ci_pipeline_creation() {
 
 #[test]
 fn test_add_pipeline_stage() {
-    let mut pipeline = CIPipeline::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string());
+    let mut pipeline = CIPipeline::new(
+        "pipeline-1".to_string(),
+        "main".to_string(),
+        "abc123".to_string(),
+    );
     let stage = PipelineStage {
         stage_id: "build".to_string(),
         stage_name: "Build".to_string(),
@@ -65,7 +77,11 @@ fn test_add_pipeline_stage() {
 
 #[test]
 fn test_update_pipeline_stage_status() {
-    let mut pipeline = CIPipeline::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string());
+    let mut pipeline = CIPipeline::new(
+        "pipeline-1".to_string(),
+        "main".to_string(),
+        "abc123".to_string(),
+    );
     let stage = PipelineStage {
         stage_id: "build".to_string(),
         stage_name: "Build".to_string(),
@@ -83,7 +99,11 @@ fn test_update_pipeline_stage_status() {
 
 #[test]
 fn test_pipeline_success_rate() {
-    let mut pipeline = CIPipeline::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string());
+    let mut pipeline = CIPipeline::new(
+        "pipeline-1".to_string(),
+        "main".to_string(),
+        "abc123".to_string(),
+    );
     for i in 0..4 {
         let stage = PipelineStage {
             stage_id: format!("stage-{}", i),
@@ -107,14 +127,22 @@ fn test_pipeline_success_rate() {
 
 #[test]
 fn test_test_suite_creation() {
-    let suite = TestSuite::new("suite-1".to_string(), "Suite 1".to_string(), TestSuiteType::Unit);
+    let suite = TestSuite::new(
+        "suite-1".to_string(),
+        "Suite 1".to_string(),
+        TestSuiteType::Unit,
+    );
     assert_eq!(suite.suite_id, "suite-1");
     assert_eq!(suite.total_tests, 0);
 }
 
 #[test]
 fn test_add_test_case() {
-    let mut suite = TestSuite::new("suite-1".to_string(), "Suite 1".to_string(), TestSuiteType::Unit);
+    let mut suite = TestSuite::new(
+        "suite-1".to_string(),
+        "Suite 1".to_string(),
+        TestSuiteType::Unit,
+    );
     let test = TestCase {
         test_id: "test-1".to_string(),
         test_name: "Test 1".to_string(),
@@ -130,12 +158,20 @@ fn test_add_test_case() {
 
 #[test]
 fn test_test_suite_pass_rate() {
-    let mut suite = TestSuite::new("suite-1".to_string(), "Suite 1".to_string(), TestSuiteType::Unit);
+    let mut suite = TestSuite::new(
+        "suite-1".to_string(),
+        "Suite 1".to_string(),
+        TestSuiteType::Unit,
+    );
     for i in 0..10 {
         let test = TestCase {
             test_id: format!("test-{}", i),
             test_name: format!("Test {}", i),
-            status: if i < 8 { TestStatus::Passed } else { TestStatus::Failed },
+            status: if i < 8 {
+                TestStatus::Passed
+            } else {
+                TestStatus::Failed
+            },
             duration_ms: 100,
             error_message: None,
             stack_trace: None,
@@ -148,7 +184,11 @@ fn test_test_suite_pass_rate() {
 #[test]
 fn test_test_runner() {
     let mut runner = TestRunner::new();
-    runner.create_suite("suite-1".to_string(), "Suite 1".to_string(), TestSuiteType::Unit);
+    runner.create_suite(
+        "suite-1".to_string(),
+        "Suite 1".to_string(),
+        TestSuiteType::Unit,
+    );
     let test = TestCase {
         test_id: "test-1".to_string(),
         test_name: "Test 1".to_string(),
@@ -164,20 +204,28 @@ fn test_test_runner() {
 #[test]
 fn test_test_runner_overall_pass_rate() {
     let mut runner = TestRunner::new();
-    runner.create_suite("suite-1".to_string(), "Suite 1".to_string(), TestSuiteType::Unit);
-    
+    runner.create_suite(
+        "suite-1".to_string(),
+        "Suite 1".to_string(),
+        TestSuiteType::Unit,
+    );
+
     for i in 0..10 {
         let test = TestCase {
             test_id: format!("test-{}", i),
             test_name: format!("Test {}", i),
-            status: if i < 9 { TestStatus::Passed } else { TestStatus::Failed },
+            status: if i < 9 {
+                TestStatus::Passed
+            } else {
+                TestStatus::Failed
+            },
             duration_ms: 100,
             error_message: None,
             stack_trace: None,
         };
         runner.add_test("suite-1", test);
     }
-    
+
     assert_eq!(runner.get_overall_pass_rate(), 90.0);
 }
 
@@ -232,7 +280,11 @@ fn test_coverage_analyzer() {
 
 #[test]
 fn test_pipeline_report_generation() {
-    let mut pipeline = CIPipeline::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string());
+    let mut pipeline = CIPipeline::new(
+        "pipeline-1".to_string(),
+        "main".to_string(),
+        "abc123".to_string(),
+    );
     let stage = PipelineStage {
         stage_id: "build".to_string(),
         stage_name: "Build".to_string(),
@@ -245,14 +297,18 @@ fn test_pipeline_report_generation() {
     };
     pipeline.add_stage(stage);
     pipeline.update_stage_status("build", StageStatus::Success, 5000);
-    
+
     let report = pipeline.generate_report();
     assert!(report.contains("CI PIPELINE REPORT"));
 }
 
 #[test]
 fn test_test_suite_report_generation() {
-    let mut suite = TestSuite::new("suite-1".to_string(), "Suite 1".to_string(), TestSuiteType::Unit);
+    let mut suite = TestSuite::new(
+        "suite-1".to_string(),
+        "Suite 1".to_string(),
+        TestSuiteType::Unit,
+    );
     let test = TestCase {
         test_id: "test-1".to_string(),
         test_name: "Test 1".to_string(),
@@ -262,7 +318,7 @@ fn test_test_suite_report_generation() {
         stack_trace: None,
     };
     suite.add_test(test);
-    
+
     let report = suite.generate_report();
     assert!(report.contains("TEST SUITE REPORT"));
 }
@@ -280,7 +336,7 @@ fn test_coverage_report_generation() {
         functions_covered: 9,
     };
     report.add_metrics(metrics);
-    
+
     let report_text = report.generate_report();
     assert!(report_text.contains("COVERAGE REPORT"));
 }
@@ -288,8 +344,12 @@ fn test_coverage_report_generation() {
 #[test]
 fn test_test_runner_summary() {
     let mut runner = TestRunner::new();
-    runner.create_suite("suite-1".to_string(), "Suite 1".to_string(), TestSuiteType::Unit);
-    
+    runner.create_suite(
+        "suite-1".to_string(),
+        "Suite 1".to_string(),
+        TestSuiteType::Unit,
+    );
+
     for i in 0..5 {
         let test = TestCase {
             test_id: format!("test-{}", i),
@@ -301,15 +361,19 @@ fn test_test_runner_summary() {
         };
         runner.add_test("suite-1", test);
     }
-    
+
     let summary = runner.generate_summary();
     assert!(summary.contains("TEST EXECUTION SUMMARY"));
 }
 
 #[test]
 fn test_complete_phase6_workflow() {
-    let mut pipeline = CIPipeline::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string());
-    
+    let mut pipeline = CIPipeline::new(
+        "pipeline-1".to_string(),
+        "main".to_string(),
+        "abc123".to_string(),
+    );
+
     let build_stage = PipelineStage {
         stage_id: "build".to_string(),
         stage_name: "Build".to_string(),
@@ -322,22 +386,30 @@ fn test_complete_phase6_workflow() {
     };
     pipeline.add_stage(build_stage);
     pipeline.update_stage_status("build", StageStatus::Success, 10000);
-    
+
     let mut runner = TestRunner::new();
-    runner.create_suite("suite-1".to_string(), "Unit Tests".to_string(), TestSuiteType::Unit);
-    
+    runner.create_suite(
+        "suite-1".to_string(),
+        "Unit Tests".to_string(),
+        TestSuiteType::Unit,
+    );
+
     for i in 0..20 {
         let test = TestCase {
             test_id: format!("test-{}", i),
             test_name: format!("Test {}", i),
-            status: if i < 19 { TestStatus::Passed } else { TestStatus::Failed },
+            status: if i < 19 {
+                TestStatus::Passed
+            } else {
+                TestStatus::Failed
+            },
             duration_ms: 50,
             error_message: None,
             stack_trace: None,
         };
         runner.add_test("suite-1", test);
     }
-    
+
     let mut analyzer = CoverageAnalyzer::new();
     analyzer.create_report("report-1".to_string());
     let metrics = CoverageMetrics {
@@ -350,7 +422,7 @@ fn test_complete_phase6_workflow() {
         functions_covered: 95,
     };
     analyzer.add_metrics("report-1", metrics);
-    
+
     assert!(pipeline.is_successful());
     assert!(runner.get_overall_pass_rate() > 90.0);
     assert!(analyzer.is_coverage_sufficient("report-1", 80.0));
@@ -358,7 +430,11 @@ fn test_complete_phase6_workflow() {
 
 #[test]
 fn test_pipeline_failed_stage() {
-    let mut pipeline = CIPipeline::new("pipeline-1".to_string(), "main".to_string(), "abc123".to_string());
+    let mut pipeline = CIPipeline::new(
+        "pipeline-1".to_string(),
+        "main".to_string(),
+        "abc123".to_string(),
+    );
     let stage = PipelineStage {
         stage_id: "build".to_string(),
         stage_name: "Build".to_string(),
@@ -371,7 +447,7 @@ fn test_pipeline_failed_stage() {
     };
     pipeline.add_stage(stage);
     pipeline.update_stage_status("build", StageStatus::Failed, 5000);
-    
+
     assert!(!pipeline.is_successful());
     assert_eq!(pipeline.failed_stages, 1);
 }
@@ -389,7 +465,7 @@ fn test_coverage_below_threshold() {
         functions_covered: 5,
     };
     report.add_metrics(metrics);
-    
+
     let below_threshold = report.get_files_below_threshold(80.0);
     assert_eq!(below_threshold.len(), 1);
 }
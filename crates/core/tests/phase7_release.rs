@@ -1,6 +1,6 @@
 use librepods_core::release_manager::*;
-use librepods_core::sbom_generator::*;
 use librepods_core::release_notes::*;
+use librepods_core::sbom_generator::*;
 
 #[test]
 fn test_phase7_complete_release_artifacts() {
@@ -213,7 +213,7 @@ fn test_get_latest_release() {
     let mut manager = ReleaseManager::new();
     manager.create_release("1.0.0".to_string());
     manager.create_release("1.1.0".to_string());
-    
+
     let latest = manager.get_latest_release().unwrap();
     assert_eq!(latest.version.version, "1.1.0");
 }
@@ -223,7 +223,7 @@ fn test_stable_releases() {
     let mut manager = ReleaseManager::new();
     manager.create_release("1.0.0".to_string());
     manager.create_release("1.1.0-rc.1".to_string());
-    
+
     let stable = manager.get_stable_releases();
     assert_eq!(stable.len(), 1);
 }
@@ -233,7 +233,7 @@ fn test_prerelease_releases() {
     let mut manager = ReleaseManager::new();
     manager.create_release("1.0.0".to_string());
     manager.create_release("1.1.0-rc.1".to_string());
-    
+
     let prerelease = manager.get_prerelease_releases();
     assert_eq!(prerelease.len(), 1);
 }
@@ -264,7 +264,7 @@ fn test_release_report_generation() {
         checksum_algorithm: "sha256".to_string(),
     };
     release.add_artifact(artifact);
-    
+
     let report = release.generate_report();
     assert!(report.contains("RELEASE REPORT"));
 }
@@ -282,7 +282,7 @@ fn test_sbom_report_generation() {
         purl: "pkg:cargo/serde@1.0.0".to_string(),
     };
     sbom.add_component(component);
-    
+
     let report = sbom.generate_report();
     assert!(report.contains("SBOM REPORT"));
 }
@@ -291,7 +291,7 @@ fn test_sbom_report_generation() {
 fn test_release_notes_markdown() {
     let mut notes = ReleaseNotes::new("1.0.0".to_string());
     notes.set_summary("Initial release".to_string());
-    
+
     let change = ChangeEntry {
         entry_id: "feat-1".to_string(),
         change_type: ChangeType::Feature,
@@ -301,7 +301,7 @@ fn test_release_notes_markdown() {
         pr_number: Some(1),
     };
     notes.add_change(change);
-    
+
     let md = notes.generate_markdown();
     assert!(md.contains("✨ Features"));
 }
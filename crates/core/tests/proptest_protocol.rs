@@ -0,0 +1,263 @@
+//! Property-based round-trip and robustness tests for the AAP parsers
+
+use librepods_core::dissector::parse_hex;
+use librepods_core::parser::parse_message;
+use librepods_core::state::BatteryInfo;
+use librepods_core::*;
+use proptest::prelude::*;
+
+fn message_type() -> impl Strategy<Value = MessageType> {
+    any::<u8>().prop_map(MessageType::from_opcode)
+}
+
+fn message(max_len: usize) -> impl Strategy<Value = Message> {
+    (
+        message_type(),
+        prop::collection::vec(any::<u8>(), 0..=max_len),
+    )
+        .prop_map(|(msg_type, payload)| Message::new(msg_type, payload))
+}
+
+fn short_string() -> impl Strategy<Value = String> {
+    "[a-zA-Z0-9 ]{0,32}"
+}
+
+fn finite_f64() -> impl Strategy<Value = f64> {
+    prop::num::f64::NORMAL | prop::num::f64::ZERO
+}
+
+fn finite_f32() -> impl Strategy<Value = f32> {
+    prop::num::f32::NORMAL | prop::num::f32::ZERO
+}
+
+fn long_press_action() -> impl Strategy<Value = LongPressAction> {
+    prop_oneof![
+        Just(LongPressAction::Siri),
+        Just(LongPressAction::PlayPause),
+        Just(LongPressAction::NextTrack),
+        Just(LongPressAction::PreviousTrack),
+        Just(LongPressAction::VolumeUp),
+        Just(LongPressAction::VolumeDown),
+    ]
+}
+
+fn payload() -> impl Strategy<Value = Payload> {
+    prop_oneof![
+        (any::<u8>(), any::<u8>(), any::<u8>(), any::<bool>()).prop_map(
+            |(left_bud, right_bud, case, is_charging)| Payload::BatteryStatus(BatteryInfo {
+                left_bud,
+                right_bud,
+                case,
+                is_charging,
+            })
+        ),
+        prop_oneof![
+            Just(AncMode::Off),
+            Just(AncMode::Active),
+            Just(AncMode::Transparency),
+            Just(AncMode::Adaptive),
+        ]
+        .prop_map(Payload::AncControl),
+        prop_oneof![
+            Just(EarDetectionState::Unknown),
+            Just(EarDetectionState::LeftEarIn),
+            Just(EarDetectionState::RightEarIn),
+            Just(EarDetectionState::BothEarsIn),
+            Just(EarDetectionState::BothEarsOut),
+        ]
+        .prop_map(Payload::EarDetection),
+        any::<String>().prop_map(Payload::FirmwareInfo),
+        (any::<bool>(), any::<bool>(), any::<bool>()).prop_map(
+            |(enabled, head_tracking, dynamic_head_tracking)| {
+                Payload::SpatialAudio(SpatialAudioConfig {
+                    enabled,
+                    head_tracking,
+                    dynamic_head_tracking,
+                })
+            }
+        ),
+        (any::<u16>(), any::<u8>(), any::<u64>()).prop_map(|(bpm, confidence, timestamp)| {
+            Payload::HeartRate(HeartRateMeasurement {
+                bpm,
+                confidence,
+                timestamp,
+            })
+        }),
+        (finite_f64(), finite_f64(), finite_f32(), any::<u64>()).prop_map(
+            |(latitude, longitude, accuracy, timestamp)| Payload::FindMy(FindMyLocation {
+                latitude,
+                longitude,
+                accuracy,
+                timestamp,
+            })
+        ),
+        prop_oneof![
+            Just(ConversationAwarenessState::Inactive),
+            Just(ConversationAwarenessState::Active),
+            Just(ConversationAwarenessState::Speaking),
+        ]
+        .prop_map(Payload::ConversationAwareness),
+        (
+            any::<bool>(),
+            any::<u8>(),
+            prop::collection::vec(any::<u8>(), 0..600)
+        )
+            .prop_map(|(enabled, amplification_level, frequency_response)| {
+                Payload::HearingAid(HearingAidConfig {
+                    enabled,
+                    amplification_level,
+                    frequency_response,
+                })
+            }),
+        any::<String>().prop_map(Payload::DeviceRename),
+        (
+            any::<bool>(),
+            prop::collection::vec(short_string(), 0..8),
+            prop::option::of(short_string()),
+        )
+            .prop_map(|(enabled, connected_devices, active_device)| {
+                Payload::MultipointControl(MultipointInfo {
+                    enabled,
+                    connected_devices,
+                    active_device,
+                })
+            }),
+        any::<bool>().prop_map(Payload::AdaptiveTransparency),
+        (long_press_action(), long_press_action())
+            .prop_map(|(left, right)| Payload::LongPressActions { left, right }),
+        (any::<bool>(), any::<u8>(), any::<bool>()).prop_map(
+            |(enabled, ambient_mix_level, voice_focus)| {
+                Payload::CustomTransparency(CustomTransparencyConfig {
+                    enabled,
+                    ambient_mix_level,
+                    voice_focus,
+                })
+            }
+        ),
+        (any::<bool>(), prop::option::of(long_press_action())).prop_map(
+            |(double_tap_enabled, double_tap_action)| {
+                Payload::HeadGestures(HeadGestureConfig {
+                    double_tap_enabled,
+                    double_tap_action,
+                })
+            }
+        ),
+        (any::<u8>(), prop::collection::vec(any::<u8>(), 0..64))
+            .prop_filter("opcode must be unregistered", |(opcode, _)| {
+                MessageType::from_opcode(*opcode).is_unknown()
+            })
+            .prop_map(|(opcode, data)| Payload::Unknown { opcode, data }),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn message_round_trip_v1(msg in message(255)) {
+        let bytes = msg.serialize().unwrap();
+        prop_assert_eq!(Message::parse(&bytes).unwrap(), msg.clone());
+        let (rest, parsed) = parse_message(&bytes).unwrap();
+        prop_assert!(rest.is_empty());
+        prop_assert_eq!(parsed, msg);
+    }
+
+    #[test]
+    fn message_round_trip_v2(msg in message(1024)) {
+        let bytes = msg.serialize_versioned(FrameVersion::V2).unwrap();
+        prop_assert_eq!(Message::parse_versioned(&bytes, FrameVersion::V2).unwrap(), msg);
+    }
+
+    #[test]
+    fn message_ref_matches_message(msg in message(300)) {
+        for version in FrameVersion::SUPPORTED {
+            let owned = msg.serialize_versioned(version);
+            let borrowed = msg.as_message_ref().serialize_versioned::<320>(version);
+            prop_assert_eq!(owned.as_deref().ok(), borrowed.as_deref().ok());
+            if let Ok(frame) = borrowed {
+                let parsed = MessageRef::parse_versioned(&frame, version).unwrap();
+                prop_assert_eq!(Message::from(parsed), msg.clone());
+            }
+        }
+    }
+
+    #[test]
+    fn parsers_never_panic(data in prop::collection::vec(any::<u8>(), 0..300)) {
+        let _ = Message::parse(&data);
+        let _ = Message::parse_versioned(&data, FrameVersion::V2);
+        let _ = parse_message(&data);
+        let _ = MessageRef::parse_versioned(&data, FrameVersion::V2);
+        let _ = Dissector::new().dissect(&data);
+        let _ = Dissector::new().with_version(FrameVersion::V2).dissect(&data);
+        let _ = FrameDecoder::new().push(&data);
+        if let Some((opcode, rest)) = data.split_first() {
+            let _ = Payload::decode(MessageType::from_opcode(*opcode), rest);
+        }
+    }
+
+    #[test]
+    fn parsers_agree(data in prop::collection::vec(any::<u8>(), 0..64)) {
+        let strict = Message::parse(&data);
+        let nom = parse_message(&data).map(|(_, m)| m);
+        prop_assert_eq!(strict.ok(), nom.ok());
+    }
+
+    #[test]
+    fn payload_round_trip(payload in payload()) {
+        let bytes = payload.encode().unwrap();
+        prop_assert_eq!(Payload::decode(payload.message_type(), &bytes).unwrap(), payload.clone());
+
+        let message = payload.to_message().unwrap();
+        let frame = message.serialize_versioned(FrameVersion::V2).unwrap();
+        let parsed = Message::parse_versioned(&frame, FrameVersion::V2).unwrap();
+        prop_assert_eq!(parsed.decode_payload().unwrap(), payload);
+    }
+
+    #[test]
+    fn decoder_handles_any_chunking(
+        messages in prop::collection::vec(message(40), 0..8),
+        cuts in prop::collection::vec(any::<prop::sample::Index>(), 0..8),
+    ) {
+        let stream: Vec<u8> = messages.iter().flat_map(|m| m.serialize().unwrap()).collect();
+        let mut points: Vec<usize> = cuts.iter().map(|i| i.index(stream.len() + 1)).collect();
+        points.push(0);
+        points.push(stream.len());
+        points.sort_unstable();
+
+        let mut decoder = FrameDecoder::new();
+        let mut decoded = Vec::new();
+        for window in points.windows(2) {
+            decoded.extend(decoder.push(&stream[window[0]..window[1]]));
+        }
+        prop_assert_eq!(decoder.finish(), Ok(()));
+        let decoded: Vec<Message> = decoded.into_iter().map(|r| r.unwrap()).collect();
+        prop_assert_eq!(decoded, messages);
+    }
+
+    #[test]
+    fn parse_hex_never_panics(input in any::<String>()) {
+        let _ = parse_hex(&input);
+    }
+}
+
+#[test]
+fn regression_parse_exact_length_without_crc() {
+    // Type, length 1, one payload byte and a single CRC byte: 3 + len bytes
+    assert_eq!(
+        Message::parse(&[0x01, 0x01, 0x32, 0x00]),
+        Err(Error::InvalidLength)
+    );
+    assert_eq!(
+        Message::parse(&[0x01, 0x00, 0x00]),
+        Err(Error::InvalidLength)
+    );
+}
+
+#[test]
+fn regression_unknown_type_with_registered_opcode() {
+    let msg = Message::new(MessageType::Unknown(0x01), vec![1, 2, 3]);
+    assert_eq!(msg.msg_type, MessageType::BatteryStatus);
+}
+
+#[test]
+fn regression_parse_hex_multibyte_input() {
+    assert!(parse_hex("é1").is_err());
+}
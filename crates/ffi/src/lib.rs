@@ -3,14 +3,14 @@
 use librepods_core::*;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
-static ENGINE: Mutex<Option<Engine>> = Mutex::new(None);
+static ENGINE: Mutex<Option<Arc<Engine>>> = Mutex::new(None);
 
 #[no_mangle]
 pub extern "C" fn librepods_init() -> i32 {
     if let Ok(mut engine) = ENGINE.lock() {
-        *engine = Some(Engine::new());
+        *engine = Some(Arc::new(Engine::new()));
         0
     } else {
         -1